@@ -0,0 +1,881 @@
+use std::{fs, mem, path::PathBuf};
+
+use glyph_brush::OwnedSection;
+
+use winit::keyboard::{Key, ModifiersState, PhysicalKey};
+
+use crate::{
+    buffers::Buffers,
+    commands,
+    editor::{Editor, Indentation},
+    emacs::Emacs,
+    encoding::{Encoding, EncodingAction, EncodingPicker},
+    file_finder::FileFinder,
+    find::{FindBar, SearchQuery},
+    goto::GotoBar,
+    jumps::{BufferId, JumpList, Location},
+    keymap::{KeyPress, Keymap, Lookup},
+    macros::{Choice, MacroAction, MacroPicker, Recorder, Step, LAST_RECORDED},
+    marks::{MarkAction, MarkChoice, MarkEntry, MarkPicker},
+    palette::Palette,
+    project_search::ProjectSearch,
+    selection::Selection,
+    session::Session,
+    snippets::{self, Snippets},
+    text_document::TextDocument,
+    utils::RectVertex,
+    view::View,
+    vim::{self, Response, Stroke, Vim},
+};
+
+// everything the commands act on, kept apart from the window so keys can be fed in without one
+pub struct App {
+    pub editor: Editor,
+    pub buffers: Buffers,
+    pub keymap: Keymap,
+    pub session: Session,
+    pub find_bar: FindBar,
+    pub goto_bar: GotoBar,
+    pub file_finder: FileFinder,
+    pub palette: Palette,
+    pub macro_picker: MacroPicker,
+    pub mark_picker: MarkPicker,
+    pub encoding_picker: EncodingPicker,
+    pub recorder: Recorder,
+    pub snippets: Snippets,
+    pub jumps: JumpList,
+    // where the caret was when a bar that moves it while typing opened
+    jump_from: Option<Location>,
+    pub project_search: ProjectSearch,
+    pub vim: Vim,
+    pub emacs: Emacs,
+    // the system clipboard is optional, copy and paste stay within the editor without it
+    pub clipboard: Option<arboard::Clipboard>,
+    // command run by the previous key, kills right after kills add to them
+    pub last_command: Option<&'static str>,
+    // modifiers held, as last reported by the window
+    modifiers: ModifiersState,
+    // set by the commands that close the window
+    pub quit: bool,
+}
+
+impl App {
+    pub fn new(editor: Editor, indentation: Indentation, session: Session) -> Self {
+        Self {
+            editor,
+            buffers: Buffers::new(indentation),
+            keymap: Keymap::default(),
+            session,
+            find_bar: FindBar::default(),
+            goto_bar: GotoBar::default(),
+            file_finder: FileFinder::default(),
+            palette: Palette::default(),
+            macro_picker: MacroPicker::default(),
+            mark_picker: MarkPicker::default(),
+            encoding_picker: EncodingPicker::default(),
+            recorder: Recorder::default(),
+            snippets: Snippets::default(),
+            jumps: JumpList::default(),
+            jump_from: None,
+            project_search: ProjectSearch::default(),
+            vim: Vim::default(),
+            emacs: Emacs::default(),
+            last_command: None,
+            clipboard: None,
+            modifiers: ModifiersState::empty(),
+            quit: false,
+        }
+    }
+
+    // run the command with the id, false if there is none
+    pub fn run(&mut self, id: &str) -> bool {
+        match commands::find(id) {
+            Some(command) => {
+                (command.run)(self);
+                true
+            }
+            None => false,
+        }
+    }
+
+    // modal editing and macro recording, for the status bar
+    pub fn mode_status(&self) -> Option<String> {
+        let recording = self.recorder.is_recording().then(|| "REC".to_string());
+        match (self.vim.status(), recording) {
+            (Some(mode), Some(recording)) => Some(format!("{mode} {recording}")),
+            (mode, recording) => mode.or(recording),
+        }
+    }
+
+    pub fn modifiers(&self) -> ModifiersState {
+        self.modifiers
+    }
+
+    pub fn set_modifiers(&mut self, modifiers: ModifiersState) {
+        self.modifiers = modifiers;
+    }
+
+    pub fn handle_key(&mut self, key: &Key, physical: PhysicalKey) {
+        self.press(&Stroke::new(key.clone(), physical, self.modifiers));
+    }
+
+    // keys in the notation of vim::strokes, for driving the editor without a window
+    pub fn feed_keys(&mut self, notation: &str) {
+        for stroke in vim::strokes(notation) {
+            self.press(&stroke);
+        }
+    }
+
+    // overlays get the key first, then modal editing and the key bindings, anything else
+    // is typed
+    fn press(&mut self, stroke: &Stroke) {
+        let revision = self.editor.document.revision();
+        let from = self
+            .jump_from
+            .take()
+            .unwrap_or_else(|| Location::of(&self.editor));
+        self.last_command = self.route(stroke);
+        let to = Location::of(&self.editor);
+        let edited = from.buffer == to.buffer && self.editor.document.revision() != revision;
+        // an edit ends the region
+        if edited {
+            self.emacs.deactivate_mark();
+        }
+        // the caret follows the query of the bars, what they found is one jump when they close
+        if self.find_bar.is_open() || self.goto_bar.is_open() {
+            self.jump_from = Some(from);
+        } else if !edited {
+            self.jumps.moved(from, &to);
+        }
+    }
+
+    // the command the key ran through the key bindings, if any
+    fn route(&mut self, stroke: &Stroke) -> Option<&'static str> {
+        let (key, modifiers) = (&stroke.key, stroke.modifiers);
+        if self.palette.is_open()
+            && self
+                .palette
+                .handle_key(key, modifiers, &self.session.recent_commands)
+        {
+            if let Some(id) = self.palette.take_chosen() {
+                self.session.command_used(id);
+                self.save_session();
+                self.recorder.command(id);
+                self.run(id);
+            }
+            return None;
+        }
+        if self.macro_picker.is_open() && self.macro_picker.handle_key(key, modifiers) {
+            if let Some(choice) = self.macro_picker.take_chosen() {
+                self.macro_chosen(choice);
+            }
+            return None;
+        }
+        if self.mark_picker.is_open() && self.mark_picker.handle_key(key, modifiers) {
+            if let Some(choice) = self.mark_picker.take_chosen() {
+                self.mark_chosen(choice);
+            }
+            return None;
+        }
+        if self.encoding_picker.is_open() && self.encoding_picker.handle_key(key, modifiers) {
+            if let Some((action, encoding)) = self.encoding_picker.take_chosen() {
+                self.encoding_chosen(action, encoding);
+            }
+            return None;
+        }
+        if self.file_finder.is_open()
+            && self
+                .file_finder
+                .handle_key(&mut self.editor, &mut self.buffers, key, modifiers)
+        {
+            return None;
+        }
+        if self.goto_bar.is_open() && self.goto_bar.handle_key(&mut self.editor, key, modifiers) {
+            return None;
+        }
+        if self.find_bar.is_open() && self.find_bar.handle_key(&mut self.editor, key, modifiers) {
+            if let Some(query) = self.find_bar.take_submitted() {
+                self.search_project(&query);
+            }
+            return None;
+        }
+
+        let response = self.vim.handle_key(&mut self.editor, stroke);
+        if !matches!(response, Response::Pass) {
+            // recorded as the key, what it runs or repeats comes with it on playing
+            if let Some(keys) = vim::notation(stroke) {
+                self.recorder.keys(&keys);
+            }
+        }
+        match response {
+            Response::Pass => {}
+            Response::Handled => return None,
+            Response::Run(ids) => {
+                for id in ids {
+                    self.run(id);
+                }
+                return None;
+            }
+            Response::Repeat(strokes) => {
+                let playing = mem::replace(&mut self.recorder.playing, true);
+                for stroke in &strokes {
+                    self.press(stroke);
+                }
+                self.recorder.playing = playing;
+                return None;
+            }
+        }
+
+        match self
+            .keymap
+            .press(KeyPress::new(key, stroke.physical, modifiers))
+        {
+            Lookup::Command(id) => {
+                self.recorder.command(id);
+                self.run(id);
+                return Some(id);
+            }
+            Lookup::Pending(keys) => {
+                self.editor
+                    .set_message(format!("{keys} was pressed, waiting for the next key"));
+                return self.last_command;
+            }
+            Lookup::Unbound(keys) => self.editor.set_message(format!("{keys} is not bound")),
+            Lookup::Unmatched => {
+                if !modifiers.control_key() && !modifiers.super_key() {
+                    if let Some(text) = key.to_text() {
+                        self.recorder.insert(text);
+                        self.editor.type_text(text);
+                    }
+                }
+            }
+        }
+        None
+    }
+
+    // called once per frame for the work done in the background
+    pub fn poll(&mut self) {
+        self.project_search
+            .poll(&mut self.editor, &mut self.buffers);
+        self.file_finder.poll();
+        self.restore_folds();
+        self.restore_marks();
+    }
+
+    // the shown file as the session knows it
+    fn session_key(&self) -> Option<String> {
+        session_key(&self.editor)
+    }
+
+    // keep the folds of the shown file for the next run
+    pub fn remember_folds(&mut self) {
+        let Some(key) = self.session_key() else {
+            return;
+        };
+        let lines = self.editor.folds.lines(&self.editor.document);
+        let changed = match lines.is_empty() {
+            true => self.session.folds.remove(&key).is_some(),
+            false => self.session.folds.insert(key, lines.clone()) != Some(lines),
+        };
+        if changed {
+            self.save_session();
+        }
+    }
+
+    // the folds kept for a file come back the first time it is shown
+    fn restore_folds(&mut self) {
+        if self.editor.folds.restored {
+            return;
+        }
+        self.editor.folds.restored = true;
+        let kept = self
+            .session_key()
+            .and_then(|key| self.session.folds.get(&key).cloned());
+        if let Some(lines) = kept {
+            self.editor.restore_folds(&lines);
+        }
+    }
+
+    // keep the marks of the shown file for the next run
+    pub fn remember_marks(&mut self) {
+        let Some(key) = self.session_key().filter(|_| self.editor.marks.restored) else {
+            return;
+        };
+        let saved = self.editor.marks.saved(&self.editor.document);
+        let changed = match saved.is_empty() {
+            true => self.session.marks.remove(&key).is_some(),
+            false => self.session.marks.insert(key, saved.clone()) != Some(saved),
+        };
+        if changed {
+            self.save_session();
+        }
+    }
+
+    fn restore_marks(&mut self) {
+        if self.editor.marks.restored {
+            return;
+        }
+        self.editor.marks.restored = true;
+        let kept = self
+            .session_key()
+            .and_then(|key| self.session.marks.get(&key).cloned());
+        if let Some(saved) = kept {
+            self.editor.marks.restore(&self.editor.document, &saved);
+        }
+    }
+
+    pub fn toggle_bookmark(&mut self) {
+        let line = self.editor.caret_line();
+        let added = self
+            .editor
+            .marks
+            .toggle_bookmark(&self.editor.document, line);
+        let message = match added {
+            true => "Bookmark added",
+            false => "Bookmark removed",
+        };
+        self.editor.set_message(message);
+        self.remember_marks();
+    }
+
+    pub fn clear_bookmarks(&mut self) {
+        self.editor.marks.clear_bookmarks();
+        self.remember_marks();
+    }
+
+    // the bookmarks or named marks of the open buffers, then those kept for other files
+    fn mark_entries(&self, action: MarkAction) -> Vec<MarkEntry> {
+        let mut entries = Vec::new();
+        let mut open = Vec::new();
+        for editor in std::iter::once(&self.editor).chain(self.buffers.hidden()) {
+            let document = &editor.document;
+            let name = match (document.file_name(), document.title()) {
+                (Some(path), _) => path.display().to_string(),
+                (None, Some(title)) => title.to_string(),
+                (None, None) => "untitled".to_string(),
+            };
+            open.extend(session_key(editor));
+            match action {
+                MarkAction::Bookmarks => {
+                    for line in editor.marks.bookmarked_lines(document) {
+                        entries.push(MarkEntry {
+                            label: format!("{name}:{}  {}", line + 1, document.line(line).trim()),
+                            detail: None,
+                            location: Location::at(editor, document.line_start(line)),
+                        });
+                    }
+                }
+                MarkAction::GotoNamed | MarkAction::SetNamed => {
+                    for (mark, offset) in editor.marks.named_marks() {
+                        let location = Location::at(editor, offset);
+                        entries.push(MarkEntry {
+                            label: mark.to_string(),
+                            detail: Some(format!("{name}:{}", location.line + 1)),
+                            location,
+                        });
+                    }
+                }
+            }
+        }
+        for (key, saved) in &self.session.marks {
+            if open.contains(key) {
+                continue;
+            }
+            let location = |line, column| Location {
+                buffer: Some(BufferId::File(PathBuf::from(key))),
+                line,
+                column,
+            };
+            match action {
+                MarkAction::Bookmarks => {
+                    let text = fs::read_to_string(key).unwrap_or_default();
+                    let lines: Vec<&str> = text.lines().collect();
+                    for line in &saved.bookmarks {
+                        let preview = lines.get(*line).map_or("", |text| text.trim());
+                        entries.push(MarkEntry {
+                            label: format!("{key}:{}  {preview}", line + 1),
+                            detail: None,
+                            location: location(*line, 0),
+                        });
+                    }
+                }
+                MarkAction::GotoNamed | MarkAction::SetNamed => {
+                    for (mark, [line, column]) in &saved.named {
+                        entries.push(MarkEntry {
+                            label: mark.clone(),
+                            detail: Some(format!("{key}:{}", line + 1)),
+                            location: location(*line, *column),
+                        });
+                    }
+                }
+            }
+        }
+        entries
+    }
+
+    pub fn open_mark_picker(&mut self, action: MarkAction) {
+        let entries = self.mark_entries(action);
+        if action != MarkAction::SetNamed && entries.is_empty() {
+            self.editor.set_message(match action {
+                MarkAction::Bookmarks => "No bookmarks",
+                _ => "No marks",
+            });
+            return;
+        }
+        self.close_overlays();
+        self.mark_picker.open(action, entries);
+    }
+
+    fn mark_chosen(&mut self, choice: MarkChoice) {
+        match choice {
+            MarkChoice::Visit(location) => {
+                self.visit(&location);
+            }
+            // a name marks one place, wherever it was before
+            MarkChoice::Set(name) => {
+                for editor in self.buffers.hidden_mut() {
+                    editor.marks.remove_named(&name);
+                }
+                let key = self.session_key();
+                let mut changed = false;
+                for (file, saved) in self.session.marks.iter_mut() {
+                    if Some(file) != key.as_ref() {
+                        changed |= saved.named.remove(&name).is_some();
+                    }
+                }
+                self.session.marks.retain(|_, saved| !saved.is_empty());
+                if changed {
+                    self.save_session();
+                }
+                self.editor.marks.set_named(&name, self.editor.caret());
+                self.editor.set_message(format!("Set mark {name}"));
+                self.remember_marks();
+            }
+        }
+    }
+
+    pub fn open_encoding_picker(&mut self, action: EncodingAction) {
+        if self.editor.document.file_name().is_none() {
+            self.editor.set_message("The buffer has no file");
+            return;
+        }
+        self.close_overlays();
+        self.encoding_picker
+            .open(action, self.editor.document.encoding());
+    }
+
+    fn encoding_chosen(&mut self, action: EncodingAction, encoding: Encoding) {
+        let Some(path) = self.editor.document.file_name() else {
+            return;
+        };
+        let path = path.display().to_string();
+        match action {
+            // the text read in the wrong encoding would be lost
+            EncodingAction::Reopen if self.editor.document.is_dirty() => {
+                self.editor
+                    .set_message("Save or discard the changes before reopening");
+            }
+            EncodingAction::Reopen => match TextDocument::with_encoding(&path, encoding) {
+                Ok(document) => {
+                    self.remember_folds();
+                    self.remember_marks();
+                    self.editor.reload(document);
+                    self.editor.set_message(format!("Reopened as {encoding}"));
+                }
+                Err(e) => self.editor.set_message(format!("{path}: {e}")),
+            },
+            EncodingAction::Save => match encoding.encode(self.editor.document.text()) {
+                Ok(_) => {
+                    self.editor.document.set_encoding(encoding);
+                    self.save();
+                }
+                Err(e) => self.editor.set_message(e),
+            },
+        }
+    }
+
+    fn working_dir(&mut self) -> Option<PathBuf> {
+        std::env::current_dir()
+            .map_err(|e| self.editor.set_message(e.to_string()))
+            .ok()
+    }
+
+    fn search_project(&mut self, query: &SearchQuery) {
+        if let Some(root) = self.working_dir() {
+            self.project_search
+                .start(query, root, &mut self.editor, &mut self.buffers);
+        }
+    }
+
+    fn save_session(&mut self) {
+        if let Err(e) = self.session.save() {
+            self.editor.set_message(e.to_string());
+        }
+    }
+
+    // close the pickers at the top of the window, at most one of them is open
+    pub fn close_overlays(&mut self) {
+        self.palette.close();
+        self.file_finder.close();
+        self.macro_picker.close();
+        self.mark_picker.close();
+        self.encoding_picker.close();
+    }
+
+    // the texts and rects of the open picker, drawn over everything else
+    pub fn overlay(&self, view: &View) -> Option<(Vec<OwnedSection>, Vec<RectVertex>)> {
+        if self.palette.is_open() {
+            Some((
+                self.palette.sections(view, &self.keymap),
+                self.palette.rects(view),
+            ))
+        } else if self.file_finder.is_open() {
+            Some((
+                self.file_finder.sections(view),
+                self.file_finder.rects(view),
+            ))
+        } else if self.macro_picker.is_open() {
+            Some((
+                self.macro_picker.sections(view),
+                self.macro_picker.rects(view),
+            ))
+        } else if self.mark_picker.is_open() {
+            Some((
+                self.mark_picker.sections(view),
+                self.mark_picker.rects(view),
+            ))
+        } else if self.encoding_picker.is_open() {
+            Some((
+                self.encoding_picker.sections(view),
+                self.encoding_picker.rects(view),
+            ))
+        } else {
+            None
+        }
+    }
+
+    pub fn open_palette(&mut self) {
+        self.close_overlays();
+        self.palette.open(&self.session.recent_commands);
+    }
+
+    pub fn open_file_finder(&mut self) {
+        if let Some(root) = self.working_dir() {
+            self.close_overlays();
+            self.file_finder.open(root);
+        }
+    }
+
+    pub fn open_macro_picker(&mut self, action: MacroAction) {
+        let mut names: Vec<String> = self.session.macros.keys().cloned().collect();
+        if action != MacroAction::Save && self.recorder.last().is_some() {
+            names.insert(0, LAST_RECORDED.into());
+        }
+        if action != MacroAction::Save && names.is_empty() {
+            self.editor.set_message("No macros recorded");
+            return;
+        }
+        self.close_overlays();
+        self.macro_picker.open(action, names);
+    }
+
+    pub fn open_goto_bar(&mut self) {
+        self.close_overlays();
+        self.goto_bar.open(&self.editor);
+    }
+
+    // show the place, opening its buffer; false if that is gone
+    fn visit(&mut self, location: &Location) -> bool {
+        let shown = match &location.buffer {
+            _ if location.buffer == Location::of(&self.editor).buffer => true,
+            Some(BufferId::File(path)) => match self.buffers.open(&mut self.editor, path) {
+                Ok(()) => true,
+                Err(e) => {
+                    self.editor.set_message(e.to_string());
+                    return false;
+                }
+            },
+            Some(BufferId::Titled(title)) => {
+                self.buffers.show_hidden_titled(&mut self.editor, title)
+            }
+            None => false,
+        };
+        if !shown {
+            self.editor.set_message("That buffer was closed");
+            return false;
+        }
+        let line = location.line.min(self.editor.document.line_count() - 1);
+        let offset = self.editor.offset_at_column(line, location.column);
+        self.editor.select(Selection::caret(offset));
+        self.editor.scroll_to_center();
+        true
+    }
+
+    pub fn jump_back(&mut self) {
+        match self.jumps.back(Location::of(&self.editor)) {
+            Some(location) => {
+                self.visit(&location);
+            }
+            None => self.editor.set_message("No earlier jumps"),
+        }
+    }
+
+    pub fn jump_forward(&mut self) {
+        match self.jumps.forward() {
+            Some(location) => {
+                self.visit(&location);
+            }
+            None => self.editor.set_message("No later jumps"),
+        }
+    }
+
+    pub fn start_recording(&mut self) {
+        self.recorder.start();
+        self.editor.set_message("Recording macro");
+    }
+
+    pub fn stop_recording(&mut self) {
+        match self.recorder.stop() {
+            Some(steps) => self
+                .editor
+                .set_message(format!("Recorded a macro of {steps} steps")),
+            None => self.editor.set_message("Not recording a macro"),
+        }
+    }
+
+    fn macro_chosen(&mut self, choice: Choice) {
+        if choice.action == MacroAction::Save {
+            match self.recorder.last() {
+                Some(steps) => {
+                    self.session
+                        .macros
+                        .insert(choice.name.clone(), steps.to_vec());
+                    self.save_session();
+                    self.editor
+                        .set_message(format!("Saved macro {}", choice.name));
+                }
+                None => self.editor.set_message("No macro recorded"),
+            }
+            return;
+        }
+        let steps = match choice.name.as_str() {
+            LAST_RECORDED => self.recorder.last().map(<[Step]>::to_vec),
+            name => self.session.macros.get(name).cloned(),
+        };
+        let Some(steps) = steps else {
+            return;
+        };
+        match choice.action {
+            MacroAction::PlayOnLines => self.play_macro_on_lines(&steps),
+            _ => {
+                self.play_macro(&steps, choice.times);
+            }
+        }
+    }
+
+    // run the steps the given number of times, stopping at the first motion that cannot
+    // move; false if it stopped
+    pub fn play_macro(&mut self, steps: &[Step], times: usize) -> bool {
+        self.recorder.playing = true;
+        let completed = (0..times).all(|_| self.play_steps(steps));
+        self.recorder.playing = false;
+        completed
+    }
+
+    fn play_steps(&mut self, steps: &[Step]) -> bool {
+        for step in steps {
+            match step {
+                Step::Insert(text) => self.editor.type_text(text),
+                Step::Keys(keys) => self.feed_keys(keys),
+                Step::Command(id) => {
+                    let Some(command) = commands::find(id) else {
+                        self.editor
+                            .set_message(format!("Macro stopped: no command {id}"));
+                        return false;
+                    };
+                    let before = self.editor.selections().clone();
+                    (command.run)(self);
+                    let motion = id.starts_with("cursor.") || id.starts_with("selection.");
+                    // moving up or down past the first or last line only slides along it
+                    let vertical = id.ends_with(".up") || id.ends_with(".down");
+                    // every caret has to move; carets that ran into each other and merged
+                    // count as moved
+                    let document = &self.editor.document;
+                    let after = self.editor.selections();
+                    let failed = after.len() == before.len()
+                        && before.iter().zip(after.iter()).any(|(before, after)| {
+                            before.head == after.head
+                                || vertical
+                                    && document.line_of_offset(before.head)
+                                        == document.line_of_offset(after.head)
+                        });
+                    if motion && failed {
+                        self.editor
+                            .set_message(format!("Macro stopped: {} failed", command.title));
+                        return false;
+                    }
+                }
+            }
+        }
+        true
+    }
+
+    // run the steps once from the start of every line the selection touches
+    pub fn play_macro_on_lines(&mut self, steps: &[Step]) {
+        let document = &self.editor.document;
+        let range = self.editor.primary().range();
+        let mut line = document.line_of_offset(range.start);
+        let mut last = document.line_of_offset(range.end);
+        // a selection ending at a line start leaves that line out
+        if last > line && document.line_start(last) == range.end {
+            last -= 1;
+        }
+        while line <= last && line < self.editor.document.line_count() {
+            let line_count = self.editor.document.line_count();
+            let start = self.editor.document.line_start(line);
+            self.editor.select(Selection::caret(start));
+            if !self.play_macro(steps, 1) {
+                return;
+            }
+            // lines the macro added or removed move the ones still to come
+            let added = self.editor.document.line_count() as isize - line_count as isize;
+            last = last.saturating_add_signed(added);
+            line = (line + 1).saturating_add_signed(added);
+        }
+    }
+
+    pub fn save(&mut self) {
+        let document = &mut self.editor.document;
+        let name = match document.file_name() {
+            Some(path) => path.display().to_string(),
+            None => "untitled".into(),
+        };
+        match document.save() {
+            Ok(()) => self.editor.set_message(format!("Saved {name}")),
+            Err(e) => self.editor.set_message(format!("{name}: {e}")),
+        }
+        self.remember_folds();
+        self.remember_marks();
+    }
+
+    // show the buffer hidden last instead, the window closes with the last buffer;
+    // unsaved changes keep the buffer open unless they are to be discarded
+    pub fn close_buffer(&mut self, discard: bool) {
+        if !discard && has_unsaved_changes(&self.editor) {
+            let message = unsaved_message(&self.editor);
+            self.editor.set_message(message);
+            return;
+        }
+        self.remember_folds();
+        self.remember_marks();
+        if !self.buffers.close(&mut self.editor) {
+            self.quit = true;
+        }
+    }
+
+    // close the window unless a buffer, shown or hidden, has unsaved changes that are not
+    // to be discarded
+    pub fn request_quit(&mut self, discard: bool) {
+        if !discard {
+            let unsaved = std::iter::once(&self.editor)
+                .chain(self.buffers.hidden())
+                .find(|editor| has_unsaved_changes(editor));
+            if let Some(editor) = unsaved {
+                let message = unsaved_message(editor);
+                self.editor.set_message(message);
+                return;
+            }
+        }
+        self.quit = true;
+    }
+
+    // the previous key ran one of the commands
+    pub fn follows(&self, ids: &[&str]) -> bool {
+        self.last_command.is_some_and(|id| ids.contains(&id))
+    }
+
+    // kills also go to the system clipboard
+    pub fn export_kill(&mut self) {
+        if let (Some(clipboard), Some(text)) = (self.clipboard.as_mut(), self.emacs.latest()) {
+            let _ = clipboard.set_text(text);
+        }
+    }
+
+    pub fn copy(&mut self) {
+        let text = self.editor.copy();
+        if let Some(clipboard) = self.clipboard.as_mut() {
+            let _ = clipboard.set_text(text);
+        }
+    }
+
+    pub fn cut(&mut self) {
+        let text = self.editor.cut();
+        if let Some(clipboard) = self.clipboard.as_mut() {
+            let _ = clipboard.set_text(text);
+        }
+    }
+
+    // paste into the open overlay, or the editor
+    pub fn paste(&mut self) {
+        let text = self
+            .clipboard
+            .as_mut()
+            .and_then(|clipboard| clipboard.get_text().ok())
+            .unwrap_or_else(|| self.editor.clipboard_text());
+        if self.palette.is_open() {
+            self.palette.paste(&text, &self.session.recent_commands);
+        } else if self.macro_picker.is_open() {
+            self.macro_picker.paste(&text);
+        } else if self.mark_picker.is_open() {
+            self.mark_picker.paste(&text);
+        } else if self.encoding_picker.is_open() {
+            self.encoding_picker.paste(&text);
+        } else if self.file_finder.is_open() {
+            self.file_finder.paste(&text);
+        } else if self.goto_bar.is_open() {
+            self.goto_bar.paste(&mut self.editor, &text);
+        } else if self.find_bar.is_open() {
+            self.find_bar.paste(&mut self.editor, &text);
+        } else {
+            self.editor.paste(&text);
+        }
+    }
+
+    // the next snippet field, else the snippet whose prefix is before the caret, else
+    // indentation
+    pub fn tab(&mut self) {
+        if snippets::next_field(&mut self.editor) {
+            return;
+        }
+        let clipboard = &mut self.clipboard;
+        let read_clipboard = || clipboard.as_mut()?.get_text().ok();
+        if !snippets::expand_at_caret(&mut self.editor, &self.snippets, read_clipboard) {
+            self.editor.indent();
+        }
+    }
+
+    pub fn shift_tab(&mut self) {
+        if !snippets::previous_field(&mut self.editor) {
+            self.editor.dedent();
+        }
+    }
+}
+
+// a file as the session knows it
+fn session_key(editor: &Editor) -> Option<String> {
+    let path = editor.document.file_name()?;
+    let path = path.canonicalize().unwrap_or_else(|_| path.to_path_buf());
+    Some(path.display().to_string())
+}
+
+fn has_unsaved_changes(editor: &Editor) -> bool {
+    editor.document.is_dirty() && !editor.read_only
+}
+
+fn unsaved_message(editor: &Editor) -> String {
+    let name = editor
+        .document
+        .file_name()
+        .map_or("untitled".into(), |path| path.display().to_string());
+    format!("{name} has unsaved changes")
+}
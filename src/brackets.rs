@@ -0,0 +1,202 @@
+use crate::{language::Language, text_document::TextDocument};
+
+pub const BRACKET_COLOR: [f32; 4] = [0.3, 0.3, 0.35, 1.0];
+
+pub const BRACKETS: &[(char, char)] = &[('(', ')'), ('[', ']'), ('{', '}')];
+
+// how far a match is looked for, so brackets in large files stay cheap to draw
+const SCAN_LIMIT: usize = 100_000;
+
+// the brackets of the line outside strings and line comments with their offsets in it,
+// going by the quotes the language pairs and its comment token
+fn code_brackets(line: &str, language: &Language) -> Vec<(usize, char)> {
+    let mut brackets = Vec::new();
+    let mut open = None;
+    let mut escaped = false;
+    for (i, c) in line.char_indices() {
+        match open {
+            Some(_) if escaped => escaped = false,
+            Some(_) if c == '\\' => escaped = true,
+            Some(quote) if c == quote => open = None,
+            Some(_) => {}
+            None if language.is_quote(c) => open = Some(c),
+            None if language
+                .line_comment
+                .is_some_and(|token| line[i..].starts_with(token)) =>
+            {
+                break
+            }
+            None if BRACKETS
+                .iter()
+                .any(|&(open, close)| c == open || c == close) =>
+            {
+                brackets.push((i, c))
+            }
+            None => {}
+        }
+    }
+    brackets
+}
+
+// the code brackets of the line with their offsets in the document
+fn line_brackets(
+    document: &TextDocument,
+    line: usize,
+    language: &Language,
+) -> impl DoubleEndedIterator<Item = (usize, char)> {
+    let start = document.line_start(line);
+    code_brackets(document.line(line), language)
+        .into_iter()
+        .map(move |(i, c)| (start + i, c))
+}
+
+// the bracket at the offset, or else the one just before it, and the bracket it pairs with;
+// every line is scanned once, lines further than the scan limit away not at all
+pub fn matching(
+    document: &TextDocument,
+    offset: usize,
+    language: &Language,
+) -> Option<(usize, usize)> {
+    let text = document.text();
+    let at = text[offset..].chars().next().map(|c| (offset, c));
+    let before = text[..offset]
+        .chars()
+        .next_back()
+        .map(|c| (offset - c.len_utf8(), c));
+    [at, before]
+        .into_iter()
+        .flatten()
+        .find_map(|(position, c)| {
+            let &(open, close) = BRACKETS
+                .iter()
+                .find(|(open, close)| c == *open || c == *close)?;
+            let line = document.line_of_offset(position);
+            let brackets: Vec<_> = line_brackets(document, line, language).collect();
+            // none in a string or a comment
+            let index = brackets.iter().position(|(i, _)| *i == position)?;
+
+            let mut depth = 0;
+            let mut find = |(i, found): (usize, char)| {
+                depth += match found {
+                    found if found == c => 1,
+                    found if found == open || found == close => -1,
+                    _ => return None,
+                };
+                (depth < 0).then_some(i)
+            };
+            let found = match c == open {
+                true => {
+                    let limit = position + SCAN_LIMIT;
+                    let later = (line + 1..document.line_count())
+                        .take_while(|line| document.line_start(*line) <= limit)
+                        .flat_map(|line| line_brackets(document, line, language));
+                    brackets[index + 1..]
+                        .iter()
+                        .copied()
+                        .chain(later)
+                        .take_while(|(i, _)| *i <= limit)
+                        .find_map(&mut find)
+                }
+                false => {
+                    let limit = position.saturating_sub(SCAN_LIMIT);
+                    let earlier = (0..line)
+                        .rev()
+                        .take_while(|line| document.line_end(*line) >= limit)
+                        .flat_map(|line| line_brackets(document, line, language).rev());
+                    brackets[..index]
+                        .iter()
+                        .rev()
+                        .copied()
+                        .chain(earlier)
+                        .take_while(|(i, _)| *i >= limit)
+                        .find_map(&mut find)
+                }
+            }?;
+            Some((position, found))
+        })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::path::Path;
+
+    use crate::{
+        editor::{Editor, Indentation},
+        selection::Selection,
+    };
+
+    fn rust() -> &'static Language {
+        Language::detect(Some(Path::new("main.rs")))
+    }
+
+    fn matched(text: &str, offset: usize) -> Option<(usize, usize)> {
+        matching(&TextDocument::from_text(text), offset, rust())
+    }
+
+    fn rust_editor(text: &str, selection: Selection) -> Editor {
+        let mut editor = Editor::new(TextDocument::from_text(text), Indentation::default());
+        editor.language = rust();
+        editor.select(selection);
+        editor
+    }
+
+    #[test]
+    fn matches_nested_brackets_both_ways() {
+        let text = "f(a[1], {b})";
+        assert_eq!(matched(text, 1), Some((1, 11)));
+        assert_eq!(matched(text, 12), Some((11, 1)));
+        assert_eq!(matched(text, 3), Some((3, 5)));
+        assert_eq!(matched(text, 8), Some((8, 10)));
+        assert_eq!(matched("fn main() {\n    x\n}", 10), Some((10, 18)));
+        assert_eq!(matched("a (b", 2), None);
+        assert_eq!(matched("abc", 1), None);
+    }
+
+    #[test]
+    fn skips_brackets_in_strings_and_comments() {
+        let text = "f(\")\") // )\n)";
+        assert_eq!(matched(text, 1), Some((1, 5)));
+        assert_eq!(matched(text, 3), None);
+        assert_eq!(matched(text, 10), None);
+        assert_eq!(matched("(\"\\\")\")", 0), Some((0, 6)));
+        // lines are scanned on their own, a comment ends with its line
+        assert_eq!(matched("( // (\n)", 0), Some((0, 7)));
+        assert_eq!(matched("( // (\n)", 8), Some((7, 0)));
+    }
+
+    #[test]
+    fn openers_get_their_closers_and_closers_step_over() {
+        let mut editor = rust_editor("", Selection::caret(0));
+        editor.type_text("f(x");
+        assert_eq!(editor.document.text(), "f(x)");
+        editor.type_text(")");
+        assert_eq!(editor.document.text(), "f(x)");
+        assert_eq!(editor.caret(), 4);
+
+        // not in front of a word
+        let mut editor = rust_editor("word", Selection::caret(0));
+        editor.type_text("(");
+        assert_eq!(editor.document.text(), "(word");
+    }
+
+    #[test]
+    fn backspace_removes_an_empty_pair() {
+        let mut editor = rust_editor("[]", Selection::caret(1));
+        editor.backspace();
+        assert_eq!(editor.document.text(), "");
+        let mut editor = rust_editor("[x]", Selection::caret(2));
+        editor.backspace();
+        assert_eq!(editor.document.text(), "[]");
+    }
+
+    #[test]
+    fn openers_surround_the_selection() {
+        let mut editor = rust_editor("a word", Selection::new(6, 2));
+        editor.type_text("\"");
+        assert_eq!(editor.document.text(), "a \"word\"");
+        assert_eq!(editor.primary(), Selection::new(7, 3));
+        editor.type_text("[");
+        assert_eq!(editor.document.text(), "a \"[word]\"");
+    }
+}
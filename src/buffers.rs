@@ -0,0 +1,128 @@
+use std::{mem, path::Path};
+
+use crate::{
+    editor::{Editor, Indentation},
+    text_document::TextDocument,
+    utils::Res,
+};
+
+// buffers that are open but not shown, the shown one is the editor itself
+pub struct Buffers {
+    hidden: Vec<Editor>,
+    // for the editors of newly opened files
+    indentation: Indentation,
+}
+
+impl Buffers {
+    pub fn new(indentation: Indentation) -> Self {
+        Self {
+            hidden: Vec::new(),
+            indentation,
+        }
+    }
+
+    // swap the buffer into the editor, dropping the shown one if it is an empty scratch
+    fn show(&mut self, editor: &mut Editor, next: Editor) {
+        let previous = mem::replace(editor, next);
+        let disposable = previous.document.file_name().is_none()
+            && previous.document.title().is_none()
+            && previous.document.is_empty()
+            && !previous.document.is_dirty();
+        if !disposable {
+            self.hidden.push(previous);
+        }
+    }
+
+    // show the file, reusing its buffer when it is already open
+    pub fn open(&mut self, editor: &mut Editor, path: &Path) -> Res<()> {
+        if editor
+            .document
+            .file_name()
+            .is_some_and(|open| same_file(open, path))
+        {
+            return Ok(());
+        }
+
+        let next = match self.hidden.iter().position(|hidden| {
+            hidden
+                .document
+                .file_name()
+                .is_some_and(|open| same_file(open, path))
+        }) {
+            Some(index) => self.hidden.remove(index),
+            None => Editor::new(
+                TextDocument::new(&path.to_string_lossy())?,
+                self.indentation,
+            ),
+        };
+        self.show(editor, next);
+        Ok(())
+    }
+
+    // show the buffer not backed by a file, replacing an open one with the same title
+    pub fn show_titled(&mut self, editor: &mut Editor, next: Editor) {
+        let title = next.document.title();
+        self.hidden
+            .retain(|hidden| hidden.document.title() != title);
+        match editor.document.title() == title {
+            true => *editor = next,
+            false => self.show(editor, next),
+        }
+    }
+
+    // show the hidden buffer with the title, false if there is none
+    pub fn show_hidden_titled(&mut self, editor: &mut Editor, title: &str) -> bool {
+        match self
+            .hidden
+            .iter()
+            .position(|hidden| hidden.document.title() == Some(title))
+        {
+            Some(index) => {
+                let next = self.hidden.remove(index);
+                self.show(editor, next);
+                true
+            }
+            None => false,
+        }
+    }
+
+    // drop the shown buffer for the one hidden last, false if there is none
+    pub fn close(&mut self, editor: &mut Editor) -> bool {
+        match self.hidden.pop() {
+            Some(next) => {
+                *editor = next;
+                true
+            }
+            None => false,
+        }
+    }
+
+    pub fn hidden(&self) -> &[Editor] {
+        &self.hidden
+    }
+
+    pub fn hidden_mut(&mut self) -> &mut [Editor] {
+        &mut self.hidden
+    }
+
+    // the buffer with the title, shown or not
+    pub fn titled_mut<'a>(
+        &'a mut self,
+        editor: &'a mut Editor,
+        title: &str,
+    ) -> Option<&'a mut Editor> {
+        if editor.document.title() == Some(title) {
+            return Some(editor);
+        }
+        self.hidden
+            .iter_mut()
+            .find(|hidden| hidden.document.title() == Some(title))
+    }
+}
+
+fn same_file(a: &Path, b: &Path) -> bool {
+    match (a.canonicalize(), b.canonicalize()) {
+        (Ok(a), Ok(b)) => a == b,
+        _ => a == b,
+    }
+}
@@ -0,0 +1,221 @@
+use std::ops::Range;
+
+use crate::{editor::Editor, history::EditKind, selection::Selection};
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Case {
+    Upper,
+    Lower,
+    // first letter of every word upper case, the rest lower case
+    Title,
+    Snake,
+    Camel,
+    Pascal,
+    Kebab,
+    ScreamingSnake,
+}
+
+impl Case {
+    fn is_identifier_style(self) -> bool {
+        !matches!(self, Self::Upper | Self::Lower | Self::Title)
+    }
+}
+
+fn is_identifier_char(c: char) -> bool {
+    c.is_alphanumeric() || c == '_' || c == '-'
+}
+
+// the words of an identifier: separators split them, as does a lower case letter followed
+// by an upper case one, the last capital of an acronym before a lower case letter
+// (HTTPServer) and a capital after digits; digits stay with the word before them
+pub fn words(identifier: &str) -> Vec<&str> {
+    let mut words = Vec::new();
+    for part in identifier.split(|c: char| !c.is_alphanumeric()) {
+        let chars: Vec<(usize, char)> = part.char_indices().collect();
+        let mut start = 0;
+        for (index, &(i, c)) in chars.iter().enumerate().skip(1) {
+            let previous = chars[index - 1].1;
+            let next = chars.get(index + 1).map(|(_, c)| *c);
+            let boundary = c.is_uppercase()
+                && (previous.is_lowercase()
+                    || previous.is_numeric()
+                    || (previous.is_uppercase() && next.is_some_and(char::is_lowercase)));
+            if boundary {
+                words.push(&part[start..i]);
+                start = i;
+            }
+        }
+        if start < part.len() {
+            words.push(&part[start..]);
+        }
+    }
+    words
+}
+
+fn capitalize(word: &str) -> String {
+    let mut chars = word.chars();
+    match chars.next() {
+        Some(first) => first
+            .to_uppercase()
+            .chain(chars.flat_map(char::to_lowercase))
+            .collect(),
+        None => String::new(),
+    }
+}
+
+// one identifier in the style, keeping underscores and dashes around it
+fn convert_identifier(identifier: &str, case: Case) -> String {
+    let inner = identifier.trim_matches(['_', '-']);
+    let start = identifier.len() - identifier.trim_start_matches(['_', '-']).len();
+    let (before, after) = (&identifier[..start], &identifier[start + inner.len()..]);
+    let words = words(inner);
+    let inner = match case {
+        Case::Snake => words
+            .iter()
+            .map(|word| word.to_lowercase())
+            .collect::<Vec<_>>()
+            .join("_"),
+        Case::Kebab => words
+            .iter()
+            .map(|word| word.to_lowercase())
+            .collect::<Vec<_>>()
+            .join("-"),
+        Case::ScreamingSnake => words
+            .iter()
+            .map(|word| word.to_uppercase())
+            .collect::<Vec<_>>()
+            .join("_"),
+        Case::Pascal => words.iter().map(|word| capitalize(word)).collect(),
+        Case::Camel => words
+            .iter()
+            .enumerate()
+            .map(|(i, word)| match i {
+                0 => word.to_lowercase(),
+                _ => capitalize(word),
+            })
+            .collect(),
+        Case::Upper | Case::Lower | Case::Title => unreachable!(),
+    };
+    format!("{before}{inner}{after}")
+}
+
+// the text in the case; identifier styles convert every run of letters, digits,
+// underscores and dashes on its own and leave the rest as it is
+pub fn convert(text: &str, case: Case) -> String {
+    match case {
+        Case::Upper => return text.to_uppercase(),
+        Case::Lower => return text.to_lowercase(),
+        _ => {}
+    }
+    let is_part = |c: char| match case.is_identifier_style() {
+        true => is_identifier_char(c),
+        false => c.is_alphanumeric() || c == '\'',
+    };
+    let mut converted = String::with_capacity(text.len());
+    let mut rest = text;
+    while !rest.is_empty() {
+        let end = rest.find(|c: char| !is_part(c)).unwrap_or(rest.len());
+        let (run, after) = rest.split_at(end);
+        match case {
+            Case::Title => converted.push_str(&capitalize(run)),
+            _ => converted.push_str(&convert_identifier(run, case)),
+        }
+        let gap = after.find(is_part).unwrap_or(after.len());
+        converted.push_str(&after[..gap]);
+        rest = &after[gap..];
+    }
+    converted
+}
+
+// convert the selected text, the word at empty selections; the selections cover the
+// converted text afterwards
+pub fn convert_selections(editor: &mut Editor, case: Case) {
+    let text = editor.document.text();
+    let selections: Vec<Selection> = editor.selections().iter().copied().collect();
+    let ranges: Vec<Range<usize>> = selections
+        .iter()
+        .map(|selection| match selection.is_empty() {
+            true => editor.word_range(selection.head),
+            false => selection.range(),
+        })
+        .collect();
+    let mut end = 0;
+    let edits = ranges
+        .iter()
+        .map(|range| {
+            // carets in the same word convert it once
+            let edit = match range.start < end {
+                true => (end..end, String::new()),
+                false => (range.clone(), convert(&text[range.clone()], case)),
+            };
+            end = end.max(range.end);
+            edit
+        })
+        .collect();
+    editor.edit(EditKind::Other, edits, |inserted| {
+        selections
+            .iter()
+            .zip(ranges.iter().zip(inserted))
+            .map(
+                |(selection, (range, inserted))| match selection.is_empty() {
+                    // a caret keeps its place in the word as far as the word still reaches
+                    true => Selection::caret(
+                        (inserted.start + selection.head - range.start).min(inserted.end),
+                    ),
+                    false if selection.head < selection.anchor => {
+                        Selection::new(inserted.end, inserted.start)
+                    }
+                    false => Selection::new(inserted.start, inserted.end),
+                },
+            )
+            .collect()
+    });
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn splits_identifiers_into_words() {
+        assert_eq!(words("snake_case_name"), vec!["snake", "case", "name"]);
+        assert_eq!(words("camelCaseName"), vec!["camel", "Case", "Name"]);
+        assert_eq!(words("HTTPServer"), vec!["HTTP", "Server"]);
+        assert_eq!(words("utf8String"), vec!["utf8", "String"]);
+        assert_eq!(words("version2Beta"), vec!["version2", "Beta"]);
+        assert_eq!(words("kebab-case"), vec!["kebab", "case"]);
+        assert_eq!(words("ALL_CAPS"), vec!["ALL", "CAPS"]);
+    }
+
+    #[test]
+    fn converts_identifiers_between_styles() {
+        let name = "parseHTTPResponse";
+        assert_eq!(convert(name, Case::Snake), "parse_http_response");
+        assert_eq!(convert(name, Case::Kebab), "parse-http-response");
+        assert_eq!(convert(name, Case::ScreamingSnake), "PARSE_HTTP_RESPONSE");
+        assert_eq!(convert(name, Case::Pascal), "ParseHttpResponse");
+        assert_eq!(
+            convert("parse_http_response", Case::Camel),
+            "parseHttpResponse"
+        );
+    }
+
+    #[test]
+    fn keeps_what_is_around_identifiers() {
+        assert_eq!(convert("__fooBar__", Case::Snake), "__foo_bar__");
+        assert_eq!(
+            convert("let fooBar = bazQux(1);", Case::Snake),
+            "let foo_bar = baz_qux(1);"
+        );
+    }
+
+    #[test]
+    fn title_case_capitalizes_words() {
+        assert_eq!(
+            convert("the QUICK fox's tail", Case::Title),
+            "The Quick Fox's Tail"
+        );
+        assert_eq!(convert("snake_case", Case::Upper), "SNAKE_CASE");
+        assert_eq!(convert("MiXeD", Case::Lower), "mixed");
+    }
+}
@@ -0,0 +1,33 @@
+use unicode_width::UnicodeWidthChar;
+
+// visual columns taken by the char when it starts at the given column
+pub fn char_columns(c: char, column: usize, tab_width: usize) -> usize {
+    match c {
+        '\t' => tab_width.max(1) - column % tab_width.max(1),
+        c => c.width().unwrap_or(0),
+    }
+}
+
+// visual column at the byte offset into a single line
+pub fn visual_column(line: &str, offset: usize, tab_width: usize) -> usize {
+    line[..offset]
+        .chars()
+        .fold(0, |column, c| column + char_columns(c, column, tab_width))
+}
+
+// byte offset of the first char starting at or after the visual column, the line length
+// when the line is shorter; a wide char or tab spanning the column counts as before it
+pub fn offset_at_column(line: &str, target: usize, tab_width: usize) -> usize {
+    let mut column = 0;
+    for (i, c) in line.char_indices() {
+        if column >= target {
+            return i;
+        }
+        column += char_columns(c, column, tab_width);
+    }
+    line.len()
+}
+
+pub fn line_columns(line: &str, tab_width: usize) -> usize {
+    visual_column(line, line.len(), tab_width)
+}
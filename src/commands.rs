@@ -0,0 +1,491 @@
+use crate::{
+    app::App,
+    case::{self, Case},
+    comments,
+    encoding::EncodingAction,
+    find::FindMode,
+    lines::{self, Sort},
+    macros::{MacroAction, Step},
+    marks::{self, MarkAction},
+    selection::Selection,
+    text_document::LineEnding,
+};
+
+// an action of the editor, run from its key bindings or the palette
+pub struct Command {
+    // stable name used in key bindings and the session
+    pub id: &'static str,
+    // shown in the palette
+    pub title: &'static str,
+    // default key bindings
+    pub keys: &'static [&'static str],
+    pub run: fn(&mut App),
+}
+
+const fn command(
+    id: &'static str,
+    title: &'static str,
+    keys: &'static [&'static str],
+    run: fn(&mut App),
+) -> Command {
+    Command {
+        id,
+        title,
+        keys,
+        run,
+    }
+}
+
+// commands that add to the kill before them when run one after the other
+const KILLS: &[&str] = &[
+    "kill.line",
+    "kill.region",
+    "kill.copy_region",
+    "kill.word",
+    "kill.word_backward",
+];
+
+pub const COMMANDS: &[Command] = &[
+    command("app.cancel", "Cancel", &["Escape"], |app| {
+        if !app.editor.collapse_selections() {
+            app.project_search.cancel(&mut app.editor);
+        }
+    }),
+    command("app.quit", "Quit", &["Ctrl+Q"], |app| {
+        app.request_quit(false)
+    }),
+    command("app.force_quit", "Quit Without Saving", &[], |app| {
+        app.request_quit(true)
+    }),
+    command("app.keyboard_quit", "Keyboard Quit", &[], |app| {
+        app.emacs.deactivate_mark();
+        app.find_bar.close();
+        app.goto_bar.cancel(&mut app.editor);
+        app.editor.select(Selection::caret(app.editor.caret()));
+    }),
+    command("file.save", "Save", &["Ctrl+S"], App::save),
+    command("goto.line", "Go to Line", &["Ctrl+G"], App::open_goto_bar),
+    command("jump.back", "Go Back", &["Alt+Left"], App::jump_back),
+    command(
+        "jump.forward",
+        "Go Forward",
+        &["Alt+Right"],
+        App::jump_forward,
+    ),
+    command(
+        "bookmark.toggle",
+        "Toggle Bookmark",
+        &["Ctrl+F2"],
+        App::toggle_bookmark,
+    ),
+    command("bookmark.next", "Next Bookmark", &["F2"], |app| {
+        marks::next_bookmark(&mut app.editor, true)
+    }),
+    command(
+        "bookmark.previous",
+        "Previous Bookmark",
+        &["Shift+F2"],
+        |app| marks::next_bookmark(&mut app.editor, false),
+    ),
+    command("bookmark.list", "List Bookmarks", &["Alt+F2"], |app| {
+        app.open_mark_picker(MarkAction::Bookmarks)
+    }),
+    command(
+        "bookmark.clear",
+        "Clear Bookmarks",
+        &[],
+        App::clear_bookmarks,
+    ),
+    command("mark.set_named", "Set Named Mark", &[], |app| {
+        app.open_mark_picker(MarkAction::SetNamed)
+    }),
+    command("mark.goto_named", "Go to Named Mark", &[], |app| {
+        app.open_mark_picker(MarkAction::GotoNamed)
+    }),
+    command("buffer.close", "Close Buffer", &["Ctrl+W"], |app| {
+        app.close_buffer(false)
+    }),
+    command(
+        "buffer.force_close",
+        "Close Buffer Without Saving",
+        &[],
+        |app| app.close_buffer(true),
+    ),
+    command("vim.toggle", "Toggle Vim Mode", &[], |app| {
+        let enabled = !app.vim.is_enabled();
+        app.vim.set_enabled(enabled);
+    }),
+    command(
+        "palette.open",
+        "Show All Commands",
+        &["Ctrl+Shift+P"],
+        App::open_palette,
+    ),
+    command(
+        "file_finder.open",
+        "Go to File",
+        &["Ctrl+P"],
+        App::open_file_finder,
+    ),
+    command("find.open", "Find", &["Ctrl+F"], |app| {
+        app.find_bar.open(&app.editor, FindMode::Find)
+    }),
+    command("find.replace", "Replace", &["Ctrl+H"], |app| {
+        app.find_bar.open(&app.editor, FindMode::Replace)
+    }),
+    command(
+        "search.project",
+        "Find in Files",
+        &["Ctrl+Shift+F"],
+        |app| app.find_bar.open(&app.editor, FindMode::Project),
+    ),
+    command("find.forward", "Find Next", &["F3"], |app| {
+        match app.find_bar.is_open() {
+            true => app.find_bar.find(&mut app.editor, true),
+            false => app.find_bar.open(&app.editor, FindMode::Find),
+        }
+    }),
+    command(
+        "find.backward",
+        "Find Previous",
+        &["Shift+F3"],
+        |app| match app.find_bar.is_open() {
+            true => app.find_bar.find(&mut app.editor, false),
+            false => app.find_bar.open(&app.editor, FindMode::Find),
+        },
+    ),
+    command(
+        "macro.start_recording",
+        "Start Recording Macro",
+        &["Ctrl+Alt+R"],
+        App::start_recording,
+    ),
+    command(
+        "macro.stop_recording",
+        "Stop Recording Macro",
+        &["Ctrl+Alt+S"],
+        App::stop_recording,
+    ),
+    command(
+        "macro.play_last",
+        "Play Last Macro",
+        &["Ctrl+Alt+P"],
+        |app| match app.recorder.last().map(<[Step]>::to_vec) {
+            Some(steps) => {
+                app.play_macro(&steps, 1);
+            }
+            None => app.editor.set_message("No macro recorded"),
+        },
+    ),
+    command("macro.play", "Play Macro…", &[], |app| {
+        app.open_macro_picker(MacroAction::Play)
+    }),
+    command(
+        "macro.play_on_lines",
+        "Play Macro on Selected Lines…",
+        &[],
+        |app| app.open_macro_picker(MacroAction::PlayOnLines),
+    ),
+    command("macro.save", "Save Last Macro As…", &[], |app| {
+        app.open_macro_picker(MacroAction::Save)
+    }),
+    command("edit.undo", "Undo", &["Ctrl+Z"], |app| app.editor.undo()),
+    command("edit.redo", "Redo", &["Ctrl+Shift+Z", "Ctrl+Y"], |app| {
+        app.editor.redo()
+    }),
+    command("edit.newline", "Insert Line Break", &["Enter"], |app| {
+        // in the search results the line under the caret opens instead
+        if !app
+            .project_search
+            .activate(&mut app.editor, &mut app.buffers)
+        {
+            app.editor.newline();
+        }
+    }),
+    command("edit.indent", "Indent", &["Tab"], |app| app.tab()),
+    command(
+        "edit.toggle_line_comment",
+        "Toggle Line Comment",
+        &["Ctrl+/"],
+        |app| comments::toggle_line(&mut app.editor),
+    ),
+    command(
+        "edit.toggle_block_comment",
+        "Toggle Block Comment",
+        &["Ctrl+Shift+[Slash]"],
+        |app| comments::toggle_block(&mut app.editor),
+    ),
+    command("edit.dedent", "Outdent", &["Shift+Tab"], |app| {
+        app.shift_tab()
+    }),
+    command("edit.backspace", "Delete Left", &["Backspace"], |app| {
+        app.editor.backspace()
+    }),
+    command("edit.delete", "Delete Right", &["Delete"], |app| {
+        app.editor.delete_forward()
+    }),
+    command("case.upper", "Upper Case", &["Ctrl+K Ctrl+U"], |app| {
+        case::convert_selections(&mut app.editor, Case::Upper)
+    }),
+    command("case.lower", "Lower Case", &["Ctrl+K Ctrl+L"], |app| {
+        case::convert_selections(&mut app.editor, Case::Lower)
+    }),
+    command("case.title", "Title Case", &[], |app| {
+        case::convert_selections(&mut app.editor, Case::Title)
+    }),
+    command("case.snake", "Snake Case (snake_case)", &[], |app| {
+        case::convert_selections(&mut app.editor, Case::Snake)
+    }),
+    command("case.camel", "Camel Case (camelCase)", &[], |app| {
+        case::convert_selections(&mut app.editor, Case::Camel)
+    }),
+    command("case.pascal", "Pascal Case (PascalCase)", &[], |app| {
+        case::convert_selections(&mut app.editor, Case::Pascal)
+    }),
+    command("case.kebab", "Kebab Case (kebab-case)", &[], |app| {
+        case::convert_selections(&mut app.editor, Case::Kebab)
+    }),
+    command(
+        "case.screaming_snake",
+        "Screaming Snake Case (SCREAMING_SNAKE)",
+        &[],
+        |app| case::convert_selections(&mut app.editor, Case::ScreamingSnake),
+    ),
+    command("lines.move_up", "Move Lines Up", &["Alt+Up"], |app| {
+        lines::move_lines(&mut app.editor, false)
+    }),
+    command("lines.move_down", "Move Lines Down", &["Alt+Down"], |app| {
+        lines::move_lines(&mut app.editor, true)
+    }),
+    command(
+        "lines.duplicate",
+        "Duplicate Lines",
+        &["Ctrl+Shift+D"],
+        |app| lines::duplicate_lines(&mut app.editor),
+    ),
+    command("lines.delete", "Delete Lines", &["Ctrl+Shift+K"], |app| {
+        lines::delete_lines(&mut app.editor)
+    }),
+    command("lines.join", "Join Lines", &["Ctrl+J"], |app| {
+        lines::join_lines(&mut app.editor)
+    }),
+    command("lines.sort", "Sort Lines", &[], |app| {
+        lines::sort_lines(&mut app.editor, Sort::Lexicographic, false)
+    }),
+    command(
+        "lines.sort_descending",
+        "Sort Lines Descending",
+        &[],
+        |app| lines::sort_lines(&mut app.editor, Sort::Lexicographic, true),
+    ),
+    command("lines.sort_natural", "Sort Lines Naturally", &[], |app| {
+        lines::sort_lines(&mut app.editor, Sort::Natural, false)
+    }),
+    command("lines.sort_numeric", "Sort Lines by Number", &[], |app| {
+        lines::sort_lines(&mut app.editor, Sort::Numeric, false)
+    }),
+    command(
+        "lines.sort_case_insensitive",
+        "Sort Lines Ignoring Case",
+        &[],
+        |app| lines::sort_lines(&mut app.editor, Sort::CaseInsensitive, false),
+    ),
+    command("lines.unique", "Remove Duplicate Lines", &[], |app| {
+        lines::unique_lines(&mut app.editor)
+    }),
+    command("lines.reverse", "Reverse Lines", &[], |app| {
+        lines::reverse_lines(&mut app.editor)
+    }),
+    command("lines.shuffle", "Shuffle Lines", &[], |app| {
+        lines::shuffle_lines(&mut app.editor)
+    }),
+    command("line_ending.lf", "Convert Line Endings to LF", &[], |app| {
+        app.editor.convert_line_endings(LineEnding::Lf)
+    }),
+    command(
+        "line_ending.crlf",
+        "Convert Line Endings to CRLF",
+        &[],
+        |app| app.editor.convert_line_endings(LineEnding::Crlf),
+    ),
+    command("line_ending.cr", "Convert Line Endings to CR", &[], |app| {
+        app.editor.convert_line_endings(LineEnding::Cr)
+    }),
+    command("encoding.reopen", "Reopen with Encoding", &[], |app| {
+        app.open_encoding_picker(EncodingAction::Reopen)
+    }),
+    command("encoding.save", "Save with Encoding", &[], |app| {
+        app.open_encoding_picker(EncodingAction::Save)
+    }),
+    command("mark.set", "Set Mark", &[], |app| {
+        app.emacs.set_mark(&mut app.editor)
+    }),
+    command("mark.exchange", "Exchange Point and Mark", &[], |app| {
+        app.emacs.exchange_point_and_mark(&mut app.editor)
+    }),
+    command("kill.line", "Kill Line", &[], |app| {
+        let append = app.follows(KILLS);
+        app.emacs.kill_line(&mut app.editor, append);
+        app.export_kill();
+    }),
+    command("kill.region", "Kill Region", &[], |app| {
+        let append = app.follows(KILLS);
+        app.emacs.kill_region(&mut app.editor, append);
+        app.export_kill();
+    }),
+    command("kill.copy_region", "Copy Region as Kill", &[], |app| {
+        let append = app.follows(KILLS);
+        app.emacs.copy_region(&mut app.editor, append);
+        app.export_kill();
+    }),
+    command("kill.word", "Kill Word", &[], |app| {
+        let append = app.follows(KILLS);
+        app.emacs.kill_word(&mut app.editor, false, append);
+        app.export_kill();
+    }),
+    command("kill.word_backward", "Backward Kill Word", &[], |app| {
+        let append = app.follows(KILLS);
+        app.emacs.kill_word(&mut app.editor, true, append);
+        app.export_kill();
+    }),
+    command("kill.yank", "Yank", &[], |app| {
+        let fallback = app
+            .clipboard
+            .as_mut()
+            .and_then(|clipboard| clipboard.get_text().ok());
+        app.emacs.yank(&mut app.editor, fallback);
+    }),
+    command("kill.yank_pop", "Yank Pop", &[], |app| {
+        let after_yank = app.follows(&["kill.yank", "kill.yank_pop"]);
+        app.emacs.yank_pop(&mut app.editor, after_yank);
+    }),
+    command("clipboard.copy", "Copy", &["Ctrl+C"], App::copy),
+    command("clipboard.cut", "Cut", &["Ctrl+X"], App::cut),
+    command("clipboard.paste", "Paste", &["Ctrl+V"], App::paste),
+    command("cursor.left", "Move Left", &["Left"], |app| {
+        app.editor.move_left(app.emacs.mark_active())
+    }),
+    command("cursor.right", "Move Right", &["Right"], |app| {
+        app.editor.move_right(app.emacs.mark_active())
+    }),
+    command("cursor.up", "Move Up", &["Up"], |app| {
+        app.editor.move_up(app.emacs.mark_active())
+    }),
+    command("cursor.down", "Move Down", &["Down"], |app| {
+        app.editor.move_down(app.emacs.mark_active())
+    }),
+    command(
+        "cursor.line_start",
+        "Move to Line Start",
+        &["Home"],
+        |app| app.editor.move_line_start(app.emacs.mark_active()),
+    ),
+    command("cursor.line_end", "Move to Line End", &["End"], |app| {
+        app.editor.move_line_end(app.emacs.mark_active())
+    }),
+    command("fold.fold", "Fold", &["Ctrl+Shift+[BracketLeft]"], |app| {
+        app.editor.fold();
+        app.remember_folds();
+    }),
+    command(
+        "fold.unfold",
+        "Unfold",
+        &["Ctrl+Shift+[BracketRight]"],
+        |app| {
+            app.editor.unfold();
+            app.remember_folds();
+        },
+    ),
+    command("fold.toggle", "Toggle Fold", &[], |app| {
+        app.editor.toggle_fold();
+        app.remember_folds();
+    }),
+    command("fold.fold_all", "Fold All", &["Ctrl+K Ctrl+0"], |app| {
+        app.editor.fold_all();
+        app.remember_folds();
+    }),
+    command("fold.unfold_all", "Unfold All", &["Ctrl+K Ctrl+J"], |app| {
+        app.editor.unfold_all();
+        app.remember_folds();
+    }),
+    command(
+        "cursor.matching_bracket",
+        "Go to Matching Bracket",
+        &["Ctrl+Shift+[Backslash]"],
+        |app| app.editor.jump_to_matching_bracket(),
+    ),
+    command("selection.left", "Select Left", &["Shift+Left"], |app| {
+        app.editor.move_left(true)
+    }),
+    command("selection.right", "Select Right", &["Shift+Right"], |app| {
+        app.editor.move_right(true)
+    }),
+    command("selection.up", "Select Up", &["Shift+Up"], |app| {
+        app.editor.move_up(true)
+    }),
+    command("selection.down", "Select Down", &["Shift+Down"], |app| {
+        app.editor.move_down(true)
+    }),
+    command(
+        "selection.line_start",
+        "Select to Line Start",
+        &["Shift+Home"],
+        |app| app.editor.move_line_start(true),
+    ),
+    command(
+        "selection.line_end",
+        "Select to Line End",
+        &["Shift+End"],
+        |app| app.editor.move_line_end(true),
+    ),
+    command("selection.all", "Select All", &["Ctrl+A"], |app| {
+        let end = app.editor.document.len();
+        app.editor.select(Selection::new(0, end));
+    }),
+    command(
+        "selection.next_occurrence",
+        "Add Next Occurrence to Selection",
+        &["Ctrl+D"],
+        |app| app.editor.select_next_occurrence(),
+    ),
+    command(
+        "selection.caret_above",
+        "Add Caret Above",
+        &["Ctrl+Alt+Up"],
+        |app| app.editor.add_caret_vertically(true),
+    ),
+    command(
+        "selection.caret_below",
+        "Add Caret Below",
+        &["Ctrl+Alt+Down"],
+        |app| app.editor.add_caret_vertically(false),
+    ),
+    command(
+        "selection.block_up",
+        "Extend Block Selection Up",
+        &["Alt+Shift+Up"],
+        |app| app.editor.extend_block(-1, 0),
+    ),
+    command(
+        "selection.block_down",
+        "Extend Block Selection Down",
+        &["Alt+Shift+Down"],
+        |app| app.editor.extend_block(1, 0),
+    ),
+    command(
+        "selection.block_left",
+        "Extend Block Selection Left",
+        &["Alt+Shift+Left"],
+        |app| app.editor.extend_block(0, -1),
+    ),
+    command(
+        "selection.block_right",
+        "Extend Block Selection Right",
+        &["Alt+Shift+Right"],
+        |app| app.editor.extend_block(0, 1),
+    ),
+];
+
+pub fn find(id: &str) -> Option<&'static Command> {
+    COMMANDS.iter().find(|command| command.id == id)
+}
@@ -0,0 +1,221 @@
+use std::ops::Range;
+
+use crate::{editor::Editor, history::EditKind, indent, selection::Selection};
+
+// comment out the selected lines with the token at their least indentation, or uncomment
+// them when all of them are; languages without line comments get a block comment instead
+pub fn toggle_line(editor: &mut Editor) {
+    let Some(token) = editor.language.line_comment else {
+        toggle_block(editor);
+        return;
+    };
+    let document = &editor.document;
+    let lines: Vec<usize> = editor
+        .selected_lines()
+        .into_iter()
+        .filter(|line| !document.line(*line).trim().is_empty())
+        .collect();
+    if lines.is_empty() {
+        return;
+    }
+    let commented = lines
+        .iter()
+        .all(|line| document.line(*line).trim_start().starts_with(token));
+    let column = lines
+        .iter()
+        .map(|line| indent::leading(document.line(*line)).len())
+        .min()
+        .unwrap_or(0);
+
+    let edits = lines
+        .into_iter()
+        .map(|line| {
+            let text = document.line(line);
+            let start = document.line_start(line);
+            match commented {
+                true => {
+                    let at = indent::leading(text).len();
+                    let mut end = at + token.len();
+                    // the space written after the token goes with it
+                    if text[end..].starts_with(' ') {
+                        end += 1;
+                    }
+                    (start + at..start + end, String::new())
+                }
+                false => (start + column..start + column, format!("{token} ")),
+            }
+        })
+        .collect();
+    editor.edit_in_place(EditKind::Other, edits);
+}
+
+// wrap every selection in the block comment tokens, or unwrap them when it already is one;
+// an empty selection stands for the text of its line and selections sharing text are
+// toggled together
+pub fn toggle_block(editor: &mut Editor) {
+    let Some((open, close)) = editor.language.block_comment else {
+        let name = editor.language.name;
+        editor.set_message(format!("{name} has no comments"));
+        return;
+    };
+    let document = &editor.document;
+    let text = document.text();
+    let ranges: Vec<Range<usize>> = editor
+        .selections()
+        .iter()
+        .map(|selection| match selection.is_empty() {
+            true => {
+                let line = document.line_of_offset(selection.head);
+                let start = document.line_start(line);
+                let line_text = document.line(line);
+                let leading = indent::leading(line_text).len();
+                start + leading..start + line_text.trim_end().len().max(leading)
+            }
+            false => selection.range(),
+        })
+        .collect();
+    let mut blocks: Vec<Range<usize>> = ranges.clone();
+    blocks.sort_by_key(|range| range.start);
+    blocks.dedup_by(|next, block| {
+        let overlaps = next.start <= block.end;
+        if overlaps {
+            block.end = block.end.max(next.end);
+        }
+        overlaps
+    });
+
+    let mut edits: Vec<(Range<usize>, String)> = Vec::new();
+    for range in &blocks {
+        let inner = &text[range.clone()];
+        let trimmed = inner.trim();
+        let start = range.start + inner.len() - inner.trim_start().len();
+        let end = start + trimmed.len();
+        match trimmed.len() >= open.len() + close.len()
+            && trimmed.starts_with(open)
+            && trimmed.ends_with(close)
+        {
+            true => {
+                // the spaces written inside the tokens go with them
+                let mut open_end = start + open.len();
+                let mut close_start = end - close.len();
+                if text[open_end..close_start].starts_with(' ') {
+                    open_end += 1;
+                }
+                if close_start > open_end && text[..close_start].ends_with(' ') {
+                    close_start -= 1;
+                }
+                edits.push((start..open_end, String::new()));
+                edits.push((close_start..end, String::new()));
+            }
+            false => {
+                edits.push((range.start..range.start, format!("{open} ")));
+                edits.push((range.end..range.end, format!(" {close}")));
+            }
+        }
+    }
+    // non-empty selections keep the text between the tokens of their block selected
+    let selections: Vec<(Selection, usize)> = editor
+        .selections()
+        .iter()
+        .zip(&ranges)
+        .map(|(selection, range)| {
+            let block = blocks
+                .iter()
+                .position(|block| block.start <= range.start && range.end <= block.end)
+                .unwrap_or(0);
+            (*selection, block)
+        })
+        .collect();
+    let carets: Vec<usize> = selections
+        .iter()
+        .map(|(selection, _)| indent::map_offset(selection.head, &edits))
+        .collect();
+    editor.edit(EditKind::Other, edits, |inserted| {
+        selections
+            .iter()
+            .zip(carets)
+            .map(|((selection, block), caret)| {
+                let (start, end) = (inserted[2 * block].end, inserted[2 * block + 1].start);
+                match (selection.is_empty(), selection.head < selection.anchor) {
+                    (true, _) => Selection::caret(caret),
+                    (false, true) => Selection::new(end, start),
+                    (false, false) => Selection::new(start, end),
+                }
+            })
+            .collect()
+    });
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::path::Path;
+
+    use crate::{
+        editor::Indentation, language::Language, selection::Selections, text_document::TextDocument,
+    };
+
+    fn editor_for(path: &str, text: &str, selections: Vec<Selection>) -> Editor {
+        let mut editor = Editor::new(TextDocument::from_text(text), Indentation::default());
+        editor.language = Language::detect(Some(Path::new(path)));
+        editor.set_selections(Selections::new(selections, 0));
+        editor
+    }
+
+    #[test]
+    fn line_comments_go_at_the_least_indentation() {
+        let text = "fn f() {\n    a();\n\n        b();\n}";
+        let mut editor = editor_for("f.rs", text, vec![Selection::new(9, 31)]);
+        toggle_line(&mut editor);
+        assert_eq!(
+            editor.document.text(),
+            "fn f() {\n    // a();\n\n    //     b();\n}"
+        );
+        toggle_line(&mut editor);
+        assert_eq!(editor.document.text(), text);
+    }
+
+    #[test]
+    fn lines_partly_commented_are_commented_again() {
+        let mut editor = editor_for("f.py", "# a\nb", vec![Selection::new(0, 5)]);
+        toggle_line(&mut editor);
+        assert_eq!(editor.document.text(), "# # a\n# b");
+    }
+
+    #[test]
+    fn block_comments_wrap_and_unwrap_the_selection() {
+        let mut editor = editor_for("f.rs", "let x = 1 + 2;", vec![Selection::new(8, 13)]);
+        toggle_block(&mut editor);
+        assert_eq!(editor.document.text(), "let x = /* 1 + 2 */;");
+        assert_eq!(editor.primary(), Selection::new(11, 16));
+        editor.select(Selection::new(8, 19));
+        toggle_block(&mut editor);
+        assert_eq!(editor.document.text(), "let x = 1 + 2;");
+
+        // a caret stands for its line, without the indentation
+        let mut editor = editor_for("f.rs", "  x();\ny", vec![Selection::caret(3)]);
+        toggle_block(&mut editor);
+        assert_eq!(editor.document.text(), "  /* x(); */\ny");
+        assert_eq!(editor.caret(), 6);
+    }
+
+    #[test]
+    fn carets_on_one_line_comment_it_once() {
+        let carets = vec![
+            Selection::caret(1),
+            Selection::caret(3),
+            Selection::caret(9),
+        ];
+        let mut editor = editor_for("f.rs", "a b c\nd e", carets);
+        toggle_block(&mut editor);
+        assert_eq!(editor.document.text(), "/* a b c */\n/* d e */");
+        toggle_block(&mut editor);
+        assert_eq!(editor.document.text(), "a b c\nd e");
+
+        // a selection inside a line another caret comments takes part in it
+        let selections = vec![Selection::new(2, 3), Selection::caret(0)];
+        let mut editor = editor_for("f.rs", "a b c", selections);
+        toggle_block(&mut editor);
+        assert_eq!(editor.document.text(), "/* a b c */");
+    }
+}
@@ -0,0 +1,54 @@
+use std::{fs, io, path::PathBuf};
+
+use serde::Deserialize;
+
+use crate::{
+    editor::Indentation, gutter::Gutter, status_bar::StatusBar, text_view::Whitespace, utils::Res,
+};
+
+// user settings read from <config dir>/text_editor/config.toml, every key is optional
+#[derive(Debug, Deserialize)]
+#[serde(default)]
+pub struct Config {
+    pub font_size: f32,
+    // wrap long lines at the window edge instead of running off screen
+    pub soft_wrap: bool,
+    // the width of a level is also how wide tabs are drawn
+    pub indentation: Indentation,
+    pub whitespace: Whitespace,
+    pub gutter: Gutter,
+    pub status_bar: StatusBar,
+}
+
+impl Default for Config {
+    fn default() -> Self {
+        Self {
+            font_size: 18.0,
+            soft_wrap: false,
+            indentation: Indentation::default(),
+            whitespace: Whitespace::default(),
+            gutter: Gutter::default(),
+            status_bar: StatusBar::default(),
+        }
+    }
+}
+
+impl Config {
+    pub fn path() -> Option<PathBuf> {
+        dirs::config_dir().map(|dir| dir.join("text_editor").join("config.toml"))
+    }
+
+    // a missing config file is not an error, a malformed one is
+    pub fn load() -> Res<Self> {
+        let Some(path) = Self::path() else {
+            return Ok(Self::default());
+        };
+        match fs::read_to_string(&path) {
+            Ok(text) => {
+                toml::from_str(&text).map_err(|e| format!("{}: {e}", path.display()).into())
+            }
+            Err(e) if e.kind() == io::ErrorKind::NotFound => Ok(Self::default()),
+            Err(e) => Err(e.into()),
+        }
+    }
+}
@@ -0,0 +1,1177 @@
+use std::{
+    fmt,
+    ops::Range,
+    time::{Duration, Instant},
+};
+
+use serde::Deserialize;
+
+use crate::{
+    brackets, columns,
+    folding::{self, Folds},
+    history::{Change, EditKind, History},
+    indent,
+    language::Language,
+    marks::Marks,
+    selection::{Selection, Selections},
+    snippets::SnippetSession,
+    text_document::{normalize_line_endings, LineEnding, TextDocument},
+    wrap::{wrap_line, Row},
+};
+
+// how long a status message stays visible
+const MESSAGE_TIMEOUT: Duration = Duration::from_secs(4);
+
+#[derive(Clone, Copy, Debug, Deserialize)]
+#[serde(default)]
+pub struct Indentation {
+    pub use_tabs: bool,
+    pub width: usize,
+    // follow the indentation a file already uses instead
+    pub detect: bool,
+}
+
+impl Default for Indentation {
+    fn default() -> Self {
+        Self {
+            use_tabs: false,
+            width: 4,
+            detect: true,
+        }
+    }
+}
+
+impl Indentation {
+    // one level as inserted
+    pub fn unit(&self) -> String {
+        match self.use_tabs {
+            true => "\t".to_string(),
+            false => " ".repeat(self.width),
+        }
+    }
+}
+
+impl fmt::Display for Indentation {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self.use_tabs {
+            true => write!(f, "Tab Size: {}", self.width),
+            false => write!(f, "Spaces: {}", self.width),
+        }
+    }
+}
+
+// rectangular selection between two (line, visual column) corners
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct Block {
+    pub anchor: (usize, usize),
+    pub head: (usize, usize),
+}
+
+pub struct Editor {
+    pub document: TextDocument,
+    pub language: &'static Language,
+    pub indentation: Indentation,
+    pub folds: Folds,
+    pub marks: Marks,
+    // fields of the snippet being filled in
+    pub snippet: Option<SnippetSession>,
+    // generated buffers such as search results refuse edits
+    pub read_only: bool,
+    selections: Selections,
+    // set while the selections are the rows of a block selection
+    block: Option<Block>,
+    history: History,
+    // pieces of the last copy, one per selection
+    clipboard: Vec<String>,
+    // the last copy came from a block selection
+    clipboard_block: bool,
+    // first document line shown in the viewport
+    pub scroll_line: usize,
+    viewport_rows: usize,
+    // soft wrap width in columns, None when wrapping is off
+    wrap_columns: Option<usize>,
+    // transient status message and when it was set
+    message: Option<(String, Instant)>,
+}
+
+impl Editor {
+    pub fn new(document: TextDocument, indentation: Indentation) -> Self {
+        let indentation = match indentation.detect {
+            true => indent::detect(document.text(), indentation),
+            false => indentation,
+        };
+        Self {
+            language: Language::detect(document.file_name()),
+            document,
+            indentation,
+            folds: Folds::default(),
+            marks: Marks::default(),
+            snippet: None,
+            read_only: false,
+            selections: Selections::default(),
+            block: None,
+            history: History::default(),
+            clipboard: Vec::new(),
+            clipboard_block: false,
+            scroll_line: 0,
+            viewport_rows: 1,
+            wrap_columns: None,
+            message: None,
+        }
+    }
+
+    pub fn set_message(&mut self, message: impl Into<String>) {
+        self.message = Some((message.into(), Instant::now()));
+    }
+
+    pub fn message(&self) -> Option<&str> {
+        self.message
+            .as_ref()
+            .filter(|(_, set)| set.elapsed() < MESSAGE_TIMEOUT)
+            .map(|(message, _)| message.as_str())
+    }
+
+    pub fn selections(&self) -> &Selections {
+        &self.selections
+    }
+
+    pub fn primary(&self) -> Selection {
+        self.selections.primary()
+    }
+
+    pub fn caret(&self) -> usize {
+        self.primary().head
+    }
+
+    pub fn caret_line(&self) -> usize {
+        self.document.line_of_offset(self.caret())
+    }
+
+    // 1-based line and column of the caret
+    pub fn caret_position(&self) -> (usize, usize) {
+        (self.caret_line() + 1, self.column_of(self.caret()) + 1)
+    }
+
+    // a fold hiding a caret opens, a caret leaving the snippet field ends the snippet
+    pub fn set_selections(&mut self, selections: Selections) {
+        self.selections = selections;
+        if !self
+            .snippet
+            .as_ref()
+            .is_some_and(|snippet| snippet.holds(&self.selections))
+        {
+            self.snippet = None;
+        }
+        let heads: Vec<usize> = self.selections.iter().map(|s| s.head).collect();
+        self.folds.reveal(heads);
+        self.block = None;
+        self.scroll_to_caret();
+    }
+
+    pub fn select(&mut self, selection: Selection) {
+        self.set_selections(Selections::single(selection));
+    }
+
+    // add a selection that becomes the primary one, merging it with any it overlaps
+    pub fn add_selection(&mut self, selection: Selection) {
+        self.selections.push(selection);
+        self.scroll_to_caret();
+    }
+
+    pub fn replace_primary(&mut self, selection: Selection) {
+        self.selections.replace_primary(selection);
+        self.scroll_to_caret();
+    }
+
+    // drop every selection but the primary one, false if there was only one
+    pub fn collapse_selections(&mut self) -> bool {
+        if self.selections.len() == 1 {
+            return false;
+        }
+        self.select(self.primary());
+        true
+    }
+
+    // text covered by a selection
+    pub fn selected_text(&self, selection: &Selection) -> &str {
+        &self.document.text()[selection.range()]
+    }
+
+    // called every frame, so it leaves the scroll position alone
+    pub fn set_viewport(&mut self, rows: usize, wrap_columns: Option<usize>) {
+        self.viewport_rows = rows.max(1);
+        self.wrap_columns = wrap_columns;
+    }
+
+    // rows of a single line after soft wrapping
+    pub fn line_rows(&self, line: usize) -> Vec<Row> {
+        let start = self.document.line_start(line);
+        let mut rows: Vec<Row> = wrap_line(
+            self.document.line(line),
+            start,
+            self.wrap_columns,
+            self.indentation.width,
+        )
+        .into_iter()
+        .map(|range| Row {
+            line,
+            continuation: range.start != start,
+            folded: false,
+            range,
+        })
+        .collect();
+        if let Some(last) = rows.last_mut() {
+            last.folded = self.is_folded(line);
+        }
+        rows
+    }
+
+    // rows filling the viewport, starting at the scroll line
+    pub fn visible_rows(&self) -> Vec<Row> {
+        std::iter::successors(Some(self.first_visible_line()), |line| {
+            self.next_visible_line(*line)
+        })
+        .flat_map(|line| self.line_rows(line))
+        .take(self.viewport_rows)
+        .collect()
+    }
+
+    // the scroll line, or the header of the fold hiding it
+    fn first_visible_line(&self) -> usize {
+        let line = self.scroll_line.min(self.document.line_count() - 1);
+        match self.folds.hiding(self.document.line_start(line)) {
+            Some(fold) => self.document.line_of_offset(fold.start),
+            None => line,
+        }
+    }
+
+    // whether lines are folded under the line
+    pub fn is_folded(&self, line: usize) -> bool {
+        let end = self.document.line_end(line);
+        self.folds
+            .hiding(end + 1)
+            .is_some_and(|fold| fold.start == end)
+    }
+
+    // the line shown below the line, past the ones folded under it
+    pub fn next_visible_line(&self, line: usize) -> Option<usize> {
+        let end = self.document.line_end(line);
+        let next = match self.folds.hiding(end + 1) {
+            Some(fold) if fold.start == end => self.document.line_of_offset(fold.end) + 1,
+            _ => line + 1,
+        };
+        (next < self.document.line_count()).then_some(next)
+    }
+
+    // the line shown above the line, the header of a fold right above it
+    pub fn prev_visible_line(&self, line: usize) -> Option<usize> {
+        let prev = line.checked_sub(1)?;
+        Some(match self.folds.hiding(self.document.line_start(prev)) {
+            Some(fold) => self.document.line_of_offset(fold.start),
+            None => prev,
+        })
+    }
+
+    pub fn scroll_by(&mut self, lines: isize) {
+        let mut line = self.first_visible_line();
+        for _ in 0..lines.unsigned_abs() {
+            let next = match lines < 0 {
+                true => self.prev_visible_line(line),
+                false => self.next_visible_line(line),
+            };
+            match next {
+                Some(next) => line = next,
+                None => break,
+            }
+        }
+        self.scroll_line = line;
+    }
+
+    pub fn scroll_to_caret(&mut self) {
+        let line = self.caret_line();
+        let mut scroll = self.first_visible_line();
+        if line <= scroll {
+            self.scroll_line = line;
+            return;
+        }
+
+        // every line takes at least one row
+        let mut lowest = line;
+        for _ in 1..self.viewport_rows {
+            match self.prev_visible_line(lowest) {
+                Some(prev) if prev > scroll => lowest = prev,
+                _ => break,
+            }
+        }
+        scroll = scroll.max(lowest);
+        let caret_row = self
+            .line_rows(line)
+            .iter()
+            .rposition(|row| row.range.start <= self.caret())
+            .unwrap_or(0);
+        let mut rows = std::iter::successors(Some(scroll), |above| self.next_visible_line(*above))
+            .take_while(|above| *above < line)
+            .map(|above| self.line_rows(above).len())
+            .sum::<usize>()
+            + caret_row
+            + 1;
+        while rows > self.viewport_rows && scroll < line {
+            rows -= self.line_rows(scroll).len();
+            scroll = self.next_visible_line(scroll).unwrap_or(line);
+        }
+        self.scroll_line = scroll;
+    }
+
+    // scroll so the caret line is in the middle of the viewport, for jumps far away
+    pub fn scroll_to_center(&mut self) {
+        self.scroll_line = self.caret_line();
+        self.scroll_by(-(self.viewport_rows as isize / 2));
+    }
+
+    // collapse the block by indentation at the caret
+    pub fn fold(&mut self) {
+        let line = self.caret_line();
+        match folding::block_at(&self.document, line, self.indentation.width) {
+            Some((header, last)) => {
+                let range = folding::hidden_range(&self.document, header, last);
+                // carets folded away go to the end of the header
+                let selections = self.selections.map(|selection| {
+                    match range.start < selection.head && selection.head <= range.end {
+                        true => Selection::caret(range.start),
+                        false => *selection,
+                    }
+                });
+                self.folds.fold(range);
+                self.set_selections(selections);
+            }
+            None => self.set_message("Nothing to fold"),
+        }
+    }
+
+    // expand the fold under the caret line
+    pub fn unfold(&mut self) {
+        let end = self.document.line_end(self.caret_line());
+        if !self.folds.unfold_at(end) {
+            self.set_message("Nothing to unfold");
+        }
+    }
+
+    pub fn toggle_fold(&mut self) {
+        match self.is_folded(self.caret_line()) {
+            true => self.unfold(),
+            false => self.fold(),
+        }
+    }
+
+    pub fn fold_all(&mut self) {
+        for (header, last) in folding::outer_blocks(&self.document, self.indentation.width) {
+            let range = folding::hidden_range(&self.document, header, last);
+            self.folds.fold(range);
+        }
+        let selections = self
+            .selections
+            .map(|selection| match self.folds.hiding(selection.head) {
+                Some(fold) => Selection::caret(fold.start),
+                None => *selection,
+            });
+        self.set_selections(selections);
+    }
+
+    pub fn unfold_all(&mut self) {
+        self.folds.clear();
+    }
+
+    // fold the (header, last) lines given, leaving out those past the end of the document
+    // and those hiding a caret
+    pub fn restore_folds(&mut self, lines: &[[usize; 2]]) {
+        let count = self.document.line_count();
+        for &[header, last] in lines {
+            if header < last && last < count {
+                let range = folding::hidden_range(&self.document, header, last);
+                self.folds.fold(range);
+            }
+        }
+        let heads: Vec<usize> = self.selections.iter().map(|s| s.head).collect();
+        self.folds.reveal(heads);
+    }
+
+    // replace the ranges with their texts as one undo step; ranges are sorted and in the
+    // current text, one overlapping the previous is cut short. `select` gets the inserted
+    // ranges in the new text, one per edit, and returns the selections to leave behind
+    pub fn edit(
+        &mut self,
+        kind: EditKind,
+        edits: Vec<(Range<usize>, String)>,
+        select: impl FnOnce(&[Range<usize>]) -> Vec<Selection>,
+    ) {
+        if self.read_only {
+            self.set_message("Buffer is read-only");
+            return;
+        }
+        let before = self.selections.clone();
+        let mut changes = Vec::with_capacity(edits.len());
+        let mut inserted = Vec::with_capacity(edits.len());
+        let mut delta = 0isize;
+        let mut last_end = 0;
+        for (range, text) in edits {
+            // as the document will have it, so the inserted ranges fit
+            let text = match text.contains('\r') {
+                true => normalize_line_endings(&text),
+                false => text,
+            };
+            let range = range.start.max(last_end)..range.end.max(last_end);
+            last_end = range.end;
+            let start = range.start.saturating_add_signed(delta);
+            let end = range.end.saturating_add_signed(delta);
+            let deleted = self.document.text()[start..end].to_string();
+            if deleted != text {
+                self.document.replace(start..end, &text);
+                let change = Change {
+                    offset: start,
+                    deleted,
+                    inserted: text.clone(),
+                };
+                self.folds.update(&change);
+                self.marks.update(&change);
+                if !self
+                    .snippet
+                    .as_mut()
+                    .is_some_and(|snippet| snippet.update(&change))
+                {
+                    self.snippet = None;
+                }
+                changes.push(change);
+            }
+            delta += text.len() as isize - range.len() as isize;
+            inserted.push(start..start + text.len());
+        }
+
+        let mut selections = select(&inserted);
+        // no selections left behind leaves a caret after the last edit
+        if selections.is_empty() {
+            let offset = inserted.last().map_or(self.caret(), |range| range.end);
+            selections.push(Selection::caret(offset.min(self.document.len())));
+        }
+        let primary = self.selections.primary_index().min(selections.len() - 1);
+        self.set_selections(Selections::new(selections, primary));
+        if !changes.is_empty() {
+            self.history
+                .record(kind, changes, before, self.selections.clone());
+        }
+    }
+
+    pub fn undo(&mut self) {
+        self.snippet = None;
+        match self.history.undo(&mut self.document, |change| {
+            self.folds.update(change);
+            self.marks.update(change);
+        }) {
+            Some(selections) => self.set_selections(selections),
+            None => self.set_message("Nothing to undo"),
+        }
+    }
+
+    pub fn redo(&mut self) {
+        self.snippet = None;
+        match self.history.redo(&mut self.document, |change| {
+            self.folds.update(change);
+            self.marks.update(change);
+        }) {
+            Some(selections) => self.set_selections(selections),
+            None => self.set_message("Nothing to redo"),
+        }
+    }
+
+    // replace every selection with the text, carets after the inserted text
+    pub fn insert_text(&mut self, text: &str) {
+        let edits = self
+            .selections
+            .iter()
+            .map(|selection| (selection.range(), text.to_string()))
+            .collect();
+        self.edit(EditKind::Insert, edits, carets_after);
+    }
+
+    // typed text; openers get their closer, a closer typed before the same one steps over
+    // it and an opener typed over a selection wraps it
+    pub fn type_text(&mut self, text: &str) {
+        for c in text.chars() {
+            self.type_char(c);
+        }
+    }
+
+    fn type_char(&mut self, c: char) {
+        let text = self.document.text();
+        let pairs = self.language.pairs;
+        let pair = pairs.iter().find(|(open, _)| *open == c).copied();
+        let closes = pairs.iter().any(|(_, close)| *close == c);
+        // (anchor, head) of each selection left behind, relative to its inserted text
+        let mut ends = Vec::new();
+        let edits = self
+            .selections
+            .iter()
+            .map(|selection| {
+                let range = selection.range();
+                let next = text[range.end..].chars().next();
+                let previous = text[..range.start].chars().next_back();
+                let (edit, anchor, head) = match pair {
+                    Some((open, close)) if !selection.is_empty() => {
+                        let inner = &text[range.clone()];
+                        let (start, end) = (open.len_utf8(), open.len_utf8() + inner.len());
+                        let (anchor, head) = match selection.head < selection.anchor {
+                            true => (end, start),
+                            false => (start, end),
+                        };
+                        ((range, format!("{open}{inner}{close}")), anchor, head)
+                    }
+                    _ if closes && next == Some(c) => {
+                        let over = range.end..range.end + c.len_utf8();
+                        ((over, c.to_string()), c.len_utf8(), c.len_utf8())
+                    }
+                    Some((open, close))
+                        if next.is_none_or(|next| {
+                            next.is_whitespace() || pairs.iter().any(|(_, c)| *c == next)
+                        }) && (open != close
+                            || previous.is_none_or(|p| !p.is_alphanumeric() && p != open)) =>
+                    {
+                        let caret = open.len_utf8();
+                        ((range, format!("{open}{close}")), caret, caret)
+                    }
+                    _ => ((range, c.to_string()), c.len_utf8(), c.len_utf8()),
+                };
+                ends.push((anchor, head));
+                edit
+            })
+            .collect();
+        self.edit(EditKind::Insert, edits, |inserted| {
+            inserted
+                .iter()
+                .zip(ends)
+                .map(|(range, (anchor, head))| {
+                    Selection::new(range.start + anchor, range.start + head)
+                })
+                .collect()
+        });
+    }
+
+    // the bracket at or before the primary caret and the one it pairs with
+    pub fn matching_bracket(&self) -> Option<(usize, usize)> {
+        brackets::matching(&self.document, self.caret(), self.language)
+    }
+
+    // caret to the other bracket of the pair, on the same side of it
+    pub fn jump_to_matching_bracket(&mut self) {
+        let caret = self.caret();
+        match self.matching_bracket() {
+            Some((bracket, found)) if bracket == caret => self.select(Selection::caret(found)),
+            Some((_, found)) => {
+                let after = self.next_char_boundary(found);
+                self.select(Selection::caret(after));
+            }
+            None => self.set_message("No bracket at the caret"),
+        }
+    }
+
+    // in the leading spaces of a line one level goes at a time, between an empty pair both
+    // of it
+    pub fn backspace(&mut self) {
+        self.delete_selections(|editor, caret| {
+            let text = editor.document.text();
+            let previous = text[..caret].chars().next_back();
+            let next = text[caret..].chars().next();
+            let empty_pair = editor
+                .language
+                .pairs
+                .iter()
+                .any(|pair| (previous, next) == (Some(pair.0), Some(pair.1)));
+            if empty_pair {
+                return caret - previous.map_or(0, char::len_utf8)
+                    ..caret + next.map_or(0, char::len_utf8);
+            }
+            let start = editor
+                .document
+                .line_start(editor.document.line_of_offset(caret));
+            let before = &editor.document.text()[start..caret];
+            let from = match !before.is_empty() && before.bytes().all(|b| b == b' ') {
+                true => caret - (before.len() - 1) % editor.indentation.width.max(1) - 1,
+                false => editor.prev_char_boundary(caret),
+            };
+            from..caret
+        });
+    }
+
+    // line break keeping the indentation of the line, a level deeper after an opener; a
+    // closer right after the caret goes on a line of its own
+    pub fn newline(&mut self) {
+        let document = &self.document;
+        let unit = self.indentation.unit();
+        let mut carets = Vec::new();
+        let edits = self
+            .selections
+            .iter()
+            .map(|selection| {
+                let range = selection.range();
+                let line = document.line_of_offset(range.start);
+                let before = &document.text()[document.line_start(line)..range.start];
+                let indent = indent::leading(before);
+                let after = &document.text()[range.end..document.line_end(line)];
+                let opens = self.language.opens_block(before.trim_end());
+                let mut text = format!("\n{indent}");
+                if opens {
+                    text.push_str(&unit);
+                }
+                carets.push(text.len());
+                if opens && after.trim_start().starts_with([')', ']', '}']) {
+                    text.push_str(&format!("\n{indent}"));
+                }
+                (range, text)
+            })
+            .collect();
+        self.edit(EditKind::Insert, edits, |inserted| {
+            inserted
+                .iter()
+                .zip(carets)
+                .map(|(range, caret)| Selection::caret(range.start + caret))
+                .collect()
+        });
+    }
+
+    // a level more for the lines of multi-line selections, carets get spaces up to the
+    // next stop or a tab
+    pub fn indent(&mut self) {
+        let document = &self.document;
+        let multiline = self.selections.iter().any(|selection| {
+            document.line_of_offset(selection.start()) != document.line_of_offset(selection.end())
+        });
+        if multiline {
+            self.shift_selected_lines(false);
+            return;
+        }
+        let width = self.indentation.width.max(1);
+        let edits = self
+            .selections
+            .iter()
+            .map(|selection| {
+                let text = match self.indentation.use_tabs {
+                    true => "\t".to_string(),
+                    false => " ".repeat(width - self.visual_position(selection.start()).1 % width),
+                };
+                (selection.range(), text)
+            })
+            .collect();
+        self.edit(EditKind::Insert, edits, carets_after);
+    }
+
+    // a level less for every line a selection touches
+    pub fn dedent(&mut self) {
+        self.shift_selected_lines(true);
+    }
+
+    fn shift_selected_lines(&mut self, outdent: bool) {
+        let lines = self.selected_lines();
+        let edits = indent::shift_edits(&self.document, lines, self.indentation, outdent);
+        self.edit_in_place(EditKind::Other, edits);
+    }
+
+    // every line a selection touches, in order
+    pub fn selected_lines(&self) -> Vec<usize> {
+        let document = &self.document;
+        let mut lines: Vec<usize> = self
+            .selections
+            .iter()
+            .flat_map(|selection| {
+                let first = document.line_of_offset(selection.start());
+                let mut last = document.line_of_offset(selection.end());
+                // a selection ending at a line start leaves that line alone
+                if last > first && document.line_start(last) == selection.end() {
+                    last -= 1;
+                }
+                first..=last
+            })
+            .collect();
+        lines.sort_unstable();
+        lines.dedup();
+        lines
+    }
+
+    // edit around the selections, which move along with the text
+    pub fn edit_in_place(&mut self, kind: EditKind, edits: Vec<(Range<usize>, String)>) {
+        if edits.is_empty() {
+            return;
+        }
+        let selections: Vec<Selection> = self
+            .selections
+            .iter()
+            .map(|selection| {
+                Selection::new(
+                    indent::map_offset(selection.anchor, &edits),
+                    indent::map_offset(selection.head, &edits),
+                )
+            })
+            .collect();
+        self.edit(kind, edits, |_| selections);
+    }
+
+    // shift the lines a level in or out, for the operators of modal editing
+    pub fn shift_lines(&mut self, lines: Range<usize>, outdent: bool) {
+        let edits = indent::shift_edits(&self.document, lines, self.indentation, outdent);
+        if !edits.is_empty() {
+            let caret = indent::map_offset(self.caret(), &edits);
+            self.edit(EditKind::Other, edits, |_| vec![Selection::caret(caret)]);
+        }
+    }
+
+    pub fn delete_forward(&mut self) {
+        self.delete_selections(|editor, caret| caret..editor.next_char_boundary(caret));
+    }
+
+    // delete the selected text, or the range given for empty selections
+    fn delete_selections(&mut self, range: impl Fn(&Self, usize) -> Range<usize>) {
+        let edits = self
+            .selections
+            .iter()
+            .map(|selection| match selection.is_empty() {
+                true => (range(self, selection.head), String::new()),
+                false => (selection.range(), String::new()),
+            })
+            .collect();
+        self.edit(EditKind::Delete, edits, carets_after);
+    }
+
+    // text of every selection, the whole line for empty carets
+    fn copied_pieces(&self) -> Vec<String> {
+        self.selections
+            .iter()
+            .map(
+                |selection| match selection.is_empty() && self.block.is_none() {
+                    true => self.document.text()[self.line_range(selection.head)].to_string(),
+                    false => self.selected_text(selection).to_string(),
+                },
+            )
+            .collect()
+    }
+
+    // remember the selected text per selection and return it joined for the system clipboard
+    pub fn copy(&mut self) -> String {
+        self.clipboard = self.copied_pieces();
+        self.clipboard_block = self.block.is_some();
+        self.clipboard.join("\n")
+    }
+
+    pub fn cut(&mut self) -> String {
+        let text = self.copy();
+        let edits = self
+            .selections
+            .iter()
+            .map(
+                |selection| match selection.is_empty() && !self.clipboard_block {
+                    true => (self.line_range(selection.head), String::new()),
+                    false => (selection.range(), String::new()),
+                },
+            )
+            .collect();
+        self.edit(EditKind::Other, edits, carets_after);
+        text
+    }
+
+    // what copy() last returned, for when the system clipboard is unavailable
+    pub fn clipboard_text(&self) -> String {
+        self.clipboard.join("\n")
+    }
+
+    // end the lines of the file this way when it is saved next
+    pub fn convert_line_endings(&mut self, line_ending: LineEnding) {
+        if self.read_only {
+            self.set_message("Buffer is read-only");
+            return;
+        }
+        self.document.set_line_ending(line_ending);
+        self.set_message(format!("Line endings: {line_ending}"));
+    }
+
+    // the same file read again, with a fresh history and the caret at its line and column
+    pub fn reload(&mut self, document: TextDocument) {
+        let line = self.caret_line();
+        let column = self.column_of(self.caret());
+        let mut next = Editor::new(document, self.indentation);
+        next.scroll_line = self.scroll_line;
+        next.viewport_rows = self.viewport_rows;
+        next.wrap_columns = self.wrap_columns;
+        next.clipboard = std::mem::take(&mut self.clipboard);
+        next.clipboard_block = self.clipboard_block;
+        *self = next;
+        self.select(Selection::caret(self.offset_at_column(line, column)));
+    }
+
+    // a copy from as many selections as there are now pastes one piece into each,
+    // a copied block pasted at a single caret goes row by row into the following lines
+    pub fn paste(&mut self, text: &str) {
+        let text = normalize_line_endings(text);
+        let text = text.as_str();
+        let ours = self.clipboard.len() > 1 && self.clipboard.join("\n") == text;
+        if ours && self.clipboard_block && self.selections.len() == 1 && self.primary().is_empty() {
+            self.paste_block(self.clipboard.clone());
+            return;
+        }
+
+        let distribute = ours && self.clipboard.len() == self.selections.len();
+        let edits = self
+            .selections
+            .iter()
+            .enumerate()
+            .map(|(i, selection)| match distribute {
+                true => (selection.range(), self.clipboard[i].clone()),
+                false => (selection.range(), text.to_string()),
+            })
+            .collect();
+        self.edit(EditKind::Other, edits, carets_after);
+    }
+
+    fn paste_block(&mut self, rows: Vec<String>) {
+        let tab_width = self.indentation.width;
+        let (first, column) = self.visual_position(self.caret());
+        let line_count = self.document.line_count();
+
+        let edits = rows
+            .into_iter()
+            .enumerate()
+            .map(|(i, row)| {
+                let line = first + i;
+                if line >= line_count {
+                    let end = self.document.len();
+                    return (end..end, format!("\n{}{row}", " ".repeat(column)));
+                }
+                let text = self.document.line(line);
+                let start = self.document.line_start(line);
+                let width = columns::line_columns(text, tab_width);
+                match width < column {
+                    // pad short lines out to the block column
+                    true => {
+                        let end = start + text.len();
+                        (end..end, format!("{}{row}", " ".repeat(column - width)))
+                    }
+                    false => {
+                        let offset = start + columns::offset_at_column(text, column, tab_width);
+                        (offset..offset, row)
+                    }
+                }
+            })
+            .collect();
+        self.edit(EditKind::Other, edits, carets_after);
+    }
+
+    pub fn block(&self) -> Option<Block> {
+        self.block
+    }
+
+    // line and visual column of the offset, with tabs expanded and wide chars counted twice
+    pub fn visual_position(&self, offset: usize) -> (usize, usize) {
+        let line = self.document.line_of_offset(offset);
+        let start = self.document.line_start(line);
+        let column = columns::visual_column(
+            self.document.line(line),
+            offset - start,
+            self.indentation.width,
+        );
+        (line, column)
+    }
+
+    // one selection per line of the block covering the same visual columns; lines too short
+    // to reach the block are left out unless none of them do
+    pub fn select_block(&mut self, block: Block) {
+        let tab_width = self.indentation.width;
+        let (top, bottom) = (
+            block.anchor.0.min(block.head.0),
+            block.anchor.0.max(block.head.0),
+        );
+        let (left, right) = (
+            block.anchor.1.min(block.head.1),
+            block.anchor.1.max(block.head.1),
+        );
+        let bottom = bottom.min(self.document.line_count() - 1);
+
+        let rows: Vec<_> = (top..=bottom)
+            .map(|line| {
+                let text = self.document.line(line);
+                let start = self.document.line_start(line);
+                let reaches = columns::line_columns(text, tab_width) >= left;
+                let left = start + columns::offset_at_column(text, left, tab_width);
+                let right = start + columns::offset_at_column(text, right, tab_width);
+                let selection = match block.head.1 < block.anchor.1 {
+                    true => Selection::new(right, left),
+                    false => Selection::new(left, right),
+                };
+                (line, reaches, selection)
+            })
+            .collect();
+        let any_reach = rows.iter().any(|(_, reaches, _)| *reaches);
+        let rows: Vec<_> = rows
+            .into_iter()
+            .filter(|(_, reaches, _)| *reaches || !any_reach)
+            .collect();
+
+        let primary = rows
+            .iter()
+            .position(|(line, ..)| *line == block.head.0)
+            .unwrap_or(rows.len() - 1);
+        let selections = rows.into_iter().map(|(.., selection)| selection).collect();
+        self.set_selections(Selections::new(selections, primary));
+        self.block = Some(block);
+    }
+
+    // grow or shrink the block selection, starting one at the caret if there is none
+    pub fn extend_block(&mut self, lines: isize, columns: isize) {
+        let mut block = self.block.unwrap_or_else(|| {
+            let position = self.visual_position(self.caret());
+            Block {
+                anchor: position,
+                head: position,
+            }
+        });
+        let last_line = self.document.line_count() - 1;
+        block.head.0 = block.head.0.saturating_add_signed(lines).min(last_line);
+        block.head.1 = block.head.1.saturating_add_signed(columns);
+        self.select_block(block);
+    }
+
+    // select the word under the caret, or add the next occurrence of the primary selection
+    pub fn select_next_occurrence(&mut self) {
+        let primary = self.primary();
+        if primary.is_empty() {
+            let word = self.word_range(primary.head);
+            self.replace_primary(Selection::new(word.start, word.end));
+            return;
+        }
+
+        let needle = self.selected_text(&primary).to_string();
+        let text = self.document.text();
+        let after = self
+            .selections
+            .iter()
+            .map(Selection::end)
+            .max()
+            .unwrap_or(0);
+        let found = text[after..]
+            .match_indices(&needle)
+            .map(|(i, _)| after + i)
+            .chain(text[..after].match_indices(&needle).map(|(i, _)| i))
+            .find(|start| {
+                !self
+                    .selections
+                    .iter()
+                    .any(|selection| selection.range().contains(start))
+            });
+        match found {
+            Some(start) => self.add_selection(Selection::new(start, start + needle.len())),
+            None => self.set_message("No more occurrences"),
+        }
+    }
+
+    // add a caret on the line above the topmost or below the bottommost selection
+    pub fn add_caret_vertically(&mut self, up: bool) {
+        let edge = match up {
+            true => self.selections.iter().next(),
+            false => self.selections.iter().next_back(),
+        };
+        let Some(&edge) = edge else {
+            return;
+        };
+        let line = self.document.line_of_offset(edge.head);
+        let target = match up {
+            true => line.checked_sub(1),
+            false => Some(line + 1).filter(|line| *line < self.document.line_count()),
+        };
+        let Some(target) = target else {
+            return;
+        };
+        let column = edge.column.unwrap_or_else(|| self.column_of(edge.head));
+        let mut caret = Selection::caret(self.offset_at_column(target, column));
+        caret.column = Some(column);
+        self.add_selection(caret);
+    }
+
+    // move every selection head; without extend the selections collapse to carets
+    // heads moving into a fold go past it
+    fn move_heads(&mut self, extend: bool, target: impl Fn(&Self, &Selection) -> Selection) {
+        let selections = self.selections.map(|selection| {
+            let mut moved = target(self, selection);
+            if let Some(fold) = self.folds.hiding(moved.head) {
+                moved.head = match moved.head > selection.head && fold.end < self.document.len() {
+                    true => fold.end + 1,
+                    false => fold.start,
+                };
+            }
+            match extend {
+                true => Selection {
+                    anchor: selection.anchor,
+                    ..moved
+                },
+                false => Selection {
+                    anchor: moved.head,
+                    ..moved
+                },
+            }
+        });
+        self.set_selections(selections);
+    }
+
+    pub fn move_left(&mut self, extend: bool) {
+        self.move_heads(extend, |editor, selection| {
+            Selection::caret(match selection.is_empty() || extend {
+                true => editor.prev_char_boundary(selection.head),
+                false => selection.start(),
+            })
+        });
+    }
+
+    pub fn move_right(&mut self, extend: bool) {
+        self.move_heads(extend, |editor, selection| {
+            Selection::caret(match selection.is_empty() || extend {
+                true => editor.next_char_boundary(selection.head),
+                false => selection.end(),
+            })
+        });
+    }
+
+    pub fn move_up(&mut self, extend: bool) {
+        self.move_heads(extend, |editor, selection| {
+            let line = editor.document.line_of_offset(selection.head);
+            match editor.prev_visible_line(line) {
+                None => Selection::caret(0),
+                Some(line) => editor.vertical_target(selection, line),
+            }
+        });
+    }
+
+    pub fn move_down(&mut self, extend: bool) {
+        self.move_heads(extend, |editor, selection| {
+            let line = editor.document.line_of_offset(selection.head);
+            match editor.next_visible_line(line) {
+                None => Selection::caret(editor.document.len()),
+                Some(line) => editor.vertical_target(selection, line),
+            }
+        });
+    }
+
+    pub fn move_line_start(&mut self, extend: bool) {
+        self.move_heads(extend, |editor, selection| {
+            let line = editor.document.line_of_offset(selection.head);
+            Selection::caret(editor.document.line_start(line))
+        });
+    }
+
+    pub fn move_line_end(&mut self, extend: bool) {
+        self.move_heads(extend, |editor, selection| {
+            let line = editor.document.line_of_offset(selection.head);
+            Selection::caret(editor.document.line_end(line))
+        });
+    }
+
+    // word, run of whitespace or run of punctuation around the offset
+    pub fn word_range(&self, offset: usize) -> Range<usize> {
+        let line = self.document.line_of_offset(offset);
+        let start = self.document.line_start(line);
+        let text = self.document.line(line);
+        let column = offset - start;
+
+        let class = text[column..]
+            .chars()
+            .next()
+            .or_else(|| text[..column].chars().next_back())
+            .map(CharClass::of);
+        let Some(class) = class else {
+            return offset..offset;
+        };
+
+        let before = text[..column]
+            .char_indices()
+            .rev()
+            .take_while(|(_, c)| CharClass::of(*c) == class)
+            .last()
+            .map_or(column, |(i, _)| i);
+        let after = text[column..]
+            .char_indices()
+            .find(|(_, c)| CharClass::of(*c) != class)
+            .map_or(text.len(), |(i, _)| column + i);
+        start + before..start + after
+    }
+
+    // the whole line around the offset, including its line break
+    pub fn line_range(&self, offset: usize) -> Range<usize> {
+        let line = self.document.line_of_offset(offset);
+        let end = match line + 1 < self.document.line_count() {
+            true => self.document.line_start(line + 1),
+            false => self.document.len(),
+        };
+        self.document.line_start(line)..end
+    }
+
+    // caret on the line at the column the selection head tries to keep
+    fn vertical_target(&self, selection: &Selection, line: usize) -> Selection {
+        let column = selection
+            .column
+            .unwrap_or_else(|| self.column_of(selection.head));
+        let mut target = Selection::caret(self.offset_at_column(line, column));
+        target.column = Some(column);
+        target
+    }
+
+    // column of the offset counted in chars from its line start
+    pub fn column_of(&self, offset: usize) -> usize {
+        let start = self
+            .document
+            .line_start(self.document.line_of_offset(offset));
+        self.document.text()[start..offset].chars().count()
+    }
+
+    // offset of the column on the line, clamped to the line end
+    pub fn offset_at_column(&self, line: usize, column: usize) -> usize {
+        let text = self.document.line(line);
+        let offset = text
+            .char_indices()
+            .nth(column)
+            .map_or(text.len(), |(i, _)| i);
+        self.document.line_start(line) + offset
+    }
+
+    fn prev_char_boundary(&self, offset: usize) -> usize {
+        self.document.text()[..offset]
+            .char_indices()
+            .next_back()
+            .map_or(0, |(i, _)| i)
+    }
+
+    fn next_char_boundary(&self, offset: usize) -> usize {
+        self.document.text()[offset..]
+            .chars()
+            .next()
+            .map_or(offset, |c| offset + c.len_utf8())
+    }
+}
+
+// carets at the end of the inserted ranges
+fn carets_after(inserted: &[Range<usize>]) -> Vec<Selection> {
+    inserted
+        .iter()
+        .map(|range| Selection::caret(range.end))
+        .collect()
+}
+
+#[derive(Clone, Copy, PartialEq, Eq)]
+enum CharClass {
+    Word,
+    Whitespace,
+    Punctuation,
+}
+
+impl CharClass {
+    fn of(c: char) -> Self {
+        if c.is_alphanumeric() || c == '_' {
+            Self::Word
+        } else if c.is_whitespace() {
+            Self::Whitespace
+        } else {
+            Self::Punctuation
+        }
+    }
+}
@@ -0,0 +1,273 @@
+use std::ops::Range;
+
+use crate::{editor::Editor, history::EditKind, selection::Selection};
+
+// kills kept for yanking back, the oldest are dropped
+const KILL_RING_SIZE: usize = 60;
+
+// the text last yanked and which kill it was, for replacing it with an older one
+struct Yank {
+    range: Range<usize>,
+    // kills back from the newest
+    index: usize,
+}
+
+// mark and kill ring of the Emacs bindings
+#[derive(Default)]
+pub struct Emacs {
+    // while the mark is set, moving the caret extends the region from it
+    mark: bool,
+    // oldest first
+    kills: Vec<String>,
+    yank: Option<Yank>,
+}
+
+impl Emacs {
+    pub fn mark_active(&self) -> bool {
+        self.mark
+    }
+
+    pub fn set_mark(&mut self, editor: &mut Editor) {
+        self.mark = true;
+        editor.select(Selection::caret(editor.caret()));
+        editor.set_message("Mark set");
+    }
+
+    pub fn deactivate_mark(&mut self) {
+        self.mark = false;
+    }
+
+    // swap the caret to the other end of the region
+    pub fn exchange_point_and_mark(&mut self, editor: &mut Editor) {
+        let primary = editor.primary();
+        editor.select(Selection::new(primary.head, primary.anchor));
+        self.mark = true;
+    }
+
+    // newest kill, for the system clipboard
+    pub fn latest(&self) -> Option<&str> {
+        self.kills.last().map(String::as_str)
+    }
+
+    // a kill right after another one adds to it, backward kills at its start
+    fn push(&mut self, text: String, append: bool, backward: bool) {
+        match self.kills.last_mut() {
+            Some(last) if append && backward => last.insert_str(0, &text),
+            Some(last) if append => last.push_str(&text),
+            _ => {
+                self.kills.push(text);
+                if self.kills.len() > KILL_RING_SIZE {
+                    self.kills.remove(0);
+                }
+            }
+        }
+    }
+
+    fn kill(&mut self, editor: &mut Editor, range: Range<usize>, append: bool, backward: bool) {
+        if range.is_empty() {
+            return;
+        }
+        let text = editor.document.text()[range.clone()].to_string();
+        self.push(text, append, backward);
+        self.mark = false;
+        editor.edit(EditKind::Other, vec![(range, String::new())], |inserted| {
+            vec![Selection::caret(inserted[0].start)]
+        });
+    }
+
+    // kill to the end of the line, or the line break when the caret is there already
+    pub fn kill_line(&mut self, editor: &mut Editor, append: bool) {
+        let document = &editor.document;
+        let caret = editor.caret();
+        let end = document.line_end(editor.caret_line());
+        let range = match caret == end {
+            true if end == document.len() => {
+                editor.set_message("End of buffer");
+                return;
+            }
+            true => caret..caret + 1,
+            false => caret..end,
+        };
+        self.kill(editor, range, append, false);
+    }
+
+    pub fn kill_region(&mut self, editor: &mut Editor, append: bool) {
+        let range = editor.primary().range();
+        if range.is_empty() {
+            editor.set_message("The region is empty");
+            return;
+        }
+        self.kill(editor, range, append, false);
+    }
+
+    // kill to the end of the next word, or back to the start of the previous one
+    pub fn kill_word(&mut self, editor: &mut Editor, backward: bool, append: bool) {
+        let text = editor.document.text();
+        let caret = editor.caret();
+        let is_word = |c: char| c.is_alphanumeric() || c == '_';
+        let range = match backward {
+            true => {
+                let before = &text[..caret];
+                let word_end = before.trim_end_matches(|c| !is_word(c)).len();
+                let start = before[..word_end].trim_end_matches(is_word).len();
+                start..caret
+            }
+            false => {
+                let after = &text[caret..];
+                let word_start = after.len() - after.trim_start_matches(|c| !is_word(c)).len();
+                let rest = &after[word_start..];
+                let end = word_start + rest.len() - rest.trim_start_matches(is_word).len();
+                caret..caret + end
+            }
+        };
+        self.kill(editor, range, append, backward);
+    }
+
+    // copy the region as a kill without deleting it
+    pub fn copy_region(&mut self, editor: &mut Editor, append: bool) {
+        let primary = editor.primary();
+        let text = editor.selected_text(&primary).to_string();
+        self.push(text, append, false);
+        self.mark = false;
+        editor.select(Selection::caret(primary.head));
+    }
+
+    // insert the newest kill, or the text given when there is none
+    pub fn yank(&mut self, editor: &mut Editor, fallback: Option<String>) {
+        let Some(text) = self.kills.last().cloned().or(fallback) else {
+            editor.set_message("Kill ring is empty");
+            return;
+        };
+        self.mark = false;
+        self.insert(editor, text, 0);
+    }
+
+    // replace the text just yanked with the kill before it
+    pub fn yank_pop(&mut self, editor: &mut Editor, after_yank: bool) {
+        let (Some(yank), true) = (self.yank.take(), after_yank) else {
+            editor.set_message("Previous command was not a yank");
+            return;
+        };
+        if self.kills.is_empty() {
+            return;
+        }
+        let index = (yank.index + 1) % self.kills.len();
+        let text = self.kills[self.kills.len() - 1 - index].clone();
+        editor.select(Selection::new(yank.range.start, yank.range.end));
+        self.insert(editor, text, index);
+    }
+
+    fn insert(&mut self, editor: &mut Editor, text: String, index: usize) {
+        let range = editor.primary().range();
+        let mut yanked = None;
+        editor.edit(EditKind::Other, vec![(range, text)], |inserted| {
+            yanked = Some(inserted[0].clone());
+            vec![Selection::caret(inserted[0].end)]
+        });
+        self.yank = yanked.map(|range| Yank { range, index });
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{editor::Indentation, text_document::TextDocument};
+
+    fn editor(text: &str, caret: usize) -> Editor {
+        let mut editor = Editor::new(TextDocument::from_text(text), Indentation::default());
+        editor.set_viewport(20, None);
+        editor.select(Selection::caret(caret));
+        editor
+    }
+
+    #[test]
+    fn consecutive_kills_add_to_one_kill() {
+        let mut editor = editor("one two\nthree four", 0);
+        let mut emacs = Emacs::default();
+        emacs.kill_line(&mut editor, false);
+        emacs.kill_line(&mut editor, true);
+        assert_eq!(editor.document.text(), "three four");
+        assert_eq!(emacs.latest(), Some("one two\n"));
+
+        // a kill after something else starts a new one
+        emacs.kill_word(&mut editor, false, false);
+        assert_eq!(emacs.latest(), Some("three"));
+        assert_eq!(emacs.kills.len(), 2);
+
+        // backward kills go in front
+        editor.select(Selection::caret(editor.document.len()));
+        emacs.kill_word(&mut editor, true, false);
+        emacs.kill_word(&mut editor, true, true);
+        assert_eq!(editor.document.text(), "");
+        assert_eq!(emacs.latest(), Some(" four"));
+    }
+
+    #[test]
+    fn kill_line_stops_at_the_end_of_the_buffer() {
+        let mut editor = editor("ab", 2);
+        let mut emacs = Emacs::default();
+        emacs.kill_line(&mut editor, false);
+        assert_eq!(editor.document.text(), "ab");
+        assert_eq!(emacs.latest(), None);
+    }
+
+    #[test]
+    fn yank_pop_goes_back_through_the_kills() {
+        let mut editor = editor("a b c ", 0);
+        let mut emacs = Emacs::default();
+        for _ in 0..3 {
+            emacs.kill_word(&mut editor, false, false);
+            editor.move_right(false);
+        }
+        assert_eq!(editor.document.text(), "   ");
+
+        editor.select(Selection::caret(0));
+        emacs.yank(&mut editor, None);
+        assert_eq!(editor.document.text(), "c   ");
+        emacs.yank_pop(&mut editor, true);
+        assert_eq!(editor.document.text(), "b   ");
+        emacs.yank_pop(&mut editor, true);
+        assert_eq!(editor.document.text(), "a   ");
+        // back round to the newest
+        emacs.yank_pop(&mut editor, true);
+        assert_eq!(editor.document.text(), "c   ");
+        assert_eq!(editor.caret(), 1);
+
+        // only right after a yank
+        emacs.yank_pop(&mut editor, false);
+        assert_eq!(editor.document.text(), "c   ");
+    }
+
+    #[test]
+    fn yank_without_kills_takes_the_fallback() {
+        let mut editor = editor("", 0);
+        let mut emacs = Emacs::default();
+        emacs.yank(&mut editor, Some("clip".into()));
+        assert_eq!(editor.document.text(), "clip");
+    }
+
+    #[test]
+    fn the_region_runs_from_the_mark_to_the_caret() {
+        let mut editor = editor("one two three", 4);
+        let mut emacs = Emacs::default();
+        emacs.set_mark(&mut editor);
+        assert!(emacs.mark_active());
+        for _ in 0..3 {
+            editor.move_right(emacs.mark_active());
+        }
+        assert_eq!(editor.primary(), Selection::new(4, 7));
+
+        emacs.exchange_point_and_mark(&mut editor);
+        assert_eq!(editor.primary(), Selection::new(7, 4));
+
+        emacs.copy_region(&mut editor, false);
+        assert!(!emacs.mark_active());
+        assert_eq!(emacs.latest(), Some("two"));
+        assert_eq!(editor.primary(), Selection::caret(4));
+
+        editor.select(Selection::new(0, 4));
+        emacs.kill_region(&mut editor, false);
+        assert_eq!(editor.document.text(), "two three");
+        assert_eq!(emacs.latest(), Some("one "));
+    }
+}
@@ -0,0 +1,438 @@
+use std::fmt;
+
+use chardetng::EncodingDetector;
+use encoding_rs::{DecoderResult, REPLACEMENT, UTF_16BE, UTF_16LE, UTF_8};
+use glyph_brush::OwnedSection;
+use winit::keyboard::{Key, ModifiersState, NamedKey};
+
+use crate::{
+    picker::{Picker, Response},
+    utils::RectVertex,
+    view::View,
+};
+
+// bytes the encoding can't decode become the chars from here on, one per byte, so the
+// text shows them and saving writes them back unchanged; those chars really in the file
+// are read as the bytes they were written as, so they come back unchanged too
+const UNDECODABLE: u32 = 0x10ff00;
+// bytes looked at to tell UTF-16 without a byte order mark
+const SAMPLE: usize = 4096;
+
+// offered by the picker, any other label encoding_rs knows can be typed
+const ENCODINGS: &[&str] = &[
+    "UTF-8",
+    "UTF-8 with BOM",
+    "UTF-16LE",
+    "UTF-16BE",
+    "windows-1252",
+    "ISO-8859-15",
+    "ISO-8859-2",
+    "windows-1250",
+    "windows-1251",
+    "KOI8-R",
+    "windows-1253",
+    "windows-1254",
+    "windows-1255",
+    "windows-1256",
+    "windows-1257",
+    "windows-874",
+    "Shift_JIS",
+    "EUC-JP",
+    "GBK",
+    "gb18030",
+    "Big5",
+    "EUC-KR",
+];
+
+pub fn undecodable_byte(c: char) -> Option<u8> {
+    (c as u32).checked_sub(UNDECODABLE).map(|byte| byte as u8)
+}
+
+fn undecodable(byte: u8) -> char {
+    char::from_u32(UNDECODABLE + byte as u32).unwrap()
+}
+
+// how a file's bytes map to text
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct Encoding {
+    encoding: &'static encoding_rs::Encoding,
+    // written at the start of the file on save
+    bom: bool,
+}
+
+impl Default for Encoding {
+    fn default() -> Self {
+        Self {
+            encoding: UTF_8,
+            bom: false,
+        }
+    }
+}
+
+impl Encoding {
+    // a name from the picker or any label of the WHATWG encoding standard
+    pub fn for_label(label: &str) -> Option<Self> {
+        let label = label.trim();
+        if label.eq_ignore_ascii_case("UTF-8 with BOM") {
+            return Some(Self {
+                encoding: UTF_8,
+                bom: true,
+            });
+        }
+        let encoding = encoding_rs::Encoding::for_label(label.as_bytes())?;
+        (encoding != REPLACEMENT).then_some(Self {
+            encoding,
+            bom: encoding == UTF_16LE || encoding == UTF_16BE,
+        })
+    }
+
+    // the byte order mark if there is one, else UTF-16 by where the zero bytes are, else
+    // UTF-8 if the bytes mostly are, else the legacy encoding the text reads best in
+    pub fn detect(bytes: &[u8]) -> Self {
+        if let Some((encoding, _)) = encoding_rs::Encoding::for_bom(bytes) {
+            return Self {
+                encoding,
+                bom: true,
+            };
+        }
+        if let Some(encoding) = utf16_by_zeros(bytes) {
+            return Self {
+                encoding,
+                bom: false,
+            };
+        }
+        if mostly_utf8(bytes) {
+            return Self::default();
+        }
+        let mut detector = EncodingDetector::new();
+        detector.feed(bytes, true);
+        Self {
+            encoding: detector.guess(None, false),
+            bom: false,
+        }
+    }
+
+    fn bom_bytes(self) -> &'static [u8] {
+        match self.encoding {
+            encoding if encoding == UTF_16LE => b"\xff\xfe",
+            encoding if encoding == UTF_16BE => b"\xfe\xff",
+            _ => b"\xef\xbb\xbf",
+        }
+    }
+
+    // the text of the bytes, a byte order mark dropped
+    pub fn decode(self, bytes: &[u8]) -> String {
+        let bytes = match encoding_rs::Encoding::for_bom(bytes) {
+            Some((encoding, length)) if encoding == self.encoding => &bytes[length..],
+            _ => bytes,
+        };
+        let mut decoder = self.encoding.new_decoder_without_bom_handling();
+        let mut text = String::new();
+        let mut read = 0;
+        loop {
+            let rest = &bytes[read..];
+            text.reserve(
+                decoder
+                    .max_utf8_buffer_length_without_replacement(rest.len())
+                    .unwrap_or(rest.len()),
+            );
+            let decoded = text.len();
+            let (result, consumed) =
+                decoder.decode_to_string_without_replacement(rest, &mut text, true);
+            read += consumed;
+            self.escape_lookalikes(&mut text, decoded);
+            match result {
+                DecoderResult::InputEmpty => return text,
+                DecoderResult::OutputFull => {}
+                // the malformed bytes end `extra` bytes before where decoding stopped
+                DecoderResult::Malformed(length, extra) => {
+                    let end = read - extra as usize;
+                    let start = end.saturating_sub(length as usize);
+                    text.extend(bytes[start..end].iter().map(|byte| undecodable(*byte)));
+                }
+            }
+        }
+    }
+
+    // the chars decoded from `from` on that would be taken for undecodable bytes made the
+    // bytes they were written as; only encodings covering all of Unicode decode to them
+    fn escape_lookalikes(self, text: &mut String, from: usize) {
+        if !text[from..].chars().any(|c| undecodable_byte(c).is_some()) {
+            return;
+        }
+        let decoded = text.split_off(from);
+        for c in decoded.chars() {
+            let mut bytes = Vec::new();
+            match undecodable_byte(c).is_some()
+                && self
+                    .encode_run(c.encode_utf8(&mut [0; 4]), &mut bytes)
+                    .is_ok()
+            {
+                true => text.extend(bytes.into_iter().map(undecodable)),
+                false => text.push(c),
+            }
+        }
+    }
+
+    // the bytes of the text, an error naming the first char the encoding has no bytes for
+    pub fn encode(self, text: &str) -> Result<Vec<u8>, String> {
+        let mut bytes = Vec::with_capacity(text.len());
+        if self.bom {
+            bytes.extend_from_slice(self.bom_bytes());
+        }
+        let mut start = 0;
+        for (i, c) in text.char_indices() {
+            if let Some(byte) = undecodable_byte(c) {
+                self.encode_run(&text[start..i], &mut bytes)?;
+                bytes.push(byte);
+                start = i + c.len_utf8();
+            }
+        }
+        self.encode_run(&text[start..], &mut bytes)?;
+        Ok(bytes)
+    }
+
+    fn encode_run(self, text: &str, bytes: &mut Vec<u8>) -> Result<(), String> {
+        // encoding_rs only decodes UTF-16
+        if self.encoding == UTF_16LE {
+            bytes.extend(text.encode_utf16().flat_map(u16::to_le_bytes));
+            return Ok(());
+        }
+        if self.encoding == UTF_16BE {
+            bytes.extend(text.encode_utf16().flat_map(u16::to_be_bytes));
+            return Ok(());
+        }
+        let (encoded, _, unmappable) = self.encoding.encode(text);
+        if unmappable {
+            let c = text
+                .chars()
+                .find(|c| self.encoding.encode(c.encode_utf8(&mut [0; 4])).2)
+                .unwrap_or(char::REPLACEMENT_CHARACTER);
+            return Err(format!("{c:?} can't be written in {self}"));
+        }
+        bytes.extend_from_slice(&encoded);
+        Ok(())
+    }
+}
+
+impl fmt::Display for Encoding {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.write_str(self.encoding.name())?;
+        if self.bom && self.encoding == UTF_8 {
+            f.write_str(" with BOM")?;
+        }
+        Ok(())
+    }
+}
+
+// text in UTF-16 has a zero in nearly every other byte, ASCII being its most common chars
+fn utf16_by_zeros(bytes: &[u8]) -> Option<&'static encoding_rs::Encoding> {
+    let sample = &bytes[..bytes.len().min(SAMPLE) & !1];
+    let pairs = sample.len() / 2;
+    if pairs == 0 {
+        return None;
+    }
+    let zeros = |parity: usize| {
+        sample
+            .iter()
+            .skip(parity)
+            .step_by(2)
+            .filter(|byte| **byte == 0)
+            .count()
+    };
+    let (even, odd) = (zeros(0), zeros(1));
+    match (even * 20 < pairs, odd * 20 < pairs) {
+        (true, false) if odd * 5 >= pairs * 2 => Some(UTF_16LE),
+        (false, true) if even * 5 >= pairs * 2 => Some(UTF_16BE),
+        _ => None,
+    }
+}
+
+// UTF-8 but for a few stray bytes, which are shown as undecodable rather than the whole
+// file being read in a legacy encoding
+fn mostly_utf8(bytes: &[u8]) -> bool {
+    let (mut decoded, mut invalid) = (0, 0);
+    for chunk in bytes.utf8_chunks() {
+        decoded += chunk.valid().chars().filter(|c| !c.is_ascii()).count();
+        invalid += chunk.invalid().len();
+    }
+    invalid == 0 || decoded > invalid
+}
+
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub enum EncodingAction {
+    // read the file again in the encoding
+    #[default]
+    Reopen,
+    // write the file in the encoding from the next save on
+    Save,
+}
+
+// overlay choosing the encoding to reopen or save the file in
+#[derive(Default)]
+pub struct EncodingPicker {
+    open: bool,
+    action: EncodingAction,
+    picker: Picker,
+    // of the shown file, marked in the list
+    current: Encoding,
+    chosen: Option<(EncodingAction, Encoding)>,
+}
+
+impl EncodingPicker {
+    pub fn is_open(&self) -> bool {
+        self.open
+    }
+
+    pub fn open(&mut self, action: EncodingAction, current: Encoding) {
+        self.open = true;
+        self.action = action;
+        self.current = current;
+        self.picker.reset();
+        self.update();
+    }
+
+    pub fn close(&mut self) {
+        self.open = false;
+    }
+
+    pub fn take_chosen(&mut self) -> Option<(EncodingAction, Encoding)> {
+        self.chosen.take()
+    }
+
+    fn update(&mut self) {
+        self.picker
+            .update(ENCODINGS.iter().copied(), |a, b| a.cmp(&b));
+    }
+
+    pub fn paste(&mut self, text: &str) {
+        self.picker.paste(text);
+        self.update();
+    }
+
+    // handle a key while the overlay is open, false to let the editor have it
+    pub fn handle_key(&mut self, key: &Key, modifiers: ModifiersState) -> bool {
+        match key {
+            Key::Named(NamedKey::Escape) => self.close(),
+            Key::Named(NamedKey::Enter) => {
+                // a label matching none of the list is taken as typed
+                let label = match self.picker.selected() {
+                    Some(index) => ENCODINGS[index],
+                    None => self.picker.query.text(),
+                };
+                if let Some(encoding) = Encoding::for_label(label) {
+                    self.chosen = Some((self.action, encoding));
+                    self.close();
+                }
+            }
+            key => match self.picker.handle_key(key) {
+                Response::Ignored => return !modifiers.control_key(),
+                Response::Moved => {}
+                Response::Edited => self.update(),
+            },
+        }
+        true
+    }
+
+    pub fn sections(&self, view: &View) -> Vec<OwnedSection> {
+        let status = match self.action {
+            EncodingAction::Reopen => "reopen with encoding",
+            EncodingAction::Save => "save with encoding",
+        };
+        self.picker.sections(
+            view,
+            status,
+            |index| ENCODINGS[index],
+            |index| (ENCODINGS[index] == self.current.to_string()).then(|| "current".to_string()),
+        )
+    }
+
+    pub fn rects(&self, view: &View) -> Vec<RectVertex> {
+        self.picker.rects(view)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn encoding(label: &str) -> Encoding {
+        Encoding::for_label(label).unwrap()
+    }
+
+    // the text of the bytes and whether saving it gives the bytes back
+    fn round_trip(encoding: Encoding, bytes: &[u8]) -> (String, bool) {
+        let text = encoding.decode(bytes);
+        let same = encoding.encode(&text).as_deref() == Ok(bytes);
+        (text, same)
+    }
+
+    #[test]
+    fn detects_byte_order_marks() {
+        assert_eq!(
+            Encoding::detect(b"\xef\xbb\xbfhi"),
+            encoding("UTF-8 with BOM")
+        );
+        assert_eq!(Encoding::detect(b"\xff\xfeh\0i\0"), encoding("UTF-16LE"));
+        assert_eq!(Encoding::detect(b"\xfe\xff\0h\0i"), encoding("UTF-16BE"));
+    }
+
+    #[test]
+    fn detects_utf16_without_a_byte_order_mark() {
+        let text = "plain text in UTF-16";
+        let le: Vec<u8> = text.encode_utf16().flat_map(u16::to_le_bytes).collect();
+        let be: Vec<u8> = text.encode_utf16().flat_map(u16::to_be_bytes).collect();
+        let detected = Encoding::detect(&le);
+        assert_eq!(detected.to_string(), "UTF-16LE");
+        assert_eq!(round_trip(detected, &le), (text.to_string(), true));
+        let detected = Encoding::detect(&be);
+        assert_eq!(detected.to_string(), "UTF-16BE");
+        assert_eq!(round_trip(detected, &be), (text.to_string(), true));
+    }
+
+    #[test]
+    fn detects_utf8_and_legacy_encodings() {
+        assert_eq!(
+            Encoding::detect("naïve café".as_bytes()),
+            Encoding::default()
+        );
+        assert_eq!(Encoding::detect(b"plain ascii"), Encoding::default());
+        let latin = b"Le caf\xe9 est d\xe9j\xe0 servi \xe0 la cr\xe8me br\xfbl\xe9e.";
+        let detected = Encoding::detect(latin);
+        assert_eq!(detected.to_string(), "windows-1252");
+        assert_eq!(
+            round_trip(detected, latin),
+            (
+                "Le café est déjà servi à la crème brûlée.".to_string(),
+                true
+            )
+        );
+    }
+
+    #[test]
+    fn malformed_bytes_come_back_unchanged() {
+        let bytes = b"caf\xc3\xa9 \xff\xfe bad \xc3";
+        let (text, same) = round_trip(Encoding::default(), bytes);
+        assert!(same);
+        assert!(text.starts_with("café "));
+        let undecodable: Vec<u8> = text.chars().filter_map(undecodable_byte).collect();
+        assert_eq!(undecodable, b"\xff\xfe\xc3");
+    }
+
+    #[test]
+    fn private_use_chars_like_undecodable_bytes_come_back_unchanged() {
+        let text = "a\u{10ff41}b\u{10ffff}";
+        for label in ["UTF-8", "UTF-16LE", "UTF-16BE", "gb18030"] {
+            let bytes = encoding(label).encode(text).unwrap();
+            assert!(round_trip(encoding(label), &bytes).1, "{label}");
+        }
+    }
+
+    #[test]
+    fn names_the_char_an_encoding_cannot_write() {
+        let error = encoding("windows-1252").encode("a\u{3042}b").unwrap_err();
+        assert_eq!(error, "'\u{3042}' can't be written in windows-1252");
+        assert_eq!(Encoding::for_label("no such encoding"), None);
+        assert_eq!(encoding("latin1").to_string(), "windows-1252");
+    }
+}
@@ -0,0 +1,194 @@
+use std::{
+    path::{Path, PathBuf},
+    sync::{
+        atomic::{AtomicBool, Ordering},
+        mpsc::{self, Receiver, TryRecvError},
+        Arc,
+    },
+    thread,
+};
+
+use glyph_brush::OwnedSection;
+use ignore::WalkBuilder;
+use winit::keyboard::{Key, ModifiersState, NamedKey};
+
+use crate::{
+    buffers::Buffers,
+    editor::Editor,
+    picker::{Picker, Response},
+    utils::RectVertex,
+    view::View,
+};
+
+// the walk stops here so a huge tree can't eat the memory
+const MAX_FILES: usize = 100_000;
+// paths sent from the indexing thread at a time
+const BATCH: usize = 256;
+
+struct Candidate {
+    path: PathBuf,
+    // relative to the root, with / separators, what is matched and shown
+    shown: String,
+}
+
+// Ctrl+P overlay listing the files under the working directory that fuzzy match the query
+#[derive(Default)]
+pub struct FileFinder {
+    open: bool,
+    picker: Picker,
+    root: PathBuf,
+    candidates: Vec<Candidate>,
+    // indexing thread, None once it is done
+    receiver: Option<Receiver<Vec<PathBuf>>>,
+    cancel: Arc<AtomicBool>,
+}
+
+impl FileFinder {
+    pub fn is_open(&self) -> bool {
+        self.open
+    }
+
+    // open the overlay and index the files under the root again in the background
+    pub fn open(&mut self, root: PathBuf) {
+        self.cancel.store(true, Ordering::Relaxed);
+        self.cancel = Arc::default();
+        let (sender, receiver) = mpsc::channel();
+        let cancel = self.cancel.clone();
+        let walk_root = root.clone();
+        thread::spawn(move || {
+            let mut batch = Vec::with_capacity(BATCH);
+            // .gitignore files apply even outside of a git repository
+            let files = WalkBuilder::new(&walk_root)
+                .require_git(false)
+                .build()
+                .filter_map(Result::ok)
+                .filter(|entry| entry.file_type().is_some_and(|kind| kind.is_file()))
+                .take(MAX_FILES);
+            for entry in files {
+                if cancel.load(Ordering::Relaxed) {
+                    return;
+                }
+                batch.push(entry.into_path());
+                if batch.len() == BATCH && sender.send(std::mem::take(&mut batch)).is_err() {
+                    return;
+                }
+            }
+            let _ = sender.send(batch);
+        });
+
+        self.open = true;
+        self.picker.reset();
+        self.root = root;
+        self.candidates.clear();
+        self.receiver = Some(receiver);
+    }
+
+    pub fn close(&mut self) {
+        self.open = false;
+        self.cancel.store(true, Ordering::Relaxed);
+        self.receiver = None;
+    }
+
+    // take the paths indexed since the last call
+    pub fn poll(&mut self) {
+        let Some(receiver) = &self.receiver else {
+            return;
+        };
+        let count = self.candidates.len();
+        loop {
+            match receiver.try_recv() {
+                Ok(paths) => {
+                    for path in paths {
+                        let shown = shown_path(&self.root, &path);
+                        self.candidates.push(Candidate { path, shown });
+                    }
+                }
+                Err(TryRecvError::Empty) => break,
+                Err(TryRecvError::Disconnected) => {
+                    self.receiver = None;
+                    break;
+                }
+            }
+        }
+        if self.candidates.len() != count {
+            self.update_results();
+        }
+    }
+
+    fn update_results(&mut self) {
+        let candidates = &self.candidates;
+        // shorter paths first among equal scores, they are usually what is meant
+        self.picker.update(
+            candidates.iter().map(|candidate| candidate.shown.as_str()),
+            |a, b| {
+                let (a, b) = (&candidates[a].shown, &candidates[b].shown);
+                a.len().cmp(&b.len()).then(a.cmp(b))
+            },
+        );
+    }
+
+    pub fn paste(&mut self, text: &str) {
+        self.picker.paste(text);
+        self.update_results();
+    }
+
+    // handle a key while the overlay is open, false to let the editor have it
+    pub fn handle_key(
+        &mut self,
+        editor: &mut Editor,
+        buffers: &mut Buffers,
+        key: &Key,
+        modifiers: ModifiersState,
+    ) -> bool {
+        match key {
+            Key::Named(NamedKey::Escape) => self.close(),
+            Key::Named(NamedKey::Enter) => {
+                if let Some(index) = self.picker.selected() {
+                    let path = &self.candidates[index].path;
+                    if let Err(e) = buffers.open(editor, path) {
+                        editor.set_message(format!("{}: {e}", path.display()));
+                    }
+                }
+                self.close();
+            }
+            key => match self.picker.handle_key(key) {
+                Response::Ignored => return !modifiers.control_key(),
+                Response::Moved => {}
+                Response::Edited => self.update_results(),
+            },
+        }
+        true
+    }
+
+    pub fn sections(&self, view: &View) -> Vec<OwnedSection> {
+        let status = match (&self.receiver, self.candidates.len()) {
+            (Some(_), count) => format!("indexing… {count} files"),
+            (None, count) => format!("{} of {count}", self.picker.results().len()),
+        };
+        self.picker.sections(
+            view,
+            &status,
+            |index| self.candidates[index].shown.as_str(),
+            |_| None,
+        )
+    }
+
+    pub fn rects(&self, view: &View) -> Vec<RectVertex> {
+        self.picker.rects(view)
+    }
+}
+
+impl Drop for FileFinder {
+    fn drop(&mut self) {
+        self.cancel.store(true, Ordering::Relaxed);
+    }
+}
+
+fn shown_path(root: &Path, path: &Path) -> String {
+    let relative = path.strip_prefix(root).unwrap_or(path);
+    relative
+        .components()
+        .map(|component| component.as_os_str().to_string_lossy())
+        .collect::<Vec<_>>()
+        .join("/")
+}
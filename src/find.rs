@@ -0,0 +1,542 @@
+use std::ops::Range;
+
+use glyph_brush::{Extra, HorizontalAlign, Layout, OwnedSection, OwnedText};
+use regex::{Regex, RegexBuilder};
+use winit::keyboard::{Key, ModifiersState, NamedKey};
+
+use crate::{
+    editor::Editor, history::EditKind, input::LineInput, selection::Selection, utils::RectVertex,
+    view::View,
+};
+
+const BACKGROUND_COLOR: [f32; 4] = [0.08, 0.08, 0.1, 1.0];
+const LABEL_COLOR: [f32; 4] = [0.5, 0.5, 0.55, 1.0];
+const TEXT_COLOR: [f32; 4] = [0.9, 0.9, 0.9, 1.0];
+const OPTION_OFF_COLOR: [f32; 4] = [0.35, 0.35, 0.4, 1.0];
+const OPTION_ON_COLOR: [f32; 4] = [0.4, 0.7, 1.0, 1.0];
+const ERROR_COLOR: [f32; 4] = [0.9, 0.4, 0.35, 1.0];
+const CARET_COLOR: [f32; 4] = [0.9, 0.9, 0.9, 1.0];
+pub const MATCH_COLOR: [f32; 4] = [0.4, 0.3, 0.05, 1.0];
+const LABEL_COLUMNS: usize = 9;
+
+// what to look for and how, shared by the find bar and project search
+#[derive(Clone, Debug, Default, PartialEq, Eq)]
+pub struct SearchQuery {
+    pub pattern: String,
+    pub regex: bool,
+    pub case_sensitive: bool,
+    pub whole_word: bool,
+}
+
+impl SearchQuery {
+    pub fn compile(&self) -> Result<Regex, regex::Error> {
+        let pattern = match self.regex {
+            true => self.pattern.clone(),
+            false => regex::escape(&self.pattern),
+        };
+        let pattern = match self.whole_word {
+            true => format!(r"\b(?:{pattern})\b"),
+            false => pattern,
+        };
+        RegexBuilder::new(&pattern)
+            .case_insensitive(!self.case_sensitive)
+            .multi_line(true)
+            .build()
+    }
+
+    // replacement for a match, with $1 and ${name} expanded in regex mode; none when the
+    // regex doesn't match the range again, so there is nothing to expand from
+    pub fn expand(
+        &self,
+        regex: &Regex,
+        text: &str,
+        range: Range<usize>,
+        replacement: &str,
+    ) -> Option<String> {
+        if !self.regex {
+            return Some(replacement.into());
+        }
+        let captures = regex
+            .captures_at(text, range.start)
+            .filter(|captures| captures.get(0).is_some_and(|m| m.range() == range))?;
+        let mut expanded = String::new();
+        captures.expand(replacement, &mut expanded);
+        Some(expanded)
+    }
+}
+
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub enum FindMode {
+    #[default]
+    Find,
+    Replace,
+    // search the files under the working directory
+    Project,
+}
+
+// find and replace bar shown above the status bar
+#[derive(Default)]
+pub struct FindBar {
+    open: bool,
+    mode: FindMode,
+    focus_replacement: bool,
+    pub query: LineInput,
+    pub replacement: LineInput,
+    pub regex: bool,
+    pub case_sensitive: bool,
+    pub whole_word: bool,
+    // matches of the current query, recomputed when the query or the document changes
+    matches: Vec<Range<usize>>,
+    matched: Option<(SearchQuery, u64)>,
+    error: Option<String>,
+    // project search query submitted with Enter, picked up by the caller
+    submitted: Option<SearchQuery>,
+}
+
+impl FindBar {
+    pub fn is_open(&self) -> bool {
+        self.open
+    }
+
+    // open the bar, seeding the query from a single line selection
+    pub fn open(&mut self, editor: &Editor, mode: FindMode) {
+        let primary = editor.primary();
+        let selected = editor.selected_text(&primary);
+        if !selected.is_empty() && !selected.contains('\n') {
+            self.query.set_text(selected);
+        }
+        self.open = true;
+        self.mode = mode;
+        self.focus_replacement = mode == FindMode::Replace && !self.query.text().is_empty();
+        self.refresh(editor);
+    }
+
+    pub fn close(&mut self) {
+        self.open = false;
+    }
+
+    pub fn search_query(&self) -> SearchQuery {
+        SearchQuery {
+            pattern: self.query.text().into(),
+            regex: self.regex,
+            case_sensitive: self.case_sensitive,
+            whole_word: self.whole_word,
+        }
+    }
+
+    pub fn matches(&self) -> &[Range<usize>] {
+        match self.open && self.mode != FindMode::Project {
+            true => &self.matches,
+            false => &[],
+        }
+    }
+
+    pub fn take_submitted(&mut self) -> Option<SearchQuery> {
+        self.submitted.take()
+    }
+
+    fn submit(&mut self) {
+        match query_error(&self.search_query()) {
+            Some(error) => self.error = Some(error),
+            None => {
+                self.submitted = Some(self.search_query());
+                self.close();
+            }
+        }
+    }
+
+    // recompute the matches if the query or the document changed since the last time
+    pub fn refresh(&mut self, editor: &Editor) {
+        let query = self.search_query();
+        let revision = editor.document.revision();
+        if self
+            .matched
+            .as_ref()
+            .is_some_and(|(matched, matched_revision)| {
+                *matched == query && *matched_revision == revision
+            })
+        {
+            return;
+        }
+
+        self.matches.clear();
+        self.error = None;
+        if !query.pattern.is_empty() {
+            match query.compile() {
+                Ok(regex) => self.matches.extend(
+                    regex
+                        .find_iter(editor.document.text())
+                        .map(|m| m.range())
+                        .filter(|range| !range.is_empty()),
+                ),
+                Err(e) => self.error = Some(e.to_string()),
+            }
+        }
+        self.matched = Some((query, revision));
+    }
+
+    // index of the match the primary selection covers exactly
+    fn current(&self, editor: &Editor) -> Option<usize> {
+        let range = editor.primary().range();
+        self.matches.iter().position(|m| *m == range)
+    }
+
+    // select the next or previous match from the primary selection, wrapping around
+    pub fn find(&mut self, editor: &mut Editor, forward: bool) {
+        self.refresh(editor);
+        let primary = editor.primary();
+        let found = match forward {
+            true => self
+                .matches
+                .iter()
+                .find(|m| m.start >= primary.end() && *m != &primary.range())
+                .or(self.matches.first()),
+            false => self
+                .matches
+                .iter()
+                .rev()
+                .find(|m| m.start < primary.start())
+                .or(self.matches.last()),
+        };
+        match found {
+            Some(m) => editor.select(Selection::new(m.start, m.end)),
+            None => editor.set_message("No matches"),
+        }
+    }
+
+    // select the first match at or after the caret while the query is typed
+    fn find_incremental(&mut self, editor: &mut Editor) {
+        if self.mode == FindMode::Project {
+            self.error = None;
+            return;
+        }
+        self.refresh(editor);
+        let start = editor.primary().start();
+        let found = self
+            .matches
+            .iter()
+            .find(|m| m.start >= start)
+            .or(self.matches.first());
+        if let Some(m) = found {
+            editor.select(Selection::new(m.start, m.end));
+        }
+    }
+
+    // replace the selected match and move to the next one
+    pub fn replace_one(&mut self, editor: &mut Editor) {
+        self.refresh(editor);
+        let query = self.search_query();
+        let Some(index) = self.current(editor) else {
+            self.find(editor, true);
+            return;
+        };
+        let Ok(regex) = query.compile() else {
+            return;
+        };
+
+        let range = self.matches[index].clone();
+        let Some(text) = query.expand(
+            &regex,
+            editor.document.text(),
+            range.clone(),
+            self.replacement.text(),
+        ) else {
+            editor.set_message("Can't expand the replacement for this match");
+            return;
+        };
+        editor.edit(EditKind::Other, vec![(range, text)], |inserted| {
+            vec![Selection::caret(inserted[0].end)]
+        });
+        self.find(editor, true);
+    }
+
+    // replace every match as a single undo step
+    pub fn replace_all(&mut self, editor: &mut Editor) {
+        self.refresh(editor);
+        let query = self.search_query();
+        let Ok(regex) = query.compile() else {
+            return;
+        };
+        if self.matches.is_empty() {
+            editor.set_message("No matches");
+            return;
+        }
+
+        // matches the replacement can't be expanded for are left as they are
+        let text = editor.document.text();
+        let edits: Vec<_> = self
+            .matches
+            .iter()
+            .filter_map(|m| {
+                let replacement = query.expand(&regex, text, m.clone(), self.replacement.text())?;
+                Some((m.clone(), replacement))
+            })
+            .collect();
+        let count = edits.len();
+        let skipped = self.matches.len() - count;
+        if edits.is_empty() {
+            editor.set_message(format!("Skipped {skipped} occurrences"));
+            return;
+        }
+        let caret = editor.caret();
+        editor.edit(EditKind::Other, edits, |inserted| {
+            // keep the caret near where it was
+            let offset = inserted
+                .iter()
+                .rev()
+                .find(|range| range.start <= caret)
+                .map_or(caret.min(inserted[0].start), |range| range.end);
+            vec![Selection::caret(offset)]
+        });
+        editor.set_message(match skipped {
+            0 => format!("Replaced {count} occurrences"),
+            _ => format!("Replaced {count} occurrences, skipped {skipped}"),
+        });
+    }
+
+    // paste into the focused field
+    pub fn paste(&mut self, editor: &mut Editor, text: &str) {
+        match self.focus_replacement {
+            true => self.replacement.insert(text),
+            false => {
+                self.query.insert(text);
+                self.find_incremental(editor);
+            }
+        }
+    }
+
+    // handle a key while the bar is open, false to let the editor have it
+    pub fn handle_key(
+        &mut self,
+        editor: &mut Editor,
+        key: &Key,
+        modifiers: ModifiersState,
+    ) -> bool {
+        let (ctrl, shift, alt) = (
+            modifiers.control_key(),
+            modifiers.shift_key(),
+            modifiers.alt_key(),
+        );
+        match key {
+            Key::Named(NamedKey::Escape) => self.close(),
+            Key::Named(NamedKey::Enter) if self.mode == FindMode::Project => self.submit(),
+            Key::Named(NamedKey::Enter) if ctrl && alt => self.replace_all(editor),
+            Key::Named(NamedKey::Enter) if self.focus_replacement => self.replace_one(editor),
+            Key::Named(NamedKey::Enter) => self.find(editor, !shift),
+            Key::Named(NamedKey::Tab) if self.mode == FindMode::Replace => {
+                self.focus_replacement = !self.focus_replacement
+            }
+            // shortcuts like Ctrl+H go to the key bindings
+            Key::Character(_) if ctrl => return false,
+            Key::Character(c) if alt => {
+                match c.to_lowercase().as_str() {
+                    "c" => self.case_sensitive = !self.case_sensitive,
+                    "w" => self.whole_word = !self.whole_word,
+                    "r" => self.regex = !self.regex,
+                    _ => return true,
+                }
+                self.find_incremental(editor);
+            }
+            key => {
+                let field = match self.focus_replacement {
+                    true => &mut self.replacement,
+                    false => &mut self.query,
+                };
+                let before = field.text().to_string();
+                if !field.handle_key(key) {
+                    return !ctrl;
+                }
+                if !self.focus_replacement && self.query.text() != before {
+                    self.find_incremental(editor);
+                }
+            }
+        }
+        true
+    }
+
+    pub fn height(&self, view: &View) -> f32 {
+        match (self.open, self.mode) {
+            (false, _) => 0.0,
+            (true, FindMode::Replace) => 2.0 * view.line_height,
+            (true, _) => view.line_height,
+        }
+    }
+
+    fn status(&self, editor: &Editor) -> (String, [f32; 4]) {
+        if let Some(error) = &self.error {
+            // regex errors span several lines, the last one says what is wrong
+            let last = error.lines().last().unwrap_or_default().trim();
+            return (last.into(), ERROR_COLOR);
+        }
+        if self.mode == FindMode::Project {
+            return (String::new(), LABEL_COLOR);
+        }
+        let status = match (self.current(editor), self.matches.len()) {
+            (_, 0) if self.query.text().is_empty() => String::new(),
+            (_, 0) => "No results".into(),
+            (Some(index), count) => format!("{} of {count}", index + 1),
+            (None, count) => format!("{count} matches"),
+        };
+        (status, LABEL_COLOR)
+    }
+
+    pub fn sections(&self, view: &View, editor: &Editor, top: f32) -> Vec<OwnedSection> {
+        let label = match self.mode {
+            FindMode::Project => "Project",
+            _ => "Find",
+        };
+        let mut sections = vec![Self::field_section(view, top, label, &self.query)];
+        if self.mode == FindMode::Replace {
+            sections.push(Self::field_section(
+                view,
+                top + view.line_height,
+                "Replace",
+                &self.replacement,
+            ));
+        }
+
+        let option = |label: &str, on: bool| {
+            let color = match on {
+                true => OPTION_ON_COLOR,
+                false => OPTION_OFF_COLOR,
+            };
+            OwnedText::new(format!("{label}  "))
+                .with_scale(view.scale)
+                .with_color(color)
+        };
+        let (status, status_color) = self.status(editor);
+        sections.push(
+            OwnedSection::<Extra>::default()
+                .with_text(vec![
+                    option("Aa", self.case_sensitive),
+                    option("W", self.whole_word),
+                    option(".*", self.regex),
+                    OwnedText::new(status)
+                        .with_scale(view.scale)
+                        .with_color(status_color),
+                ])
+                .with_screen_position((view.width - view.char_width, top))
+                .with_bounds((view.width, view.line_height))
+                .with_layout(Layout::default_single_line().h_align(HorizontalAlign::Right)),
+        );
+        sections
+    }
+
+    fn field_section(view: &View, top: f32, label: &str, input: &LineInput) -> OwnedSection {
+        OwnedSection::<Extra>::default()
+            .with_text(vec![
+                OwnedText::new(format!("{label:>width$} ", width = LABEL_COLUMNS - 1))
+                    .with_scale(view.scale)
+                    .with_color(LABEL_COLOR),
+                OwnedText::new(input.text())
+                    .with_scale(view.scale)
+                    .with_color(TEXT_COLOR),
+            ])
+            .with_screen_position((0.0, top))
+            .with_bounds((view.width, view.line_height))
+            .with_layout(Layout::default_single_line())
+    }
+
+    pub fn rects(&self, view: &View, top: f32) -> Vec<RectVertex> {
+        let [r, g, b, a] = BACKGROUND_COLOR;
+        let background = [0.0, top, view.width, top + self.height(view), r, g, b, a];
+
+        let (input, row) = match self.focus_replacement {
+            true => (&self.replacement, 1.0),
+            false => (&self.query, 0.0),
+        };
+        let x = (LABEL_COLUMNS + input.cursor_column()) as f32 * view.char_width;
+        let y = top + row * view.line_height;
+        let [r, g, b, a] = CARET_COLOR;
+        let caret = [x, y, x + 2.0, y + view.line_height, r, g, b, a];
+        vec![background, caret]
+    }
+}
+
+fn query_error(query: &SearchQuery) -> Option<String> {
+    match query.pattern.is_empty() {
+        true => Some("Nothing to search for".into()),
+        false => query.compile().err().map(|e| e.to_string()),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{editor::Indentation, text_document::TextDocument};
+
+    fn query(pattern: &str, regex: bool, case_sensitive: bool, whole_word: bool) -> SearchQuery {
+        SearchQuery {
+            pattern: pattern.into(),
+            regex,
+            case_sensitive,
+            whole_word,
+        }
+    }
+
+    fn found<'a>(query: &SearchQuery, text: &'a str) -> Vec<&'a str> {
+        let regex = query.compile().unwrap();
+        regex.find_iter(text).map(|m| m.as_str()).collect()
+    }
+
+    #[test]
+    fn matches_text_literally_or_as_a_regex() {
+        let text = "a.c abc A.C";
+        assert_eq!(found(&query("a.c", false, true, false), text), vec!["a.c"]);
+        assert_eq!(
+            found(&query("a.c", true, true, false), text),
+            vec!["a.c", "abc"]
+        );
+        assert_eq!(
+            found(&query("a.c", false, false, false), text),
+            vec!["a.c", "A.C"]
+        );
+        assert!(query("(", true, true, false).compile().is_err());
+    }
+
+    #[test]
+    fn matches_whole_words() {
+        let text = "cat catalog concat cat";
+        assert_eq!(
+            found(&query("cat", false, true, true), text),
+            vec!["cat", "cat"]
+        );
+        assert_eq!(
+            found(&query("cat|dog", true, true, true), "dogma cat"),
+            vec!["cat"]
+        );
+    }
+
+    #[test]
+    fn expands_captures_in_regex_mode() {
+        let text = "let x = f(1, 2);";
+        let query = query(r"f\((\d), (?<b>\d)\)", true, true, false);
+        let regex = query.compile().unwrap();
+        let range = regex.find(text).unwrap().range();
+        assert_eq!(
+            query.expand(&regex, text, range.clone(), "f(${b}, $1)"),
+            Some("f(2, 1)".into())
+        );
+        // a range the regex doesn't match has nothing to expand from
+        assert_eq!(query.expand(&regex, text, 0..3, "$1"), None);
+
+        let literal = SearchQuery {
+            regex: false,
+            ..query
+        };
+        assert_eq!(literal.expand(&regex, text, range, "$1"), Some("$1".into()));
+    }
+
+    #[test]
+    fn replace_all_is_one_undo_step() {
+        let text = "foo bar foo baz foo";
+        let mut editor = Editor::new(TextDocument::from_text(text), Indentation::default());
+        let mut bar = FindBar::default();
+        bar.query.set_text("foo");
+        bar.replacement.set_text("qux");
+        bar.replace_all(&mut editor);
+        assert_eq!(editor.document.text(), "qux bar qux baz qux");
+        editor.undo();
+        assert_eq!(editor.document.text(), text);
+    }
+}
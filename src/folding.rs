@@ -0,0 +1,251 @@
+use std::ops::Range;
+
+use crate::{columns, history::Change, indent, text_document::TextDocument};
+
+pub const FOLD_MARKER: &str = " ⋯";
+
+// collapsed ranges of the document, each from the end of its header line, which stays in
+// view, to the end of its last hidden line
+#[derive(Clone, Debug, Default)]
+pub struct Folds {
+    // sorted and apart from each other
+    ranges: Vec<Range<usize>>,
+    // set once the folds saved for the file were applied
+    pub restored: bool,
+}
+
+impl Folds {
+    pub fn is_empty(&self) -> bool {
+        self.ranges.is_empty()
+    }
+
+    pub fn ranges(&self) -> &[Range<usize>] {
+        &self.ranges
+    }
+
+    // collapse the range, taking in the folds inside it
+    pub fn fold(&mut self, range: Range<usize>) {
+        if range.is_empty() {
+            return;
+        }
+        self.ranges
+            .retain(|fold| fold.end < range.start || fold.start > range.end);
+        let index = self.ranges.partition_point(|fold| fold.start < range.start);
+        self.ranges.insert(index, range);
+    }
+
+    // expand the fold starting at the offset, false if there is none
+    pub fn unfold_at(&mut self, start: usize) -> bool {
+        let count = self.ranges.len();
+        self.ranges.retain(|fold| fold.start != start);
+        self.ranges.len() != count
+    }
+
+    pub fn clear(&mut self) {
+        self.ranges.clear();
+    }
+
+    // the fold hiding the offset
+    pub fn hiding(&self, offset: usize) -> Option<&Range<usize>> {
+        let index = self.ranges.partition_point(|fold| fold.end < offset);
+        self.ranges
+            .get(index)
+            .filter(|fold| fold.start < offset && offset <= fold.end)
+    }
+
+    // expand the folds that hide any of the offsets
+    pub fn reveal(&mut self, offsets: impl IntoIterator<Item = usize>) {
+        for offset in offsets {
+            if let Some(start) = self.hiding(offset).map(|fold| fold.start) {
+                self.unfold_at(start);
+            }
+        }
+    }
+
+    // follow a change to the text; folds after it move, the ones it touches are expanded
+    pub fn update(&mut self, change: &Change) {
+        let end = change.offset + change.deleted.len();
+        let delta = change.inserted.len() as isize - change.deleted.len() as isize;
+        self.ranges.retain_mut(|fold| {
+            if end <= fold.start {
+                fold.start = fold.start.saturating_add_signed(delta);
+                fold.end = fold.end.saturating_add_signed(delta);
+                true
+            } else {
+                change.offset > fold.end
+            }
+        });
+    }
+
+    // the folds as (header, last hidden) lines, for keeping them between runs
+    pub fn lines(&self, document: &TextDocument) -> Vec<[usize; 2]> {
+        self.ranges
+            .iter()
+            .map(|fold| {
+                [
+                    document.line_of_offset(fold.start),
+                    document.line_of_offset(fold.end),
+                ]
+            })
+            .collect()
+    }
+}
+
+// the part of the document a fold from the header to the last line hides
+pub fn hidden_range(document: &TextDocument, header: usize, last: usize) -> Range<usize> {
+    document.line_end(header)..document.line_end(last)
+}
+
+fn indent_width(line: &str, tab_width: usize) -> usize {
+    columns::line_columns(indent::leading(line), tab_width)
+}
+
+fn is_blank(document: &TextDocument, line: usize) -> bool {
+    document.line(line).trim().is_empty()
+}
+
+// whether the next line with text is indented deeper, so a fold can start at the line
+pub fn is_header(document: &TextDocument, line: usize, tab_width: usize) -> bool {
+    if is_blank(document, line) {
+        return false;
+    }
+    let width = indent_width(document.line(line), tab_width);
+    (line + 1..document.line_count())
+        .find(|next| !is_blank(document, *next))
+        .is_some_and(|next| indent_width(document.line(next), tab_width) > width)
+}
+
+// last line of the block the header opens: the lines indented deeper than it, without
+// the blank lines after them
+pub fn block_end(document: &TextDocument, header: usize, tab_width: usize) -> usize {
+    let width = indent_width(document.line(header), tab_width);
+    let mut last = header;
+    for line in header + 1..document.line_count() {
+        if is_blank(document, line) {
+            continue;
+        }
+        if indent_width(document.line(line), tab_width) <= width {
+            break;
+        }
+        last = line;
+    }
+    last
+}
+
+// the innermost block by indentation that opens at the line or holds it, as its header
+// and last line
+pub fn block_at(document: &TextDocument, line: usize, tab_width: usize) -> Option<(usize, usize)> {
+    if is_header(document, line, tab_width) {
+        return Some((line, block_end(document, line, tab_width)));
+    }
+    // a blank line belongs with the text after it
+    let text_line = (line..document.line_count()).find(|line| !is_blank(document, *line))?;
+    let width = indent_width(document.line(text_line), tab_width);
+    let header = (0..line).rev().find(|above| {
+        !is_blank(document, *above) && indent_width(document.line(*above), tab_width) < width
+    })?;
+    Some((header, block_end(document, header, tab_width)))
+}
+
+// the blocks by indentation not inside another one
+pub fn outer_blocks(document: &TextDocument, tab_width: usize) -> Vec<(usize, usize)> {
+    let mut blocks = Vec::new();
+    let mut line = 0;
+    while line < document.line_count() {
+        if is_header(document, line, tab_width) {
+            let last = block_end(document, line, tab_width);
+            blocks.push((line, last));
+            line = last;
+        }
+        line += 1;
+    }
+    blocks
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::editor::{Editor, Indentation};
+
+    // two blocks, blank lines inside and after the first and at the end of the file
+    const TEXT: &str = "fn a\n    b\n\n    c\n\nd\n    e\n";
+
+    fn change(offset: usize, deleted: &str, inserted: &str) -> Change {
+        Change {
+            offset,
+            deleted: deleted.into(),
+            inserted: inserted.into(),
+        }
+    }
+
+    #[test]
+    fn blocks_leave_out_the_blank_lines_after_them() {
+        let document = TextDocument::from_text(TEXT);
+        assert!(is_header(&document, 0, 4));
+        assert!(!is_header(&document, 2, 4));
+        assert!(!is_header(&document, 6, 4));
+        assert_eq!(block_end(&document, 0, 4), 3);
+        assert_eq!(block_end(&document, 5, 4), 6);
+        assert_eq!(outer_blocks(&document, 4), vec![(0, 3), (5, 6)]);
+    }
+
+    #[test]
+    fn blank_lines_belong_with_the_text_after_them() {
+        let document = TextDocument::from_text(TEXT);
+        assert_eq!(block_at(&document, 1, 4), Some((0, 3)));
+        assert_eq!(block_at(&document, 2, 4), Some((0, 3)));
+        assert_eq!(block_at(&document, 4, 4), None);
+        assert_eq!(block_at(&document, 6, 4), Some((5, 6)));
+        // nothing after the blank line at the end of the file
+        assert_eq!(block_at(&document, 7, 4), None);
+    }
+
+    #[test]
+    fn folds_follow_edits_around_them() {
+        let document = TextDocument::from_text(TEXT);
+        let range = hidden_range(&document, 0, 3);
+        assert_eq!(range, 4..17);
+        let folded = || {
+            let mut folds = Folds::default();
+            folds.fold(range.clone());
+            folds
+        };
+
+        // above it, and typing at the end of the header, moves it
+        let mut folds = folded();
+        folds.update(&change(0, "", "pub "));
+        assert_eq!(folds.ranges().first(), Some(&(8..21)));
+        let mut folds = folded();
+        folds.update(&change(4, "", "()"));
+        assert_eq!(folds.ranges().first(), Some(&(6..19)));
+        // below it leaves it be
+        let mut folds = folded();
+        folds.update(&change(19, "d", "x"));
+        assert_eq!(folds.ranges().first(), Some(&(4..17)));
+        // inside it or across its end expands it
+        let mut folds = folded();
+        folds.update(&change(9, "b", ""));
+        assert!(folds.is_empty());
+        let mut folds = folded();
+        folds.update(&change(16, "c\n", ""));
+        assert!(folds.is_empty());
+    }
+
+    #[test]
+    fn folds_hide_the_lines_under_their_header() {
+        let mut editor = Editor::new(TextDocument::from_text(TEXT), Indentation::default());
+        editor.folds.fold(hidden_range(&editor.document, 0, 3));
+        assert!(editor.is_folded(0));
+        assert!(!editor.is_folded(5));
+        // the end of the header shows, the lines under it don't
+        assert!(editor.folds.hiding(4).is_none());
+        for line in 1..=3 {
+            let start = editor.document.line_start(line);
+            assert!(editor.folds.hiding(start).is_some(), "line {line}");
+        }
+        assert_eq!(editor.next_visible_line(0), Some(4));
+        assert_eq!(editor.prev_visible_line(4), Some(0));
+        assert_eq!(editor.next_visible_line(7), None);
+        assert_eq!(editor.folds.lines(&editor.document), vec![[0, 3]]);
+    }
+}
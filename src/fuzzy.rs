@@ -0,0 +1,170 @@
+// fuzzy subsequence matching for the pickers: every query char has to appear in order,
+// matches at the start of path segments and words, camelCase humps and runs of consecutive
+// chars score higher, gaps between matched chars cost a little and leading chars are free
+
+const SCORE_MATCH: i32 = 16;
+const BONUS_SEGMENT: i32 = 12;
+const BONUS_WORD: i32 = 8;
+const BONUS_CAMEL: i32 = 8;
+const BONUS_CONSECUTIVE: i32 = 6;
+const BONUS_CASE: i32 = 1;
+// matches in the last path segment usually mean the file name
+const BONUS_LAST_SEGMENT: i32 = 4;
+const PENALTY_GAP_START: i32 = 3;
+const PENALTY_GAP: i32 = 1;
+
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct Match {
+    pub score: i32,
+    // char indices of the matched chars in the candidate
+    pub positions: Vec<usize>,
+}
+
+// best alignment of the query in the candidate, None if it is not a subsequence;
+// the query matches case-insensitively
+pub fn fuzzy_match(query: &str, candidate: &str) -> Option<Match> {
+    let query: Vec<char> = query.chars().filter(|c| !c.is_whitespace()).collect();
+    if query.is_empty() {
+        return Some(Match {
+            score: 0,
+            positions: Vec::new(),
+        });
+    }
+    let chars: Vec<char> = candidate.chars().collect();
+    if !is_subsequence(&query, &chars) {
+        return None;
+    }
+
+    let last_segment = chars
+        .iter()
+        .rposition(|&c| c == '/' || c == '\\')
+        .map_or(0, |i| i + 1);
+    let bonus: Vec<i32> = (0..chars.len())
+        .map(|j| {
+            let boundary = boundary_bonus(j.checked_sub(1).map(|i| chars[i]), chars[j]);
+            match j >= last_segment {
+                true => boundary + BONUS_LAST_SEGMENT,
+                false => boundary,
+            }
+        })
+        .collect();
+
+    // scores[i][j]: best score with query[i] matched at chars[j], from[i][j]: where
+    // query[i - 1] was matched for it
+    let (n, m) = (query.len(), chars.len());
+    let mut scores = vec![vec![None; m]; n];
+    let mut from = vec![vec![0; m]; n];
+    for i in 0..n {
+        // best score of query[i - 1] matched before j, less the gap up to j
+        let mut best: Option<(i32, usize)> = None;
+        for j in i..m {
+            if i > 0 && j > 0 {
+                if let Some(previous) = scores[i - 1][j - 1] {
+                    if best.is_none_or(|(score, _)| previous - PENALTY_GAP_START > score) {
+                        best = Some((previous - PENALTY_GAP_START, j - 1));
+                    }
+                }
+            }
+            if !chars[j].to_lowercase().eq(query[i].to_lowercase()) {
+                if let Some((score, _)) = best.as_mut() {
+                    *score -= PENALTY_GAP;
+                }
+                continue;
+            }
+
+            let own = SCORE_MATCH + bonus[j] + if chars[j] == query[i] { BONUS_CASE } else { 0 };
+            let score = match i {
+                0 => Some((own, 0)),
+                _ => {
+                    // consecutive matches skip the gap penalty and get a bonus
+                    let consecutive = (j > 0)
+                        .then(|| scores[i - 1][j - 1])
+                        .flatten()
+                        .map(|previous| (previous + own + BONUS_CONSECUTIVE, j - 1));
+                    let gapped = best.map(|(previous, k)| (previous + own, k));
+                    match (consecutive, gapped) {
+                        (Some(c), Some(g)) if g.0 > c.0 => Some(g),
+                        (Some(c), _) => Some(c),
+                        (None, g) => g,
+                    }
+                }
+            };
+            if let Some((score, k)) = score {
+                scores[i][j] = Some(score);
+                from[i][j] = k;
+            }
+            if let Some((score, _)) = best.as_mut() {
+                *score -= PENALTY_GAP;
+            }
+        }
+    }
+
+    let (score, mut j) = (0..m)
+        .filter_map(|j| scores[n - 1][j].map(|score| (score, j)))
+        .max_by_key(|&(score, j)| (score, std::cmp::Reverse(j)))?;
+    let mut positions = vec![0; n];
+    for i in (0..n).rev() {
+        positions[i] = j;
+        j = from[i][j];
+    }
+    Some(Match { score, positions })
+}
+
+fn is_subsequence(query: &[char], chars: &[char]) -> bool {
+    let mut chars = chars.iter();
+    query.iter().all(|q| {
+        chars
+            .by_ref()
+            .any(|c| c.to_lowercase().eq(q.to_lowercase()))
+    })
+}
+
+fn boundary_bonus(previous: Option<char>, c: char) -> i32 {
+    match previous {
+        None | Some('/') | Some('\\') => BONUS_SEGMENT,
+        Some('_' | '-' | '.' | ' ') => BONUS_WORD,
+        Some(p) if p.is_lowercase() && c.is_uppercase() => BONUS_CAMEL,
+        Some(p) if !p.is_ascii_digit() && c.is_ascii_digit() => BONUS_WORD,
+        _ => 0,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn positions(query: &str, candidate: &str) -> Option<Vec<usize>> {
+        fuzzy_match(query, candidate).map(|found| found.positions)
+    }
+
+    fn score(query: &str, candidate: &str) -> i32 {
+        fuzzy_match(query, candidate).unwrap().score
+    }
+
+    #[test]
+    fn matches_subsequences_only() {
+        assert_eq!(positions("abc", "a_b_c"), Some(vec![0, 2, 4]));
+        assert_eq!(positions("ABC", "abc"), Some(vec![0, 1, 2]));
+        assert_eq!(positions("acb", "abc"), None);
+        assert_eq!(positions("", "anything"), Some(Vec::new()));
+        assert_eq!(positions("a b", "ab"), Some(vec![0, 1]));
+    }
+
+    #[test]
+    fn prefers_boundaries_and_runs() {
+        // the file name rather than the scattered chars of the directory
+        assert_eq!(
+            positions("main", "src/my_aim_in/main.rs"),
+            Some(vec![14, 15, 16, 17])
+        );
+        assert_eq!(positions("fb", "fooBar"), Some(vec![0, 3]));
+        assert_eq!(positions("fb", "xfoo_bar"), Some(vec![1, 5]));
+        assert!(score("edit", "src/editor.rs") > score("edit", "src/emacs/dist.rs"));
+        assert!(score("te", "text_editor") > score("te", "out_eject"));
+    }
+
+    #[test]
+    fn exact_case_scores_a_little_higher() {
+        assert!(score("Read", "Readme") > score("Read", "readme"));
+    }
+}
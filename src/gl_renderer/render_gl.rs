@@ -7,9 +7,12 @@ use gl::types::{GLint, GLsizeiptr, GLuint, GLvoid};
 
 use crate::{
     gl_assert_ok,
-    utils::{compile_shader, link_programs, ortho, Res, Vertex},
+    utils::{compile_shader, link_programs, ortho, RectVertex, Res, Vertex},
 };
 
+// depth of text drawn above the overlay backgrounds, see `split_overlay`
+pub const OVERLAY_Z: f32 = 0.5;
+
 pub struct GlGlyphTexture {
     pub name: GLuint,
 }
@@ -210,6 +213,7 @@ impl GLTextPipe {
     // draw text
     pub fn draw(&self) {
         unsafe {
+            gl::BlendFunc(gl::SRC_ALPHA, gl::ONE);
             gl::UseProgram(self.program);
             gl::BindVertexArray(self.vao);
             gl::DrawArraysInstanced(gl::TRIANGLE_STRIP, 0, 4, self.vertex_count as i32);
@@ -228,3 +232,162 @@ impl Drop for GLTextPipe {
         }
     }
 }
+
+pub struct GLRectPipe {
+    shaders: [GLuint; 2],
+    program: GLuint,
+    vao: GLuint,
+    vbo: GLuint,
+    transform_uniform: GLint,
+    rect_count: usize,
+    rect_buffer_len: usize,
+}
+
+impl GLRectPipe {
+    pub fn new(window_size: winit::dpi::PhysicalSize<u32>) -> Res<Self> {
+        let (w, h) = (window_size.width as f32, window_size.height as f32);
+
+        let fs = compile_shader(include_str!("shaders/rect.fs"), gl::FRAGMENT_SHADER)?;
+        let vs = compile_shader(include_str!("shaders/rect.vs"), gl::VERTEX_SHADER)?;
+        let program = link_programs(vs, fs)?;
+
+        let mut vao = 0;
+        let mut vbo = 0;
+
+        let transform_uniform = unsafe {
+            // create and bind vao
+            gl::GenVertexArrays(1, &mut vao);
+            gl::BindVertexArray(vao);
+
+            // create and bind vbo
+            gl::GenBuffers(1, &mut vbo);
+            gl::BindBuffer(gl::ARRAY_BUFFER, vbo);
+
+            //use shader program
+            gl::UseProgram(program);
+            gl::BindFragDataLocation(program, 0, CString::new("out_color")?.as_ptr());
+
+            //get location of transform uniform variable in the program
+            let uniform = gl::GetUniformLocation(program, CString::new("transform")?.as_ptr());
+            if uniform < 0 {
+                return Err(format!("GetUniformLocation(\"transform\") -> {uniform}").into());
+            }
+
+            //transform the vertices
+            let transform = ortho(0.0, w, 0.0, h, 1.0, -1.0);
+            gl::UniformMatrix4fv(uniform, 1, 0, transform.as_ptr());
+
+            // one instance per rectangle, same layout as RectVertex
+            let mut offset = 0;
+            for (v_field, float_count) in &[("left_top", 2), ("right_bottom", 2), ("color", 4)] {
+                let attr = gl::GetAttribLocation(program, CString::new(*v_field)?.as_ptr());
+                if attr < 0 {
+                    return Err(format!("{v_field} GetAttribLocation -> {attr}").into());
+                }
+
+                gl::VertexAttribPointer(
+                    attr as u32,
+                    *float_count,
+                    gl::FLOAT,
+                    gl::FALSE,
+                    mem::size_of::<RectVertex>() as i32,
+                    offset as *const c_void,
+                );
+                gl::EnableVertexAttribArray(attr as u32);
+                gl::VertexAttribDivisor(attr as u32, 1);
+
+                offset += float_count * 4;
+            }
+            gl_assert_ok!();
+
+            uniform
+        };
+
+        Ok(Self {
+            shaders: [vs, fs],
+            program,
+            vao,
+            vbo,
+            transform_uniform,
+            rect_count: 0,
+            rect_buffer_len: 0,
+        })
+    }
+
+    // update rectangle data, called every frame
+    pub fn upload_rects(&mut self, rects: &[RectVertex]) {
+        self.rect_count = rects.len();
+        if rects.is_empty() {
+            return;
+        }
+
+        unsafe {
+            gl::BindVertexArray(self.vao);
+            gl::BindBuffer(gl::ARRAY_BUFFER, self.vbo);
+
+            // resize buffer or update buffer
+            if self.rect_buffer_len < self.rect_count {
+                gl::BufferData(
+                    gl::ARRAY_BUFFER,
+                    (self.rect_count * mem::size_of::<RectVertex>()) as GLsizeiptr,
+                    rects.as_ptr() as *const GLvoid,
+                    gl::DYNAMIC_DRAW,
+                );
+                self.rect_buffer_len = self.rect_count;
+            } else {
+                gl::BufferSubData(
+                    gl::ARRAY_BUFFER,
+                    0,
+                    (self.rect_count * mem::size_of::<RectVertex>()) as GLsizeiptr,
+                    rects.as_ptr() as *const GLvoid,
+                );
+            }
+
+            gl_assert_ok!();
+        }
+    }
+
+    // update transformation based on window size
+    pub fn update_geometry(&self, window_size: winit::dpi::PhysicalSize<u32>) {
+        let (w, h) = (window_size.width as f32, window_size.height as f32);
+        let transform = ortho(0.0, w, 0.0, h, 1.0, -1.0);
+
+        unsafe {
+            gl::UseProgram(self.program);
+            gl::UniformMatrix4fv(self.transform_uniform, 1, 0, transform.as_ptr());
+            gl_assert_ok!();
+        }
+    }
+
+    // draw rectangles
+    pub fn draw(&self) {
+        if self.rect_count == 0 {
+            return;
+        }
+
+        unsafe {
+            // rects cover what is below them, overlays hide the text under them
+            gl::BlendFunc(gl::SRC_ALPHA, gl::ONE_MINUS_SRC_ALPHA);
+            gl::UseProgram(self.program);
+            gl::BindVertexArray(self.vao);
+            gl::DrawArraysInstanced(gl::TRIANGLE_STRIP, 0, 4, self.rect_count as i32);
+            gl_assert_ok!();
+        }
+    }
+}
+
+impl Drop for GLRectPipe {
+    fn drop(&mut self) {
+        unsafe {
+            gl::DeleteProgram(self.program);
+            self.shaders.iter().for_each(|s| gl::DeleteShader(*s));
+            gl::DeleteBuffers(1, &self.vbo);
+            gl::DeleteVertexArrays(1, &self.vao);
+        }
+    }
+}
+
+// split the glyph vertices into the ones below and above the overlays, by their depth
+pub fn split_overlay(vertices: &[Vertex]) -> (Vec<Vertex>, Vec<Vertex>) {
+    vertices.iter().partition(|vertex| vertex[2] < OVERLAY_Z)
+}
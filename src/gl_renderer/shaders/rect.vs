@@ -0,0 +1,39 @@
+#version 150
+
+const mat4 INVERT_Y_AXIS = mat4(
+    vec4(1.0, 0.0, 0.0, 0.0),
+    vec4(0.0, -1.0, 0.0, 0.0),
+    vec4(0.0, 0.0, 1.0, 0.0),
+    vec4(0.0, 0.0, 0.0, 1.0)
+);
+
+uniform mat4 transform;
+
+in vec2 left_top;
+in vec2 right_bottom;
+in vec4 color;
+
+out vec4 f_color;
+
+// generate positional data based on vertex ID
+void main() {
+    vec2 pos = vec2(0.0);
+
+    switch (gl_VertexID) {
+        case 0:
+            pos = left_top;
+            break;
+        case 1:
+            pos = vec2(right_bottom.x, left_top.y);
+            break;
+        case 2:
+            pos = vec2(left_top.x, right_bottom.y);
+            break;
+        case 3:
+            pos = right_bottom;
+            break;
+    }
+
+    f_color = color;
+    gl_Position = INVERT_Y_AXIS * transform * vec4(pos, 0.0, 1.0);
+}
@@ -0,0 +1,218 @@
+use glyph_brush::{Extra, HorizontalAlign, Layout, OwnedSection, OwnedText};
+use winit::keyboard::{Key, ModifiersState, NamedKey};
+
+use crate::{
+    editor::Editor,
+    input::LineInput,
+    selection::{Selection, Selections},
+    utils::RectVertex,
+    view::View,
+};
+
+const BACKGROUND_COLOR: [f32; 4] = [0.08, 0.08, 0.1, 1.0];
+const LABEL_COLOR: [f32; 4] = [0.5, 0.5, 0.55, 1.0];
+const TEXT_COLOR: [f32; 4] = [0.9, 0.9, 0.9, 1.0];
+const ERROR_COLOR: [f32; 4] = [0.9, 0.4, 0.35, 1.0];
+const CARET_COLOR: [f32; 4] = [0.9, 0.9, 0.9, 1.0];
+const LABEL: &str = "   Go to ";
+const HINT: &str = "line, line:column, +lines, -lines or percent";
+
+// the line and column, both from 0, the input stands for from the line of the caret:
+// `12`, `12:5`, `+3`, `-3` or `50%`; lines are counted from 1 and clamped to the document
+pub fn parse(input: &str, caret_line: usize, line_count: usize) -> Result<(usize, usize), String> {
+    let input = input.trim();
+    let invalid = || format!("Not a line: {input}");
+    let number = |text: &str| text.trim().parse::<usize>().map_err(|_| invalid());
+    let last = line_count - 1;
+    let (line, column) = match input.split_once(':') {
+        Some((line, column)) => (line, Some(number(column)?.saturating_sub(1))),
+        None => (input, None),
+    };
+    let line = if let Some(percent) = line.strip_suffix('%') {
+        let percent = number(percent)?.min(100);
+        (line_count * percent / 100).min(last)
+    } else if let Some(lines) = line.strip_prefix('+') {
+        caret_line.saturating_add(number(lines)?).min(last)
+    } else if let Some(lines) = line.strip_prefix('-') {
+        caret_line.saturating_sub(number(lines)?)
+    } else {
+        number(line)?.saturating_sub(1).min(last)
+    };
+    Ok((line, column.unwrap_or(0)))
+}
+
+// bar above the status bar for going to a line; the caret follows the typed position and
+// goes back when the bar is dismissed
+#[derive(Default)]
+pub struct GotoBar {
+    open: bool,
+    input: LineInput,
+    // where the caret was when the bar opened
+    origin: Option<(Selections, usize)>,
+    error: Option<String>,
+}
+
+impl GotoBar {
+    pub fn is_open(&self) -> bool {
+        self.open
+    }
+
+    pub fn open(&mut self, editor: &Editor) {
+        self.open = true;
+        self.input.set_text("");
+        self.error = None;
+        self.origin = Some((editor.selections().clone(), editor.scroll_line));
+    }
+
+    // close the bar, putting the caret back where it was
+    pub fn cancel(&mut self, editor: &mut Editor) {
+        self.open = false;
+        if let Some((selections, scroll_line)) = self.origin.take() {
+            editor.set_selections(selections);
+            editor.scroll_line = scroll_line;
+        }
+    }
+
+    // move the caret to the typed position, false when there is none
+    fn preview(&mut self, editor: &mut Editor) -> bool {
+        self.error = None;
+        if self.input.text().trim().is_empty() {
+            return false;
+        }
+        let caret_line = match &self.origin {
+            Some((selections, _)) => editor.document.line_of_offset(selections.primary().head),
+            None => editor.caret_line(),
+        };
+        match parse(self.input.text(), caret_line, editor.document.line_count()) {
+            Ok((line, column)) => {
+                editor.select(Selection::caret(editor.offset_at_column(line, column)));
+                editor.scroll_to_center();
+                true
+            }
+            Err(e) => {
+                self.error = Some(e);
+                false
+            }
+        }
+    }
+
+    pub fn paste(&mut self, editor: &mut Editor, text: &str) {
+        self.input.insert(text);
+        self.preview(editor);
+    }
+
+    // handle a key while the bar is open, false to let the editor have it
+    pub fn handle_key(
+        &mut self,
+        editor: &mut Editor,
+        key: &Key,
+        modifiers: ModifiersState,
+    ) -> bool {
+        match key {
+            Key::Named(NamedKey::Escape) => self.cancel(editor),
+            Key::Named(NamedKey::Enter) => {
+                if self.preview(editor) {
+                    self.open = false;
+                }
+            }
+            Key::Character(_) if modifiers.control_key() => return false,
+            key => {
+                let before = self.input.text().to_string();
+                if !self.input.handle_key(key) {
+                    return !modifiers.control_key();
+                }
+                if self.input.text() != before && !self.preview(editor) {
+                    // nothing to go to yet, the caret waits where it was
+                    if let Some((selections, scroll_line)) = self.origin.clone() {
+                        editor.set_selections(selections);
+                        editor.scroll_line = scroll_line;
+                    }
+                }
+            }
+        }
+        true
+    }
+
+    pub fn height(&self, view: &View) -> f32 {
+        match self.open {
+            true => view.line_height,
+            false => 0.0,
+        }
+    }
+
+    pub fn sections(&self, view: &View, editor: &Editor, top: f32) -> Vec<OwnedSection> {
+        let (status, color) = match &self.error {
+            Some(error) => (error.clone(), ERROR_COLOR),
+            None => (
+                format!("{HINT}, 1 to {}", editor.document.line_count()),
+                LABEL_COLOR,
+            ),
+        };
+        vec![
+            OwnedSection::<Extra>::default()
+                .with_text(vec![
+                    OwnedText::new(LABEL)
+                        .with_scale(view.scale)
+                        .with_color(LABEL_COLOR),
+                    OwnedText::new(self.input.text())
+                        .with_scale(view.scale)
+                        .with_color(TEXT_COLOR),
+                ])
+                .with_screen_position((0.0, top))
+                .with_bounds((view.width, view.line_height))
+                .with_layout(Layout::default_single_line()),
+            OwnedSection::<Extra>::default()
+                .with_text(vec![OwnedText::new(status)
+                    .with_scale(view.scale)
+                    .with_color(color)])
+                .with_screen_position((view.width - view.char_width, top))
+                .with_bounds((view.width, view.line_height))
+                .with_layout(Layout::default_single_line().h_align(HorizontalAlign::Right)),
+        ]
+    }
+
+    pub fn rects(&self, view: &View, top: f32) -> Vec<RectVertex> {
+        let [r, g, b, a] = BACKGROUND_COLOR;
+        let background = [0.0, top, view.width, top + view.line_height, r, g, b, a];
+        let x = (LABEL.len() + self.input.cursor_column()) as f32 * view.char_width;
+        let [r, g, b, a] = CARET_COLOR;
+        let caret = [x, top, x + 2.0, top + view.line_height, r, g, b, a];
+        vec![background, caret]
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn parses_lines_and_columns() {
+        assert_eq!(parse("12", 0, 100), Ok((11, 0)));
+        assert_eq!(parse(" 12:5 ", 0, 100), Ok((11, 4)));
+        assert_eq!(parse("0", 5, 100), Ok((0, 0)));
+        assert_eq!(parse("500", 0, 100), Ok((99, 0)));
+    }
+
+    #[test]
+    fn parses_lines_from_the_caret() {
+        assert_eq!(parse("+3", 10, 100), Ok((13, 0)));
+        assert_eq!(parse("-3", 10, 100), Ok((7, 0)));
+        assert_eq!(parse("-30", 10, 100), Ok((0, 0)));
+        assert_eq!(parse("+300", 10, 100), Ok((99, 0)));
+    }
+
+    #[test]
+    fn parses_percentages() {
+        assert_eq!(parse("50%", 0, 200), Ok((100, 0)));
+        assert_eq!(parse("100%", 0, 200), Ok((199, 0)));
+        assert_eq!(parse("150%:2", 0, 200), Ok((199, 1)));
+    }
+
+    #[test]
+    fn rejects_what_is_not_a_line() {
+        assert_eq!(parse("abc", 0, 10), Err("Not a line: abc".into()));
+        assert!(parse("3:x", 0, 10).is_err());
+        assert!(parse("%", 0, 10).is_err());
+        assert!(parse("", 0, 10).is_err());
+    }
+}
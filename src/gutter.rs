@@ -0,0 +1,95 @@
+use glyph_brush::{Extra, HorizontalAlign, Layout, OwnedSection, OwnedText};
+use serde::Deserialize;
+
+use crate::{view::View, wrap::Row};
+
+const NUMBER_COLOR: [f32; 4] = [0.4, 0.4, 0.45, 1.0];
+const CURRENT_NUMBER_COLOR: [f32; 4] = [0.9, 0.9, 0.9, 1.0];
+const FOLDED_MARKER: char = '▸';
+const FOLDABLE_MARKER: char = '▾';
+const BOOKMARK_MARKER: char = '◆';
+const BOOKMARK_COLOR: [f32; 4] = [0.4, 0.7, 1.0, 1.0];
+
+// left margin showing line numbers
+#[derive(Clone, Copy, Debug, Default, Deserialize)]
+#[serde(default)]
+pub struct Gutter {
+    // show distances to the caret line instead of absolute numbers
+    pub relative: bool,
+}
+
+impl Gutter {
+    // digits needed for the largest line number, never narrower than 2
+    pub fn digits(line_count: usize) -> usize {
+        (line_count.max(1).ilog10() as usize + 1).max(2)
+    }
+
+    // one column of padding on either side of the numbers
+    pub fn width(&self, view: &View, line_count: usize) -> f32 {
+        (Self::digits(line_count) + 2) as f32 * view.char_width
+    }
+
+    pub fn contains(&self, view: &View, line_count: usize, x: f32) -> bool {
+        x < self.width(view, line_count)
+    }
+
+    pub fn label(&self, line: usize, caret_line: usize) -> String {
+        match self.relative && line != caret_line {
+            true => line.abs_diff(caret_line).to_string(),
+            false => (line + 1).to_string(),
+        }
+    }
+
+    // right-aligned numbers for the visible rows, blank on soft-wrapped continuations, with
+    // a marker in the right padding on lines that are folded or can be and one in the left
+    // padding on bookmarked lines
+    pub fn section(
+        &self,
+        view: &View,
+        rows: &[Row],
+        line_count: usize,
+        caret_line: usize,
+        foldable: impl Fn(usize) -> bool,
+        bookmarked: impl Fn(usize) -> bool,
+    ) -> OwnedSection {
+        let width = self.width(view, line_count);
+        let digits = Self::digits(line_count);
+        let text = rows
+            .iter()
+            .flat_map(|row| {
+                let color = match row.line == caret_line {
+                    true => CURRENT_NUMBER_COLOR,
+                    false => NUMBER_COLOR,
+                };
+                let label = match row.continuation {
+                    true => String::new(),
+                    false => self.label(row.line, caret_line),
+                };
+                let marker = match (row.continuation, row.folded) {
+                    (true, _) => ' ',
+                    (false, true) => FOLDED_MARKER,
+                    (false, false) if foldable(row.line) => FOLDABLE_MARKER,
+                    (false, false) => ' ',
+                };
+                let bookmark = match !row.continuation && bookmarked(row.line) {
+                    true => BOOKMARK_MARKER,
+                    false => ' ',
+                };
+                [
+                    OwnedText::new(bookmark.to_string())
+                        .with_scale(view.scale)
+                        .with_color(BOOKMARK_COLOR),
+                    OwnedText::new(format!("{label:>digits$}{marker}\n"))
+                        .with_scale(view.scale)
+                        .with_color(color),
+                ]
+            })
+            .collect();
+
+        OwnedSection::<Extra>::default()
+            .with_text(text)
+            .with_screen_position((width, 0.0))
+            .with_bounds((width, view.height))
+            .with_layout(Layout::default().h_align(HorizontalAlign::Right))
+    }
+}
@@ -0,0 +1,197 @@
+use crate::{selection::Selections, text_document::TextDocument};
+
+// a single replacement, offsets are in the text as it was when the change was applied
+#[derive(Clone, Debug)]
+pub struct Change {
+    pub offset: usize,
+    pub deleted: String,
+    pub inserted: String,
+}
+
+impl Change {
+    fn apply(&self, document: &mut TextDocument) {
+        document.replace(
+            self.offset..self.offset + self.deleted.len(),
+            &self.inserted,
+        );
+    }
+
+    // the change that undoes this one
+    fn reversed(&self) -> Self {
+        Self {
+            offset: self.offset,
+            deleted: self.inserted.clone(),
+            inserted: self.deleted.clone(),
+        }
+    }
+
+    fn revert(&self, document: &mut TextDocument) {
+        document.replace(
+            self.offset..self.offset + self.inserted.len(),
+            &self.deleted,
+        );
+    }
+}
+
+// typing and deleting merge into the previous undo step when they continue it
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum EditKind {
+    Insert,
+    Delete,
+    Other,
+}
+
+struct Transaction {
+    kind: EditKind,
+    changes: Vec<Change>,
+    before: Selections,
+    after: Selections,
+}
+
+#[derive(Default)]
+pub struct History {
+    undo: Vec<Transaction>,
+    redo: Vec<Transaction>,
+}
+
+impl History {
+    pub fn record(
+        &mut self,
+        kind: EditKind,
+        changes: Vec<Change>,
+        before: Selections,
+        after: Selections,
+    ) {
+        self.redo.clear();
+
+        let breaks_line = changes.iter().any(|change| change.inserted.contains('\n'));
+        if let Some(last) = self.undo.last_mut() {
+            if kind != EditKind::Other && kind == last.kind && last.after == before && !breaks_line
+            {
+                last.changes.extend(changes);
+                last.after = after;
+                return;
+            }
+        }
+
+        self.undo.push(Transaction {
+            kind,
+            changes,
+            before,
+            after,
+        });
+    }
+
+    // revert the last step, returning the selections from before it; `applied` sees every
+    // change made to the document
+    pub fn undo(
+        &mut self,
+        document: &mut TextDocument,
+        mut applied: impl FnMut(&Change),
+    ) -> Option<Selections> {
+        let transaction = self.undo.pop()?;
+        transaction.changes.iter().rev().for_each(|change| {
+            change.revert(document);
+            applied(&change.reversed());
+        });
+        let selections = transaction.before.clone();
+        self.redo.push(transaction);
+        Some(selections)
+    }
+
+    // reapply the last undone step, returning the selections from after it
+    pub fn redo(
+        &mut self,
+        document: &mut TextDocument,
+        mut applied: impl FnMut(&Change),
+    ) -> Option<Selections> {
+        let transaction = self.redo.pop()?;
+        transaction.changes.iter().for_each(|change| {
+            change.apply(document);
+            applied(change);
+        });
+        let selections = transaction.after.clone();
+        self.undo.push(transaction);
+        Some(selections)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::selection::Selection;
+
+    fn caret(offset: usize) -> Selections {
+        Selections::single(Selection::caret(offset))
+    }
+
+    // insert the text at the offset in the document and record it
+    fn insert(
+        history: &mut History,
+        document: &mut TextDocument,
+        kind: EditKind,
+        offset: usize,
+        text: &str,
+    ) {
+        document.insert(offset, text);
+        let change = Change {
+            offset,
+            deleted: String::new(),
+            inserted: text.into(),
+        };
+        history.record(
+            kind,
+            vec![change],
+            caret(offset),
+            caret(offset + text.len()),
+        );
+    }
+
+    #[test]
+    fn typing_merges_into_one_step() {
+        let mut document = TextDocument::from_text("");
+        let mut history = History::default();
+        insert(&mut history, &mut document, EditKind::Insert, 0, "a");
+        insert(&mut history, &mut document, EditKind::Insert, 1, "b");
+        insert(&mut history, &mut document, EditKind::Insert, 2, "c");
+        assert_eq!(document.text(), "abc");
+
+        assert_eq!(history.undo(&mut document, |_| {}), Some(caret(0)));
+        assert_eq!(document.text(), "");
+        assert!(history.undo(&mut document, |_| {}).is_none());
+        assert_eq!(history.redo(&mut document, |_| {}), Some(caret(3)));
+        assert_eq!(document.text(), "abc");
+    }
+
+    #[test]
+    fn line_breaks_kinds_and_jumps_start_new_steps() {
+        let mut document = TextDocument::from_text("");
+        let mut history = History::default();
+        insert(&mut history, &mut document, EditKind::Insert, 0, "a");
+        insert(&mut history, &mut document, EditKind::Insert, 1, "\n");
+        insert(&mut history, &mut document, EditKind::Insert, 2, "b");
+        insert(&mut history, &mut document, EditKind::Other, 3, "c");
+        insert(&mut history, &mut document, EditKind::Other, 4, "d");
+        // the caret moved away before typing again
+        insert(&mut history, &mut document, EditKind::Insert, 0, "e");
+        assert_eq!(document.text(), "ea\nbcd");
+
+        let mut undone = Vec::new();
+        while history.undo(&mut document, |_| {}).is_some() {
+            undone.push(document.text().to_string());
+        }
+        // typing on after a line break continues its step
+        assert_eq!(undone, vec!["a\nbcd", "a\nbc", "a\nb", "a", ""]);
+    }
+
+    #[test]
+    fn a_new_edit_drops_the_redo_steps() {
+        let mut document = TextDocument::from_text("");
+        let mut history = History::default();
+        insert(&mut history, &mut document, EditKind::Other, 0, "a");
+        history.undo(&mut document, |_| {});
+        insert(&mut history, &mut document, EditKind::Other, 0, "b");
+        assert!(history.redo(&mut document, |_| {}).is_none());
+        assert_eq!(document.text(), "b");
+    }
+}
@@ -0,0 +1,139 @@
+use std::ops::Range;
+
+use crate::{editor::Indentation, text_document::TextDocument};
+
+// widest step between indentation levels taken for spaces
+const MAX_WIDTH: usize = 8;
+
+// leading spaces and tabs of the line
+pub fn leading(line: &str) -> &str {
+    &line[..line.len() - line.trim_start_matches([' ', '\t']).len()]
+}
+
+// guess tabs or spaces and the width of a level from how the lines are indented, the
+// configured indentation when nothing is
+pub fn detect(text: &str, configured: Indentation) -> Indentation {
+    let (mut tabs, mut spaces) = (0, 0);
+    // how often the indentation grows by each width from one line to the next
+    let mut steps = [0usize; MAX_WIDTH + 1];
+    let mut previous = 0;
+    for line in text.lines().filter(|line| !line.trim().is_empty()) {
+        let indent = leading(line);
+        if indent.starts_with('\t') {
+            tabs += 1;
+            continue;
+        }
+        let width = indent.bytes().take_while(|b| *b == b' ').count();
+        if width > 0 {
+            spaces += 1;
+        }
+        if width > previous && width - previous <= MAX_WIDTH {
+            steps[width - previous] += 1;
+        }
+        previous = width;
+    }
+
+    if tabs > spaces {
+        return Indentation {
+            use_tabs: true,
+            ..configured
+        };
+    }
+    // a single space is usually alignment, such as in block comments
+    let width = (2..=MAX_WIDTH)
+        .rev()
+        .max_by_key(|width| steps[*width])
+        .filter(|width| steps[*width] > 0);
+    match (spaces, width) {
+        (0, _) | (_, None) => configured,
+        (_, Some(width)) => Indentation {
+            use_tabs: false,
+            width,
+            ..configured
+        },
+    }
+}
+
+// edits adding or removing one level at the start of the lines; blank lines are not
+// indented
+pub fn shift_edits(
+    document: &TextDocument,
+    lines: impl IntoIterator<Item = usize>,
+    indentation: Indentation,
+    outdent: bool,
+) -> Vec<(Range<usize>, String)> {
+    lines
+        .into_iter()
+        .filter_map(|line| {
+            let start = document.line_start(line);
+            let text = document.line(line);
+            match outdent {
+                false if !text.trim().is_empty() => Some((start..start, indentation.unit())),
+                false => None,
+                true => {
+                    let width = match text.starts_with('\t') {
+                        true => 1,
+                        false => text
+                            .bytes()
+                            .take(indentation.width)
+                            .take_while(|b| *b == b' ')
+                            .count(),
+                    };
+                    (width > 0).then(|| (start..start + width, String::new()))
+                }
+            }
+        })
+        .collect()
+}
+
+// where an offset ends up after the sorted edits
+pub fn map_offset(offset: usize, edits: &[(Range<usize>, String)]) -> usize {
+    let mut delta = 0isize;
+    for (range, text) in edits {
+        if range.start > offset {
+            break;
+        }
+        match offset < range.end {
+            // inside a deleted range it moves to where the range was
+            true => return range.start.saturating_add_signed(delta) + text.len(),
+            false => delta += text.len() as isize - range.len() as isize,
+        }
+    }
+    offset.saturating_add_signed(delta)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn detected(text: &str) -> (bool, usize) {
+        let configured = Indentation {
+            use_tabs: false,
+            width: 8,
+            detect: true,
+        };
+        let found = detect(text, configured);
+        (found.use_tabs, found.width)
+    }
+
+    #[test]
+    fn detects_the_width_of_a_level() {
+        assert_eq!(detected("a\n  b\n    c\n  d\ne\n  f"), (false, 2));
+        assert_eq!(detected("a\n    b\n        c\n    d"), (false, 4));
+        // a deeper jump now and then does not outvote the common step
+        assert_eq!(detected("a\n  b\n      c\nd\n  e\n    f"), (false, 2));
+    }
+
+    #[test]
+    fn detects_tabs() {
+        assert_eq!(detected("a\n\tb\n\t\tc\n  d"), (true, 8));
+    }
+
+    #[test]
+    fn keeps_the_configured_indentation_without_evidence() {
+        assert_eq!(detected("a\nb\nc"), (false, 8));
+        // single spaces are alignment, as in block comments
+        assert_eq!(detected("/*\n * a\n * b\n */"), (false, 8));
+        assert_eq!(detected(""), (false, 8));
+    }
+}
@@ -6,23 +6,35 @@ use glutin::{
     surface::GlSurface,
 };
 use glutin_winit::{self, DisplayBuilder, GlWindow};
-use glyph_brush::{ab_glyph::*, Section, *};
+use glyph_brush::{ab_glyph::*, *};
 use raw_window_handle::HasRawWindowHandle;
 use std::{
     ffi::{c_void, CString},
+    num::NonZeroU32,
     time::Duration,
 };
 use winit::{
-    event::{ElementState, Event, KeyEvent, WindowEvent},
+    event::{ElementState, Event, KeyEvent, MouseButton, MouseScrollDelta, WindowEvent},
     event_loop::{ControlFlow, EventLoop},
-    keyboard::{Key, NamedKey},
     window::WindowBuilder,
 };
 
 use crate::{
-    gl_assert_ok,
-    gl_renderer::render_gl::{GLTextPipe, GlGlyphTexture},
+    app::App,
+    brackets::BRACKET_COLOR,
+    config::Config as EditorConfig,
+    editor::Editor,
+    find::MATCH_COLOR,
+    folding, gl_assert_ok,
+    gl_renderer::render_gl::{split_overlay, GLRectPipe, GLTextPipe, GlGlyphTexture},
+    keymap::{Keymap, Preset},
+    mouse::Mouse,
+    session::Session,
+    snippets::Snippets,
+    text_document::TextDocument,
+    text_view::TextView,
     utils::{Res, Vertex},
+    view::View,
 };
 
 pub fn init() -> Res<()> {
@@ -93,72 +105,198 @@ pub fn init() -> Res<()> {
 
     let mut texture = GlGlyphTexture::new(glyph_brush.texture_dimensions());
     let mut text_pipe = GLTextPipe::new(dimensions)?;
+    // text of the overlays, drawn after their backgrounds
+    let mut overlay_text_pipe = GLTextPipe::new(dimensions)?;
+    let mut rect_pipe = GLRectPipe::new(dimensions)?;
+
+    // fall back to the defaults so a typo in the config doesn't lock the user out
+    let (config, config_error) = match EditorConfig::load() {
+        Ok(config) => (config, None),
+        Err(e) => (EditorConfig::default(), Some(e.to_string())),
+    };
+
+    let document = match std::env::args().nth(1) {
+        Some(file_name) => TextDocument::new(&file_name)?,
+        None => TextDocument::from_text(""),
+    };
+    let (session, session_error) = match Session::load() {
+        Ok(session) => (session, None),
+        Err(e) => (Session::default(), Some(e.to_string())),
+    };
+    let mut app = App::new(
+        Editor::new(document, config.indentation),
+        config.indentation,
+        session,
+    );
+    app.clipboard = arboard::Clipboard::new().ok();
+    let keymap_error = match Keymap::load() {
+        Ok(keymap) => {
+            app.keymap = keymap;
+            None
+        }
+        Err(e) => Some(e.to_string()),
+    };
+    app.vim.set_enabled(app.keymap.preset() == Preset::Vim);
+    app.snippets = Snippets::load();
+    for problem in app.snippets.problems() {
+        eprintln!("snippets: {problem}");
+    }
+    let snippets_problem = app
+        .snippets
+        .problems()
+        .first()
+        .map(|problem| format!("snippets: {problem}"));
+    // conflicting bindings are listed in full on stderr, the status bar has room for one
+    for problem in app.keymap.problems() {
+        eprintln!("keymap: {problem}");
+    }
+    let keymap_problem = match app.keymap.problems() {
+        [] => None,
+        [problem] => Some(format!("keymap: {problem}")),
+        [problem, rest @ ..] => Some(format!("keymap: {problem} (and {} more)", rest.len())),
+    };
+    if let Some(e) = config_error
+        .or(session_error)
+        .or(keymap_error)
+        .or(keymap_problem)
+        .or(snippets_problem)
+    {
+        app.editor.set_message(e);
+    }
+    let gutter = config.gutter;
+    let status_bar = config.status_bar;
+    let soft_wrap = config.soft_wrap;
+    let whitespace = config.whitespace;
+
+    let mut view = View::new(
+        &glyph_brush.fonts()[0],
+        (config.font_size * window.scale_factor() as f32).round(),
+        (dimensions.width as f32, dimensions.height as f32),
+    );
 
-    let mut text: String = include_str!("../text/lipsum.txt").into();
-    let font_size: f32 = 18.0;
+    // layout of the last frame, used for hit-testing mouse clicks
+    let mut text_view = TextView::new(&app.editor, &view, whitespace, 0.0, view.height);
+    let mut mouse = Mouse::default();
 
     let mut interval = spin_sleep_util::interval(Duration::from_secs(1) / 250);
     let mut reporter = spin_sleep_util::RateReporter::new(Duration::from_secs(1));
 
     events.run(move |event, elwt| match event {
-        Event::AboutToWait => window.request_redraw(),
+        Event::AboutToWait => {
+            app.poll();
+            window.request_redraw();
+        }
         Event::WindowEvent { event, .. } => match event {
-            WindowEvent::CloseRequested => elwt.exit(),
+            WindowEvent::CloseRequested => {
+                app.request_quit(false);
+                if app.quit {
+                    elwt.exit();
+                }
+            }
+            WindowEvent::Resized(size) => {
+                if let (Some(width), Some(height)) =
+                    (NonZeroU32::new(size.width), NonZeroU32::new(size.height))
+                {
+                    gl_surface.resize(&gl_ctx, width, height);
+                    unsafe { gl::Viewport(0, 0, size.width as i32, size.height as i32) };
+                    text_pipe.update_geometry(size);
+                    overlay_text_pipe.update_geometry(size);
+                    rect_pipe.update_geometry(size);
+                    view.resize(size.width as f32, size.height as f32);
+                }
+            }
+            WindowEvent::ModifiersChanged(new) => app.set_modifiers(new.state()),
             WindowEvent::KeyboardInput {
                 event:
                     KeyEvent {
                         logical_key,
+                        physical_key,
                         state: ElementState::Pressed,
                         ..
                     },
                 ..
-            } => match logical_key {
-                Key::Named(NamedKey::Escape) => elwt.exit(),
-                Key::Named(NamedKey::Backspace) => {
-                    text.pop();
+            } => {
+                app.handle_key(&logical_key, physical_key);
+                if app.quit {
+                    elwt.exit();
                 }
-                key => {
-                    if let Some(str) = key.to_text() {
-                        text.push_str(str);
-                    }
+            }
+            WindowEvent::MouseWheel { delta, .. } => {
+                let lines = match delta {
+                    MouseScrollDelta::LineDelta(_, y) => -y * 3.0,
+                    MouseScrollDelta::PixelDelta(position) => -position.y as f32 / view.line_height,
+                };
+                app.editor.scroll_by(lines.round() as isize);
+            }
+            WindowEvent::CursorMoved { position, .. } => mouse.position = position,
+            WindowEvent::MouseInput {
+                state,
+                button: MouseButton::Left,
+                ..
+            } => match state {
+                ElementState::Pressed => {
+                    let line_count = app.editor.document.line_count();
+                    let in_gutter = gutter.contains(&view, line_count, mouse.position.x as f32);
+                    let modifiers = app.modifiers();
+                    mouse.press(
+                        &mut app.editor,
+                        &text_view,
+                        in_gutter,
+                        modifiers.shift_key(),
+                        modifiers.alt_key(),
+                    );
                 }
+                ElementState::Released => mouse.release(),
             },
             WindowEvent::RedrawRequested => {
-                let width = dimensions.width as f32;
-                let height = dimensions.height as f32;
-                let scale = (font_size * window.scale_factor() as f32).round();
-                let base_text = Text::new(&text).with_scale(scale);
+                let line_count = app.editor.document.line_count();
+                let text_left = gutter.width(&view, line_count);
+                let goto_top = status_bar.top(&view) - app.goto_bar.height(&view);
+                let find_top = goto_top - app.find_bar.height(&view);
+                let text_bottom = find_top;
+                let wrap_columns =
+                    soft_wrap.then(|| ((view.width - text_left) / view.char_width) as usize);
+                app.editor
+                    .set_viewport(view.lines_in(text_bottom), wrap_columns);
+
+                //lay out the visible rows before the drag selection hit-tests against them
+                text_view = TextView::new(&app.editor, &view, whitespace, text_left, text_bottom);
+                let text_section = text_view.section(&app.editor, &view);
+                text_view.layout(&mut glyph_brush, &text_section);
+                mouse.update_drag(&mut app.editor, &text_view);
 
                 //queue sections of text
-                glyph_brush.queue(
-                    Section::default()
-                        .add_text(base_text.with_color([0.9, 0.3, 0.3, 0.1]))
-                        .with_bounds((width / 3.15, height)),
-                );
-
-                glyph_brush.queue(
-                    Section::default()
-                        .add_text(base_text.with_color([0.9, 0.3, 0.3, 1.0]))
-                        .with_screen_position((width / 2.0, height / 2.0))
-                        .with_bounds((width / 3.15, height))
-                        .with_layout(
-                            Layout::default()
-                                .h_align(HorizontalAlign::Center)
-                                .v_align(VerticalAlign::Center),
-                        ),
-                );
-
-                glyph_brush.queue(
-                    Section::default()
-                        .add_text(base_text.with_color([0.9, 0.9, 0.9, 1.0]))
-                        .with_screen_position((width, height))
-                        .with_bounds((width / 3.15, height))
-                        .with_layout(
-                            Layout::default()
-                                .h_align(HorizontalAlign::Right)
-                                .v_align(VerticalAlign::Bottom),
-                        ),
-                );
+                let tab_width = app.editor.indentation.width;
+                let bookmarks = app.editor.marks.bookmarked_lines(&app.editor.document);
+                glyph_brush.queue(&gutter.section(
+                    &view,
+                    &text_view.rows,
+                    line_count,
+                    app.editor.caret_line(),
+                    |line| folding::is_header(&app.editor.document, line, tab_width),
+                    |line| bookmarks.binary_search(&line).is_ok(),
+                ));
+                glyph_brush.queue(&text_section);
+                if app.find_bar.is_open() {
+                    app.find_bar.refresh(&app.editor);
+                    for section in &app.find_bar.sections(&view, &app.editor, find_top) {
+                        glyph_brush.queue(section);
+                    }
+                }
+                if app.goto_bar.is_open() {
+                    for section in &app.goto_bar.sections(&view, &app.editor, goto_top) {
+                        glyph_brush.queue(section);
+                    }
+                }
+                for section in
+                    &status_bar.sections(&view, &app.editor, app.mode_status().as_deref())
+                {
+                    glyph_brush.queue(section);
+                }
+                let overlay = app.overlay(&view);
+                for section in overlay.iter().flat_map(|(sections, _)| sections) {
+                    glyph_brush.queue(section);
+                }
 
                 //process the queue
                 let mut brush_action;
@@ -208,7 +346,11 @@ pub fn init() -> Res<()> {
 
                 // upload new vertices to GPU if text has changed
                 match brush_action.unwrap() {
-                    BrushAction::Draw(vertices) => text_pipe.upload_vertices(&vertices),
+                    BrushAction::Draw(vertices) => {
+                        let (below, above) = split_overlay(&vertices);
+                        text_pipe.upload_vertices(&below);
+                        overlay_text_pipe.upload_vertices(&above);
+                    }
                     BrushAction::ReDraw => {}
                 }
 
@@ -217,8 +359,35 @@ pub fn init() -> Res<()> {
                     gl::Clear(gl::COLOR_BUFFER_BIT);
                 }
 
+                let mut rects =
+                    text_view.highlight_rects(app.find_bar.matches(), &view, MATCH_COLOR);
+                if let Some((bracket, found)) = app.editor.matching_bracket() {
+                    let (first, second) = (bracket.min(found), bracket.max(found));
+                    rects.extend(text_view.highlight_rects(
+                        &[first..first + 1, second..second + 1],
+                        &view,
+                        BRACKET_COLOR,
+                    ));
+                }
+                rects.extend(text_view.rects(&app.editor, &view));
+                if app.find_bar.is_open() {
+                    rects.extend(app.find_bar.rects(&view, find_top));
+                }
+                if app.goto_bar.is_open() {
+                    rects.extend(app.goto_bar.rects(&view, goto_top));
+                }
+                rects.push(status_bar.background(&view));
+                rect_pipe.upload_rects(&rects);
+                rect_pipe.draw();
                 text_pipe.draw();
 
+                let overlay_rects = overlay.map_or_else(Vec::new, |(_, rects)| rects);
+                if !overlay_rects.is_empty() {
+                    rect_pipe.upload_rects(&overlay_rects);
+                    rect_pipe.draw();
+                    overlay_text_pipe.draw();
+                }
+
                 //swap front and back buffers to render text on screen
                 gl_surface.swap_buffers(&gl_ctx).unwrap();
 
@@ -0,0 +1,72 @@
+use winit::keyboard::{Key, NamedKey};
+
+// single line text field used by the overlays
+#[derive(Clone, Debug, Default)]
+pub struct LineInput {
+    text: String,
+    // byte offset of the caret
+    cursor: usize,
+}
+
+impl LineInput {
+    pub fn text(&self) -> &str {
+        &self.text
+    }
+
+    pub fn set_text(&mut self, text: &str) {
+        self.text = text.into();
+        self.cursor = self.text.len();
+    }
+
+    // caret position in chars, for drawing it with a monospace font
+    pub fn cursor_column(&self) -> usize {
+        self.text[..self.cursor].chars().count()
+    }
+
+    pub fn insert(&mut self, text: &str) {
+        // the field is single line, pasted line breaks become spaces
+        let text = text.replace(['\r', '\n'], " ");
+        self.text.insert_str(self.cursor, &text);
+        self.cursor += text.len();
+    }
+
+    // apply an editing key, false when the key does not belong to the field
+    pub fn handle_key(&mut self, key: &Key) -> bool {
+        match key {
+            Key::Named(NamedKey::Backspace) => {
+                if let Some((i, _)) = self.text[..self.cursor].char_indices().next_back() {
+                    self.text.replace_range(i..self.cursor, "");
+                    self.cursor = i;
+                }
+            }
+            Key::Named(NamedKey::Delete) => {
+                if let Some(c) = self.text[self.cursor..].chars().next() {
+                    self.text
+                        .replace_range(self.cursor..self.cursor + c.len_utf8(), "");
+                }
+            }
+            Key::Named(NamedKey::ArrowLeft) => {
+                self.cursor = self.text[..self.cursor]
+                    .char_indices()
+                    .next_back()
+                    .map_or(0, |(i, _)| i);
+            }
+            Key::Named(NamedKey::ArrowRight) => {
+                self.cursor += self.text[self.cursor..]
+                    .chars()
+                    .next()
+                    .map_or(0, char::len_utf8);
+            }
+            Key::Named(NamedKey::Home) => self.cursor = 0,
+            Key::Named(NamedKey::End) => self.cursor = self.text.len(),
+            Key::Named(NamedKey::Space) | Key::Character(_) => {
+                let Some(text) = key.to_text() else {
+                    return false;
+                };
+                self.insert(text);
+            }
+            _ => return false,
+        }
+        true
+    }
+}
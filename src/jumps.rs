@@ -0,0 +1,109 @@
+use std::path::PathBuf;
+
+use crate::editor::Editor;
+
+// moves across at least this many lines are jumps
+const JUMP_LINES: usize = 10;
+// oldest jumps are forgotten past this many
+const CAPACITY: usize = 100;
+
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub enum BufferId {
+    File(PathBuf),
+    // generated buffers such as search results
+    Titled(String),
+}
+
+// a place in a buffer; the line and column, in chars, stay meaningful after edits where an
+// offset may not
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct Location {
+    // None for a scratch buffer
+    pub buffer: Option<BufferId>,
+    pub line: usize,
+    pub column: usize,
+}
+
+impl Location {
+    pub fn of(editor: &Editor) -> Self {
+        Self::at(editor, editor.caret())
+    }
+
+    pub fn at(editor: &Editor, offset: usize) -> Self {
+        let document = &editor.document;
+        let buffer = match (document.file_name(), document.title()) {
+            (Some(path), _) => Some(BufferId::File(path.to_path_buf())),
+            (None, Some(title)) => Some(BufferId::Titled(title.to_string())),
+            (None, None) => None,
+        };
+        Self {
+            buffer,
+            line: document.line_of_offset(offset),
+            column: editor.column_of(offset),
+        }
+    }
+
+    fn is_near(&self, other: &Self) -> bool {
+        self.buffer == other.buffer && self.line.abs_diff(other.line) < JUMP_LINES
+    }
+}
+
+// where the caret jumped from, for going back and forward through them as in a browser
+#[derive(Debug, Default)]
+pub struct JumpList {
+    entries: Vec<Location>,
+    // the entry being visited, the length of the list when none is
+    index: usize,
+    // set by going back or forward so the move is not taken for a new jump
+    navigated: bool,
+}
+
+impl JumpList {
+    // note the move from one place to the other if it is a jump
+    pub fn moved(&mut self, from: Location, to: &Location) {
+        if std::mem::take(&mut self.navigated) || from.is_near(to) {
+            return;
+        }
+        self.push(from);
+    }
+
+    // a new jump drops the ones gone back over
+    pub fn push(&mut self, location: Location) {
+        self.entries.truncate(self.index);
+        if self
+            .entries
+            .last()
+            .is_some_and(|last| last.buffer == location.buffer && last.line == location.line)
+        {
+            self.entries.pop();
+        }
+        self.entries.push(location);
+        if self.entries.len() > CAPACITY {
+            self.entries.remove(0);
+        }
+        self.index = self.entries.len();
+    }
+
+    // the place jumped from before, leaving the current one to come forward to
+    pub fn back(&mut self, current: Location) -> Option<Location> {
+        if self.index == self.entries.len() {
+            self.push(current);
+            self.index -= 1;
+        }
+        if self.index == 0 {
+            return None;
+        }
+        self.index -= 1;
+        self.navigated = true;
+        Some(self.entries[self.index].clone())
+    }
+
+    pub fn forward(&mut self) -> Option<Location> {
+        if self.index + 1 >= self.entries.len() {
+            return None;
+        }
+        self.index += 1;
+        self.navigated = true;
+        Some(self.entries[self.index].clone())
+    }
+}
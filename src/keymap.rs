@@ -0,0 +1,611 @@
+use std::{
+    collections::BTreeMap,
+    fmt, fs, io,
+    path::PathBuf,
+    str::FromStr,
+    time::{Duration, Instant},
+};
+
+use serde::Deserialize;
+use winit::keyboard::{Key, ModifiersState, NamedKey, PhysicalKey};
+
+use crate::{commands, utils::Res};
+
+// how long the next key of a chord is waited for by default
+const CHORD_TIMEOUT: Duration = Duration::from_millis(1500);
+
+// named keys as they are written in key bindings
+const NAMED_KEYS: &[(&str, NamedKey)] = &[
+    ("Escape", NamedKey::Escape),
+    ("Enter", NamedKey::Enter),
+    ("Tab", NamedKey::Tab),
+    ("Space", NamedKey::Space),
+    ("Backspace", NamedKey::Backspace),
+    ("Delete", NamedKey::Delete),
+    ("Insert", NamedKey::Insert),
+    ("Left", NamedKey::ArrowLeft),
+    ("Right", NamedKey::ArrowRight),
+    ("Up", NamedKey::ArrowUp),
+    ("Down", NamedKey::ArrowDown),
+    ("Home", NamedKey::Home),
+    ("End", NamedKey::End),
+    ("PageUp", NamedKey::PageUp),
+    ("PageDown", NamedKey::PageDown),
+    ("F1", NamedKey::F1),
+    ("F2", NamedKey::F2),
+    ("F3", NamedKey::F3),
+    ("F4", NamedKey::F4),
+    ("F5", NamedKey::F5),
+    ("F6", NamedKey::F6),
+    ("F7", NamedKey::F7),
+    ("F8", NamedKey::F8),
+    ("F9", NamedKey::F9),
+    ("F10", NamedKey::F10),
+    ("F11", NamedKey::F11),
+    ("F12", NamedKey::F12),
+];
+
+// a pressed key as the keymap sees it
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct KeyPress {
+    pub modifiers: ModifiersState,
+    // lowercase char or key name from NAMED_KEYS
+    pub logical: Option<String>,
+    // winit key code name, the same whatever the keyboard layout
+    pub physical: Option<String>,
+    // a modifier key on its own, as pressed while holding it down for the next key
+    pub modifier: bool,
+}
+
+// Shift, Ctrl and the like, which are pressed with keys rather than bound themselves
+pub fn is_modifier(key: &Key) -> bool {
+    matches!(
+        key,
+        Key::Named(
+            NamedKey::Shift
+                | NamedKey::Control
+                | NamedKey::Alt
+                | NamedKey::AltGraph
+                | NamedKey::Super
+                | NamedKey::Meta
+                | NamedKey::Hyper
+        )
+    )
+}
+
+impl KeyPress {
+    pub fn new(logical_key: &Key, physical: PhysicalKey, modifiers: ModifiersState) -> Self {
+        let logical = match logical_key {
+            Key::Named(named) => NAMED_KEYS
+                .iter()
+                .find(|(_, candidate)| candidate == named)
+                .map(|(name, _)| name.to_string()),
+            Key::Character(c) => Some(c.to_lowercase()),
+            _ => None,
+        };
+        let physical = match physical {
+            PhysicalKey::Code(code) => Some(format!("{code:?}")),
+            PhysicalKey::Unidentified(_) => None,
+        };
+        Self {
+            modifiers,
+            logical,
+            physical,
+            modifier: is_modifier(logical_key),
+        }
+    }
+}
+
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub enum BoundKey {
+    // the key as the layout produces it, written as the char or key name
+    Logical(String),
+    // the key at a position on the keyboard, written as its code in brackets like [KeyS]
+    Physical(String),
+}
+
+// a key with the modifiers held, written like Ctrl+Shift+P
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct KeyCombo {
+    pub modifiers: ModifiersState,
+    pub key: BoundKey,
+}
+
+impl KeyCombo {
+    pub fn matches(&self, press: &KeyPress) -> bool {
+        self.modifiers == press.modifiers
+            && match &self.key {
+                BoundKey::Logical(key) => press.logical.as_ref() == Some(key),
+                BoundKey::Physical(code) => press
+                    .physical
+                    .as_ref()
+                    .is_some_and(|physical| physical.eq_ignore_ascii_case(code)),
+            }
+    }
+}
+
+impl FromStr for KeyCombo {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let mut modifiers = ModifiersState::empty();
+        let mut parts: Vec<&str> = s.split('+').collect();
+        // a lone + is the key itself, as in Ctrl++
+        if s.ends_with("++") || s == "+" {
+            parts.truncate(parts.len() - 2);
+            parts.push("+");
+        }
+        let (key, held) = parts.split_last().ok_or("empty key binding")?;
+        for modifier in held {
+            modifiers |= match modifier.to_lowercase().as_str() {
+                "ctrl" | "control" => ModifiersState::CONTROL,
+                "shift" => ModifiersState::SHIFT,
+                "alt" | "option" => ModifiersState::ALT,
+                "super" | "cmd" | "meta" => ModifiersState::SUPER,
+                _ => return Err(format!("unknown modifier {modifier:?} in {s:?}")),
+            };
+        }
+
+        let key = if let Some(code) = key.strip_prefix('[').and_then(|k| k.strip_suffix(']')) {
+            match code.is_empty() {
+                true => return Err(format!("empty key code in {s:?}")),
+                false => BoundKey::Physical(code.into()),
+            }
+        } else if let Some((name, _)) = NAMED_KEYS
+            .iter()
+            .find(|(name, _)| name.eq_ignore_ascii_case(key))
+        {
+            BoundKey::Logical(name.to_string())
+        } else if key.chars().count() == 1 {
+            BoundKey::Logical(key.to_lowercase())
+        } else {
+            return Err(format!("unknown key {key:?} in {s:?}"));
+        };
+        Ok(Self { modifiers, key })
+    }
+}
+
+impl fmt::Display for KeyCombo {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        for (modifier, name) in [
+            (ModifiersState::CONTROL, "Ctrl"),
+            (ModifiersState::ALT, "Alt"),
+            (ModifiersState::SHIFT, "Shift"),
+            (ModifiersState::SUPER, "Super"),
+        ] {
+            if self.modifiers.contains(modifier) {
+                write!(f, "{name}+")?;
+            }
+        }
+        match &self.key {
+            BoundKey::Physical(code) => write!(f, "[{code}]"),
+            BoundKey::Logical(key) if key.chars().count() == 1 => {
+                write!(f, "{}", key.to_uppercase())
+            }
+            BoundKey::Logical(key) => write!(f, "{key}"),
+        }
+    }
+}
+
+// keys pressed one after the other, written separated by spaces like Ctrl+K Ctrl+C
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct KeySequence(pub Vec<KeyCombo>);
+
+impl KeySequence {
+    fn starts_with(&self, other: &KeySequence) -> bool {
+        self.0.starts_with(&other.0)
+    }
+}
+
+impl FromStr for KeySequence {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let combos = s
+            .split_whitespace()
+            .map(str::parse)
+            .collect::<Result<Vec<_>, _>>()?;
+        match combos.is_empty() {
+            true => Err("empty key binding".into()),
+            false => Ok(Self(combos)),
+        }
+    }
+}
+
+impl fmt::Display for KeySequence {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        for (i, combo) in self.0.iter().enumerate() {
+            if i > 0 {
+                write!(f, " ")?;
+            }
+            write!(f, "{combo}")?;
+        }
+        Ok(())
+    }
+}
+
+// style of editing the bindings are meant for
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum Preset {
+    #[default]
+    Default,
+    // modal editing on top of the default bindings
+    Vim,
+    // Emacs keys in place of the default bindings they clash with
+    Emacs,
+}
+
+const EMACS_BINDINGS: &[(&str, &str)] = &[
+    ("Ctrl+F", "cursor.right"),
+    ("Ctrl+B", "cursor.left"),
+    ("Ctrl+N", "cursor.down"),
+    ("Ctrl+P", "cursor.up"),
+    ("Ctrl+A", "cursor.line_start"),
+    ("Ctrl+E", "cursor.line_end"),
+    ("Ctrl+D", "edit.delete"),
+    ("Ctrl+/", "edit.undo"),
+    ("Ctrl+Space", "mark.set"),
+    ("Ctrl+G", "app.keyboard_quit"),
+    ("Ctrl+K", "kill.line"),
+    ("Ctrl+W", "kill.region"),
+    ("Alt+W", "kill.copy_region"),
+    ("Alt+D", "kill.word"),
+    ("Alt+Backspace", "kill.word_backward"),
+    ("Ctrl+Y", "kill.yank"),
+    ("Alt+Y", "kill.yank_pop"),
+    ("Ctrl+S", "find.forward"),
+    ("Ctrl+R", "find.backward"),
+    ("Alt+X", "palette.open"),
+    ("Ctrl+X Ctrl+S", "file.save"),
+    ("Ctrl+X Ctrl+C", "app.quit"),
+    ("Ctrl+X Ctrl+F", "file_finder.open"),
+    ("Ctrl+X Ctrl+X", "mark.exchange"),
+    ("Ctrl+X K", "buffer.close"),
+    ("Ctrl+X U", "edit.undo"),
+    ("Ctrl+X H", "selection.all"),
+];
+
+// <config dir>/text_editor/keymap.toml, bindings map key sequences to command ids and
+// replace the default bindings of the same keys, an empty id unbinds the keys
+#[derive(Debug, Default, Deserialize)]
+#[serde(default)]
+struct KeymapFile {
+    preset: Preset,
+    chord_timeout_ms: Option<u64>,
+    bindings: BTreeMap<String, String>,
+}
+
+pub enum Lookup {
+    Command(&'static str),
+    // the keys so far start a chord
+    Pending(String),
+    // the keys completed no chord, they are dropped
+    Unbound(String),
+    // a single key not bound to anything, to be typed
+    Unmatched,
+}
+
+// key bindings of the commands
+pub struct Keymap {
+    preset: Preset,
+    bindings: Vec<(KeySequence, &'static str)>,
+    chord_timeout: Duration,
+    // keys of the chord typed so far and when the last one was
+    pending: Vec<KeyPress>,
+    pending_since: Instant,
+    // invalid entries of the keymap file and conflicting bindings
+    problems: Vec<String>,
+}
+
+impl Default for Keymap {
+    fn default() -> Self {
+        let bindings = commands::COMMANDS
+            .iter()
+            .flat_map(|command| {
+                command.keys.iter().map(|keys| {
+                    let sequence = keys
+                        .parse()
+                        .unwrap_or_else(|e| panic!("default binding of {}: {e}", command.id));
+                    (sequence, command.id)
+                })
+            })
+            .collect();
+        let mut keymap = Self {
+            preset: Preset::Default,
+            bindings,
+            chord_timeout: CHORD_TIMEOUT,
+            pending: Vec::new(),
+            pending_since: Instant::now(),
+            problems: Vec::new(),
+        };
+        keymap.problems = keymap.conflicts();
+        keymap
+    }
+}
+
+impl Keymap {
+    pub fn path() -> Option<PathBuf> {
+        dirs::config_dir().map(|dir| dir.join("text_editor").join("keymap.toml"))
+    }
+
+    // the defaults with the keymap file applied; a missing file is not an error, a
+    // malformed one is, invalid entries are only reported in the problems
+    pub fn load() -> Res<Self> {
+        let mut keymap = Self::default();
+        let Some(path) = Self::path() else {
+            return Ok(keymap);
+        };
+        let text = match fs::read_to_string(&path) {
+            Ok(text) => text,
+            Err(e) if e.kind() == io::ErrorKind::NotFound => return Ok(keymap),
+            Err(e) => return Err(e.into()),
+        };
+        let file: KeymapFile =
+            toml::from_str(&text).map_err(|e| format!("{}: {e}", path.display()))?;
+        keymap.apply(file);
+        Ok(keymap)
+    }
+
+    pub fn with_preset(preset: Preset) -> Self {
+        let mut keymap = Self::default();
+        keymap.add_preset(preset);
+        keymap.problems = keymap.conflicts();
+        keymap
+    }
+
+    // bindings of the preset replace the defaults they would clash with
+    fn add_preset(&mut self, preset: Preset) {
+        self.preset = preset;
+        let bindings = match preset {
+            Preset::Emacs => EMACS_BINDINGS,
+            Preset::Default | Preset::Vim => &[],
+        };
+        for (keys, id) in bindings {
+            let sequence: KeySequence = keys
+                .parse()
+                .unwrap_or_else(|e| panic!("preset binding of {id}: {e}"));
+            let command = commands::find(id).unwrap_or_else(|| panic!("preset binding of {id}"));
+            self.bindings
+                .retain(|(bound, _)| !bound.starts_with(&sequence) && !sequence.starts_with(bound));
+            self.bindings.push((sequence, command.id));
+        }
+    }
+
+    fn apply(&mut self, file: KeymapFile) {
+        self.add_preset(file.preset);
+        if let Some(timeout) = file.chord_timeout_ms {
+            self.chord_timeout = Duration::from_millis(timeout);
+        }
+
+        let mut problems = Vec::new();
+        let mut added = Vec::new();
+        for (keys, id) in file.bindings {
+            let sequence: KeySequence = match keys.parse() {
+                Ok(sequence) => sequence,
+                Err(e) => {
+                    problems.push(e);
+                    continue;
+                }
+            };
+            self.bindings.retain(|(bound, _)| *bound != sequence);
+            if id.is_empty() {
+                continue;
+            }
+            match commands::find(&id) {
+                Some(command) => added.push((sequence, command.id)),
+                None => problems.push(format!("{keys}: unknown command {id:?}")),
+            }
+        }
+        self.bindings.extend(added);
+        problems.extend(self.conflicts());
+        self.problems = problems;
+    }
+
+    // pairs of bindings where one can never be reached
+    fn conflicts(&self) -> Vec<String> {
+        let mut conflicts = Vec::new();
+        for (i, (a, a_id)) in self.bindings.iter().enumerate() {
+            for (b, b_id) in &self.bindings[i + 1..] {
+                if a == b && a_id != b_id {
+                    conflicts.push(format!("{a} is bound to both {a_id} and {b_id}"));
+                } else if a != b && a.starts_with(b) {
+                    conflicts.push(format!("{b} ({b_id}) hides {a} ({a_id})"));
+                } else if a != b && b.starts_with(a) {
+                    conflicts.push(format!("{a} ({a_id}) hides {b} ({b_id})"));
+                }
+            }
+        }
+        conflicts
+    }
+
+    pub fn preset(&self) -> Preset {
+        self.preset
+    }
+
+    pub fn problems(&self) -> &[String] {
+        &self.problems
+    }
+
+    // feed a key press, completing a chord or starting one
+    pub fn press(&mut self, press: KeyPress) -> Lookup {
+        if self.pending_since.elapsed() > self.chord_timeout {
+            self.pending.clear();
+        }
+        // pressing Ctrl again for the second key of Ctrl+K Ctrl+U goes on with the chord
+        if press.modifier {
+            return match self.pending.is_empty() {
+                true => Lookup::Unmatched,
+                false => {
+                    let typed: Vec<_> = self.pending.iter().map(describe).collect();
+                    Lookup::Pending(typed.join(" "))
+                }
+            };
+        }
+        self.pending.push(press);
+        self.pending_since = Instant::now();
+
+        let pending = &self.pending;
+        let reached = |sequence: &KeySequence| {
+            sequence.0.len() >= pending.len()
+                && pending
+                    .iter()
+                    .zip(&sequence.0)
+                    .all(|(press, combo)| combo.matches(press))
+        };
+        let exact = self
+            .bindings
+            .iter()
+            .find(|(sequence, _)| sequence.0.len() == pending.len() && reached(sequence));
+        if let Some((_, id)) = exact {
+            self.pending.clear();
+            return Lookup::Command(id);
+        }
+        if let Some((sequence, _)) = self.bindings.iter().find(|(sequence, _)| reached(sequence)) {
+            let typed = KeySequence(sequence.0[..pending.len()].to_vec());
+            return Lookup::Pending(typed.to_string());
+        }
+
+        let count = self.pending.len();
+        let typed = self.pending.drain(..).map(|press| describe(&press));
+        match count {
+            1 => Lookup::Unmatched,
+            _ => Lookup::Unbound(typed.collect::<Vec<_>>().join(" ")),
+        }
+    }
+
+    // first binding of the command, for showing next to it
+    pub fn binding(&self, id: &str) -> Option<&KeySequence> {
+        self.bindings
+            .iter()
+            .find(|(_, bound)| *bound == id)
+            .map(|(sequence, _)| sequence)
+    }
+}
+
+fn describe(press: &KeyPress) -> String {
+    let key = match (&press.logical, &press.physical) {
+        (Some(logical), _) => BoundKey::Logical(logical.clone()),
+        (None, Some(physical)) => BoundKey::Physical(physical.clone()),
+        (None, None) => BoundKey::Logical("?".into()),
+    };
+    KeyCombo {
+        modifiers: press.modifiers,
+        key,
+    }
+    .to_string()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use winit::keyboard::KeyCode;
+
+    fn press(modifiers: ModifiersState, key: &str) -> KeyPress {
+        KeyPress {
+            modifiers,
+            logical: Some(key.into()),
+            physical: None,
+            modifier: false,
+        }
+    }
+
+    #[test]
+    fn parses_and_writes_key_sequences() {
+        let sequence: KeySequence = "ctrl+k  Control+Shift+c".parse().unwrap();
+        assert_eq!(sequence.0.len(), 2);
+        assert_eq!(
+            sequence.0[1].modifiers,
+            ModifiersState::CONTROL | ModifiersState::SHIFT
+        );
+        assert_eq!(sequence.to_string(), "Ctrl+K Ctrl+Shift+C");
+
+        let combo: KeyCombo = "Ctrl++".parse().unwrap();
+        assert_eq!(combo.key, BoundKey::Logical("+".into()));
+        let combo: KeyCombo = "Alt+[KeyS]".parse().unwrap();
+        assert_eq!(combo.key, BoundKey::Physical("KeyS".into()));
+        assert_eq!(combo.to_string(), "Alt+[KeyS]");
+        let combo: KeyCombo = "shift+pageup".parse().unwrap();
+        assert_eq!(combo.to_string(), "Shift+PageUp");
+    }
+
+    #[test]
+    fn rejects_malformed_bindings() {
+        assert!("".parse::<KeySequence>().is_err());
+        assert!("Hyper+A".parse::<KeyCombo>().is_err());
+        assert!("Ctrl+Nope".parse::<KeyCombo>().is_err());
+        assert!("Ctrl+[]".parse::<KeyCombo>().is_err());
+    }
+
+    #[test]
+    fn presets_have_no_conflicts() {
+        for preset in [Preset::Default, Preset::Vim, Preset::Emacs] {
+            assert_eq!(Keymap::with_preset(preset).problems(), &[] as &[String]);
+        }
+    }
+
+    #[test]
+    fn reports_invalid_entries_and_hidden_bindings() {
+        let mut keymap = Keymap::default();
+        let mut bindings = BTreeMap::new();
+        bindings.insert("Ctrl+K".to_string(), "file.save".to_string());
+        bindings.insert("F13".to_string(), "file.save".to_string());
+        bindings.insert("Ctrl+S".to_string(), "no.such_command".to_string());
+        keymap.apply(KeymapFile {
+            bindings,
+            ..KeymapFile::default()
+        });
+        let problems = keymap.problems();
+        assert!(problems.iter().any(|problem| problem.contains("F13")));
+        assert!(problems
+            .iter()
+            .any(|problem| problem.contains("no.such_command")));
+        assert!(problems
+            .iter()
+            .any(|problem| problem.starts_with("Ctrl+K (file.save) hides Ctrl+K ")));
+    }
+
+    #[test]
+    fn chords_wait_for_their_second_key() {
+        let mut keymap = Keymap::default();
+        let ctrl = ModifiersState::CONTROL;
+        assert!(
+            matches!(keymap.press(press(ctrl, "k")), Lookup::Pending(keys) if keys == "Ctrl+K")
+        );
+        assert!(matches!(
+            keymap.press(press(ctrl, "u")),
+            Lookup::Command("case.upper")
+        ));
+        assert!(matches!(keymap.press(press(ctrl, "k")), Lookup::Pending(_)));
+        assert!(matches!(
+            keymap.press(press(ctrl, "f12")),
+            Lookup::Unbound(_)
+        ));
+        assert!(matches!(
+            keymap.press(press(ModifiersState::empty(), "a")),
+            Lookup::Unmatched
+        ));
+    }
+
+    #[test]
+    fn chords_go_on_over_modifier_presses() {
+        let mut keymap = Keymap::default();
+        let ctrl = ModifiersState::CONTROL;
+        let control = || {
+            KeyPress::new(
+                &Key::Named(NamedKey::Control),
+                PhysicalKey::Code(KeyCode::ControlLeft),
+                ModifiersState::empty(),
+            )
+        };
+        assert!(control().modifier);
+        assert!(matches!(keymap.press(control()), Lookup::Unmatched));
+        assert!(matches!(keymap.press(press(ctrl, "k")), Lookup::Pending(_)));
+        // Ctrl let go and pressed again between the two keys
+        assert!(matches!(keymap.press(control()), Lookup::Pending(keys) if keys == "Ctrl+K"));
+        assert!(matches!(
+            keymap.press(press(ctrl, "u")),
+            Lookup::Command("case.upper")
+        ));
+    }
+}
@@ -0,0 +1,163 @@
+use std::path::Path;
+
+pub struct Language {
+    pub name: &'static str,
+    pub extensions: &'static [&'static str],
+    // line endings after which the next line is indented a level deeper
+    pub indent_after: &'static [&'static str],
+    // typed openers that get their closer, quotes are the pairs of a char with itself
+    pub pairs: &'static [(char, char)],
+    pub line_comment: Option<&'static str>,
+    pub block_comment: Option<(&'static str, &'static str)>,
+}
+
+const C_BLOCK: Option<(&str, &str)> = Some(("/*", "*/"));
+
+const BRACKETS_AND_QUOTES: &[(char, char)] =
+    &[('(', ')'), ('[', ']'), ('{', '}'), ('"', '"'), ('\'', '\'')];
+
+// where a single quote is not always closed, such as before a rust lifetime
+const BRACKETS_AND_DOUBLE_QUOTES: &[(char, char)] =
+    &[('(', ')'), ('[', ']'), ('{', '}'), ('"', '"')];
+
+const SCRIPT_PAIRS: &[(char, char)] = &[
+    ('(', ')'),
+    ('[', ']'),
+    ('{', '}'),
+    ('"', '"'),
+    ('\'', '\''),
+    ('`', '`'),
+];
+
+pub const PLAIN_TEXT: Language = Language {
+    name: "Plain Text",
+    extensions: &["txt"],
+    indent_after: &[],
+    pairs: BRACKETS_AND_DOUBLE_QUOTES,
+    line_comment: None,
+    block_comment: None,
+};
+
+pub const LANGUAGES: &[Language] = &[
+    Language {
+        name: "Rust",
+        extensions: &["rs"],
+        indent_after: &["{", "(", "["],
+        pairs: BRACKETS_AND_DOUBLE_QUOTES,
+        line_comment: Some("//"),
+        block_comment: C_BLOCK,
+    },
+    Language {
+        name: "TOML",
+        extensions: &["toml"],
+        indent_after: &["[", "{"],
+        pairs: BRACKETS_AND_QUOTES,
+        line_comment: Some("#"),
+        block_comment: None,
+    },
+    Language {
+        name: "Markdown",
+        extensions: &["md", "markdown"],
+        indent_after: &[],
+        pairs: SCRIPT_PAIRS,
+        line_comment: None,
+        block_comment: Some(("<!--", "-->")),
+    },
+    Language {
+        name: "C",
+        extensions: &["c", "h"],
+        indent_after: &["{", "(", "["],
+        pairs: BRACKETS_AND_QUOTES,
+        line_comment: Some("//"),
+        block_comment: C_BLOCK,
+    },
+    Language {
+        name: "C++",
+        extensions: &["cpp", "cc", "cxx", "hpp", "hh"],
+        indent_after: &["{", "(", "["],
+        pairs: BRACKETS_AND_QUOTES,
+        line_comment: Some("//"),
+        block_comment: C_BLOCK,
+    },
+    Language {
+        name: "Python",
+        extensions: &["py"],
+        indent_after: &[":", "(", "[", "{"],
+        pairs: BRACKETS_AND_QUOTES,
+        line_comment: Some("#"),
+        block_comment: None,
+    },
+    Language {
+        name: "JavaScript",
+        extensions: &["js", "mjs"],
+        indent_after: &["{", "(", "["],
+        pairs: SCRIPT_PAIRS,
+        line_comment: Some("//"),
+        block_comment: C_BLOCK,
+    },
+    Language {
+        name: "TypeScript",
+        extensions: &["ts", "tsx"],
+        indent_after: &["{", "(", "["],
+        pairs: SCRIPT_PAIRS,
+        line_comment: Some("//"),
+        block_comment: C_BLOCK,
+    },
+    Language {
+        name: "JSON",
+        extensions: &["json"],
+        indent_after: &["{", "["],
+        pairs: BRACKETS_AND_DOUBLE_QUOTES,
+        line_comment: None,
+        block_comment: None,
+    },
+    Language {
+        name: "GLSL",
+        extensions: &["vs", "fs", "glsl", "vert", "frag"],
+        indent_after: &["{", "(", "["],
+        pairs: BRACKETS_AND_DOUBLE_QUOTES,
+        line_comment: Some("//"),
+        block_comment: C_BLOCK,
+    },
+    Language {
+        name: "Shell",
+        extensions: &["sh", "bash"],
+        indent_after: &["then", "do", "else", "{", "("],
+        pairs: BRACKETS_AND_QUOTES,
+        line_comment: Some("#"),
+        block_comment: None,
+    },
+];
+
+impl Language {
+    // pick the language by file extension, plain text when unknown
+    pub fn detect(path: Option<&Path>) -> &'static Language {
+        let extension = path
+            .and_then(|path| path.extension())
+            .and_then(|extension| extension.to_str());
+
+        extension
+            .and_then(|extension| {
+                LANGUAGES
+                    .iter()
+                    .find(|language| language.extensions.contains(&extension))
+            })
+            .unwrap_or(&PLAIN_TEXT)
+    }
+
+    pub fn is_quote(&self, c: char) -> bool {
+        self.pairs.contains(&(c, c))
+    }
+
+    // whether the line, without trailing whitespace, ends with an opener; words only count
+    // whole
+    pub fn opens_block(&self, line: &str) -> bool {
+        self.indent_after.iter().any(|opener| {
+            let Some(before) = line.strip_suffix(opener) else {
+                return false;
+            };
+            let is_word = |c: char| c.is_alphanumeric() || c == '_';
+            !opener.chars().all(is_word) || !before.ends_with(is_word)
+        })
+    }
+}
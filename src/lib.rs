@@ -1,3 +1,43 @@
+pub mod app;
+pub mod brackets;
+pub mod buffers;
+pub mod case;
+pub mod columns;
+pub mod commands;
+pub mod comments;
+pub mod config;
+pub mod editor;
+pub mod emacs;
+pub mod encoding;
+pub mod file_finder;
+pub mod find;
+pub mod folding;
+pub mod fuzzy;
 pub mod gl_renderer;
+pub mod goto;
+pub mod gutter;
+pub mod history;
+pub mod indent;
 pub mod init;
+pub mod input;
+pub mod jumps;
+pub mod keymap;
+pub mod language;
+pub mod lines;
+pub mod macros;
+pub mod marks;
+pub mod mouse;
+pub mod palette;
+pub mod picker;
+pub mod project_search;
+pub mod selection;
+pub mod session;
+pub mod snippets;
+pub mod status_bar;
+#[path = "text-document.rs"]
+pub mod text_document;
+pub mod text_view;
 pub mod utils;
+pub mod view;
+pub mod vim;
+pub mod wrap;
@@ -0,0 +1,375 @@
+use std::{
+    cmp::Ordering,
+    collections::{hash_map::RandomState, HashSet},
+    hash::BuildHasher,
+    ops::{Range, RangeInclusive},
+};
+
+use crate::{editor::Editor, history::EditKind, selection::Selection};
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Sort {
+    Lexicographic,
+    // runs of digits compare by their value, so file2 comes before file10
+    Natural,
+    // by the number the line starts with, lines without one first
+    Numeric,
+    CaseInsensitive,
+}
+
+// runs of selected lines next to each other, as first and last line
+fn line_groups(editor: &Editor) -> Vec<RangeInclusive<usize>> {
+    let mut groups: Vec<RangeInclusive<usize>> = Vec::new();
+    for line in editor.selected_lines() {
+        match groups.last_mut() {
+            Some(group) if *group.end() + 1 == line => *group = *group.start()..=line,
+            _ => groups.push(line..=line),
+        }
+    }
+    groups
+}
+
+// text of the lines without the line break after the last
+fn lines_range(editor: &Editor, lines: &RangeInclusive<usize>) -> Range<usize> {
+    editor.document.line_start(*lines.start())..editor.document.line_end(*lines.end())
+}
+
+// move every selection by the offset given for the lines it is in; a selection may end
+// at the start of the line after them
+fn shift_selections(editor: &Editor, shifts: &[(Range<usize>, isize)]) -> Vec<Selection> {
+    let shift = |offset: usize| {
+        shifts
+            .iter()
+            .find(|(range, _)| (range.start..=range.end + 1).contains(&offset))
+            .map_or(offset, |(_, by)| offset.saturating_add_signed(*by))
+    };
+    editor
+        .selections()
+        .iter()
+        .map(|selection| Selection::new(shift(selection.anchor), shift(selection.head)))
+        .collect()
+}
+
+// swap the selected lines with the line above or below them
+pub fn move_lines(editor: &mut Editor, down: bool) {
+    let document = &editor.document;
+    let last_line = document.line_count() - 1;
+    let groups = line_groups(editor);
+    let blocked = match down {
+        true => groups.last().is_some_and(|group| *group.end() == last_line),
+        false => groups.first().is_some_and(|group| *group.start() == 0),
+    };
+    if blocked {
+        return;
+    }
+    let mut edits = Vec::new();
+    let mut shifts = Vec::new();
+    for group in groups {
+        let block = lines_range(editor, &group);
+        let other = match down {
+            true => *group.end() + 1,
+            false => *group.start() - 1,
+        };
+        let other_text = document.line(other);
+        let block_text = &document.text()[block.clone()];
+        let (range, text, by) = match down {
+            true => (
+                block.start..document.line_end(other),
+                format!("{other_text}\n{block_text}"),
+                other_text.len() as isize + 1,
+            ),
+            false => (
+                document.line_start(other)..block.end,
+                format!("{block_text}\n{other_text}"),
+                -(other_text.len() as isize + 1),
+            ),
+        };
+        edits.push((range, text));
+        shifts.push((block, by));
+    }
+    let selections = shift_selections(editor, &shifts);
+    editor.edit(EditKind::Other, edits, |_| selections);
+}
+
+// copy the selected lines below themselves, the selections go with the copy
+pub fn duplicate_lines(editor: &mut Editor) {
+    let edits = line_groups(editor)
+        .into_iter()
+        .map(|group| {
+            let block = lines_range(editor, &group);
+            let text = format!("{}\n", &editor.document.text()[block.clone()]);
+            (block.start..block.start, text)
+        })
+        .collect();
+    editor.edit_in_place(EditKind::Other, edits);
+}
+
+// remove the selected lines with their line breaks
+pub fn delete_lines(editor: &mut Editor) {
+    let document = &editor.document;
+    let edits = line_groups(editor)
+        .into_iter()
+        .map(|group| {
+            let block = lines_range(editor, &group);
+            let range = match *group.end() + 1 < document.line_count() {
+                true => block.start..block.end + 1,
+                // the last line takes the break before it
+                false => block.start.saturating_sub(1)..block.end,
+            };
+            (range, String::new())
+        })
+        .collect();
+    editor.edit_in_place(EditKind::Delete, edits);
+}
+
+// join the selected lines, or the line with the next one, putting one space between them
+// in place of the break and the whitespace around it; none next to brackets or blanks
+pub fn join_lines(editor: &mut Editor) {
+    let document = &editor.document;
+    let text = document.text();
+    let last_line = document.line_count() - 1;
+    let mut edits = Vec::new();
+    for group in line_groups(editor) {
+        let (first, last) = match group.start() == group.end() {
+            true => (*group.start(), (*group.end() + 1).min(last_line)),
+            false => (*group.start(), *group.end()),
+        };
+        for line in first..last {
+            let before = document.line(line).trim_end();
+            let start = document.line_start(line) + before.len();
+            let next = document.line(line + 1);
+            let after = next.trim_start();
+            let end = document.line_start(line + 1) + next.len() - after.len();
+            let tight = before.is_empty()
+                || after.is_empty()
+                || before.ends_with(['(', '[', '{'])
+                || after.starts_with([')', ']', '}', ',', ';']);
+            let joint = match tight {
+                true => String::new(),
+                false => " ".to_string(),
+            };
+            if text[start..end] != joint {
+                edits.push((start..end, joint));
+            }
+        }
+    }
+    editor.edit_in_place(EditKind::Other, edits);
+}
+
+// the digit and non-digit runs of the text
+fn chunks(text: &str) -> impl Iterator<Item = &str> {
+    let mut rest = text;
+    std::iter::from_fn(move || {
+        let first = rest.chars().next()?;
+        let split = rest
+            .find(|c: char| c.is_ascii_digit() != first.is_ascii_digit())
+            .unwrap_or(rest.len());
+        let (chunk, remaining) = rest.split_at(split);
+        rest = remaining;
+        Some(chunk)
+    })
+}
+
+fn natural_cmp(a: &str, b: &str) -> Ordering {
+    let mut a_chunks = chunks(a);
+    let mut b_chunks = chunks(b);
+    loop {
+        let (a, b) = match (a_chunks.next(), b_chunks.next()) {
+            (None, None) => return Ordering::Equal,
+            (None, Some(_)) => return Ordering::Less,
+            (Some(_), None) => return Ordering::Greater,
+            (Some(a), Some(b)) => (a, b),
+        };
+        let numbers = a.starts_with(|c: char| c.is_ascii_digit())
+            && b.starts_with(|c: char| c.is_ascii_digit());
+        let order = match numbers {
+            true => {
+                let (a, b) = (a.trim_start_matches('0'), b.trim_start_matches('0'));
+                a.len().cmp(&b.len()).then_with(|| a.cmp(b))
+            }
+            false => a.cmp(b),
+        };
+        if order != Ordering::Equal {
+            return order;
+        }
+    }
+}
+
+// the number at the start of the line
+fn leading_number(line: &str) -> Option<f64> {
+    let line = line.trim_start();
+    let end = line
+        .char_indices()
+        .find(|(i, c)| !(c.is_ascii_digit() || *c == '.' || (*i == 0 && (*c == '-' || *c == '+'))))
+        .map_or(line.len(), |(i, _)| i);
+    (1..=end).rev().find_map(|end| line[..end].parse().ok())
+}
+
+// replace each block of selected lines, or all lines when the selections are on a single
+// line, with the ones made from them as one step; the rewritten blocks stay selected
+fn rewrite_lines(editor: &mut Editor, mut rewrite: impl FnMut(Vec<&str>) -> Vec<String>) {
+    let mut groups = line_groups(editor);
+    let whole = matches!(groups.as_slice(), [group] if group.start() == group.end());
+    if whole {
+        groups = vec![0..=editor.document.line_count() - 1];
+    }
+    let edits = groups
+        .iter()
+        .map(|lines| {
+            let range = lines_range(editor, lines);
+            let lines = editor.document.text()[range.clone()].split('\n').collect();
+            (range, rewrite(lines).join("\n"))
+        })
+        .collect();
+    let caret = editor.caret();
+    editor.edit(EditKind::Other, edits, |inserted| match whole {
+        true => vec![Selection::caret(caret.min(inserted[0].end))],
+        false => inserted
+            .iter()
+            .map(|range| Selection::new(range.start, range.end))
+            .collect(),
+    });
+}
+
+// sort the selected lines, all of them when the selection is on one line; equal lines
+// keep their order
+pub fn sort_lines(editor: &mut Editor, sort: Sort, descending: bool) {
+    rewrite_lines(editor, |mut lines| {
+        match sort {
+            Sort::Lexicographic => lines.sort(),
+            Sort::Natural => lines.sort_by(|a, b| natural_cmp(a, b)),
+            Sort::Numeric => lines.sort_by(|a, b| {
+                let (a, b) = (leading_number(a), leading_number(b));
+                a.partial_cmp(&b).unwrap_or(Ordering::Equal)
+            }),
+            Sort::CaseInsensitive => lines.sort_by_cached_key(|line| line.to_lowercase()),
+        }
+        if descending {
+            lines.reverse();
+        }
+        lines.into_iter().map(String::from).collect()
+    });
+}
+
+// drop the lines that already came before, the first of each stays
+pub fn unique_lines(editor: &mut Editor) {
+    rewrite_lines(editor, |lines| {
+        let mut seen = HashSet::new();
+        lines
+            .into_iter()
+            .filter(|line| seen.insert(*line))
+            .map(String::from)
+            .collect()
+    });
+}
+
+pub fn reverse_lines(editor: &mut Editor) {
+    rewrite_lines(editor, |lines| {
+        lines.into_iter().rev().map(String::from).collect()
+    });
+}
+
+// xorshift generator, random enough to shuffle lines
+struct Random(u64);
+
+impl Random {
+    fn new() -> Self {
+        // zero would only ever give zeros
+        Self(RandomState::new().hash_one(0) | 1)
+    }
+
+    // a number below the bound
+    fn below(&mut self, bound: usize) -> usize {
+        self.0 ^= self.0 << 13;
+        self.0 ^= self.0 >> 7;
+        self.0 ^= self.0 << 17;
+        (self.0 % bound as u64) as usize
+    }
+}
+
+// put the lines in a random order
+pub fn shuffle_lines(editor: &mut Editor) {
+    let mut random = Random::new();
+    rewrite_lines(editor, |mut lines| {
+        for i in (1..lines.len()).rev() {
+            lines.swap(i, random.below(i + 1));
+        }
+        lines.into_iter().map(String::from).collect()
+    });
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{editor::Indentation, selection::Selections, text_document::TextDocument};
+
+    // an editor on the text with the given selections
+    fn editor_with(text: &str, selections: Vec<Selection>) -> Editor {
+        let mut editor = Editor::new(TextDocument::from_text(text), Indentation::default());
+        editor.set_selections(Selections::new(selections, 0));
+        editor
+    }
+
+    #[test]
+    fn natural_order_compares_numbers_by_value() {
+        assert_eq!(natural_cmp("file2", "file10"), Ordering::Less);
+        assert_eq!(natural_cmp("file10", "file9"), Ordering::Greater);
+        assert_eq!(natural_cmp("a007", "a7"), Ordering::Equal);
+        assert_eq!(natural_cmp("a", "a1"), Ordering::Less);
+        assert_eq!(natural_cmp("b1", "a2"), Ordering::Greater);
+    }
+
+    #[test]
+    fn sorts_every_line_from_a_caret() {
+        let mut editor = editor_with("file10\nfile2\nFile1", vec![Selection::caret(0)]);
+        sort_lines(&mut editor, Sort::Natural, false);
+        assert_eq!(editor.document.text(), "File1\nfile2\nfile10");
+        sort_lines(&mut editor, Sort::CaseInsensitive, true);
+        assert_eq!(editor.document.text(), "file2\nfile10\nFile1");
+
+        let mut editor = editor_with("10 b\nx\n-2 a\n3", vec![Selection::caret(0)]);
+        sort_lines(&mut editor, Sort::Numeric, false);
+        assert_eq!(editor.document.text(), "x\n-2 a\n3\n10 b");
+    }
+
+    #[test]
+    fn rewrites_each_selected_block_alone() {
+        // lines 0-1 and 3-4 are selected, line 2 between them is not
+        let text = "b\na\nz\nd\nc\ny";
+        let mut editor = editor_with(text, vec![Selection::new(0, 3), Selection::new(6, 9)]);
+        sort_lines(&mut editor, Sort::Lexicographic, false);
+        assert_eq!(editor.document.text(), "a\nb\nz\nc\nd\ny");
+        let ranges: Vec<_> = editor.selections().iter().map(Selection::range).collect();
+        assert_eq!(ranges, vec![0..3, 6..9]);
+
+        reverse_lines(&mut editor);
+        assert_eq!(editor.document.text(), text);
+    }
+
+    #[test]
+    fn unique_keeps_the_first_of_each_line() {
+        let mut editor = editor_with("a\nb\na\nc\nb", vec![Selection::caret(0)]);
+        unique_lines(&mut editor);
+        assert_eq!(editor.document.text(), "a\nb\nc");
+    }
+
+    #[test]
+    fn shuffle_keeps_the_lines() {
+        let mut editor = editor_with("1\n2\n3\n4\n5\n6", vec![Selection::caret(0)]);
+        shuffle_lines(&mut editor);
+        let mut lines: Vec<_> = editor.document.text().split('\n').collect();
+        lines.sort();
+        assert_eq!(lines, vec!["1", "2", "3", "4", "5", "6"]);
+    }
+
+    #[test]
+    fn join_puts_one_space_between_lines() {
+        let mut editor = editor_with("one  \n   two\nthree", vec![Selection::caret(0)]);
+        join_lines(&mut editor);
+        assert_eq!(editor.document.text(), "one two\nthree");
+
+        let mut editor = editor_with("call(\n  a,\n  b\n)", vec![Selection::new(0, 16)]);
+        join_lines(&mut editor);
+        assert_eq!(editor.document.text(), "call(a, b)");
+    }
+}
@@ -0,0 +1,217 @@
+use glyph_brush::OwnedSection;
+use serde::{Deserialize, Serialize};
+use winit::keyboard::{Key, ModifiersState, NamedKey};
+
+use crate::{
+    picker::{Picker, Response},
+    utils::RectVertex,
+    view::View,
+};
+
+// the picker entry for the macro recorded last, which has no name until it is saved
+pub const LAST_RECORDED: &str = "last recorded";
+
+// what a macro replays: commands by id and typed text, not the keys that produced them,
+// except for the keys of modal editing which are commands of their own
+#[derive(Clone, Debug, PartialEq, Eq, Deserialize, Serialize)]
+#[serde(rename_all = "snake_case")]
+pub enum Step {
+    Command(String),
+    Insert(String),
+    // in the notation of vim::strokes
+    Keys(String),
+}
+
+// collects the steps between starting and stopping a recording
+#[derive(Default)]
+pub struct Recorder {
+    recording: Option<Vec<Step>>,
+    last: Option<Vec<Step>>,
+    // a macro is playing, its steps are not recorded again
+    pub playing: bool,
+}
+
+impl Recorder {
+    pub fn is_recording(&self) -> bool {
+        self.recording.is_some()
+    }
+
+    pub fn start(&mut self) {
+        self.recording = Some(Vec::new());
+    }
+
+    // the number of steps recorded, None if there was no recording
+    pub fn stop(&mut self) -> Option<usize> {
+        let steps = self.recording.take()?;
+        let count = steps.len();
+        self.last = Some(steps);
+        Some(count)
+    }
+
+    pub fn last(&self) -> Option<&[Step]> {
+        self.last.as_deref()
+    }
+
+    fn steps(&mut self) -> Option<&mut Vec<Step>> {
+        self.recording.as_mut().filter(|_| !self.playing)
+    }
+
+    pub fn command(&mut self, id: &str) {
+        // recording and playing are not part of the macro
+        if id.starts_with("macro.") {
+            return;
+        }
+        if let Some(steps) = self.steps() {
+            steps.push(Step::Command(id.into()));
+        }
+    }
+
+    // typing in a row is one step
+    pub fn insert(&mut self, text: &str) {
+        let Some(steps) = self.steps() else {
+            return;
+        };
+        match steps.last_mut() {
+            Some(Step::Insert(typed)) => typed.push_str(text),
+            _ => steps.push(Step::Insert(text.into())),
+        }
+    }
+
+    // keys handled by modal editing, those in a row are one step
+    pub fn keys(&mut self, keys: &str) {
+        let Some(steps) = self.steps() else {
+            return;
+        };
+        match steps.last_mut() {
+            Some(Step::Keys(pressed)) => pressed.push_str(keys),
+            _ => steps.push(Step::Keys(keys.into())),
+        }
+    }
+}
+
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub enum MacroAction {
+    #[default]
+    Play,
+    // once on every line the selection touches
+    PlayOnLines,
+    // name the last recorded macro
+    Save,
+}
+
+pub struct Choice {
+    pub action: MacroAction,
+    pub name: String,
+    pub times: usize,
+}
+
+// overlay listing the macros to play or to save the last recorded one over; a count at the
+// end of the query plays it that many times
+#[derive(Default)]
+pub struct MacroPicker {
+    open: bool,
+    action: MacroAction,
+    picker: Picker,
+    names: Vec<String>,
+    chosen: Option<Choice>,
+}
+
+impl MacroPicker {
+    pub fn is_open(&self) -> bool {
+        self.open
+    }
+
+    pub fn open(&mut self, action: MacroAction, names: Vec<String>) {
+        self.open = true;
+        self.action = action;
+        self.names = names;
+        self.picker.reset();
+        self.update();
+    }
+
+    pub fn close(&mut self) {
+        self.open = false;
+    }
+
+    pub fn take_chosen(&mut self) -> Option<Choice> {
+        self.chosen.take()
+    }
+
+    // the query without the count, and the count
+    fn split_query(&self) -> (&str, usize) {
+        let query = self.picker.query.text();
+        if self.action == MacroAction::Save {
+            return (query, 1);
+        }
+        match query
+            .rsplit_once(' ')
+            .map(|(name, count)| (name, count.parse()))
+        {
+            Some((name, Ok(times))) => (name, times),
+            _ => match query.parse() {
+                Ok(times) => ("", times),
+                Err(_) => (query, 1),
+            },
+        }
+    }
+
+    fn update(&mut self) {
+        let query = self.split_query().0.to_string();
+        let names = &self.names;
+        self.picker
+            .filter(&query, names.iter().map(String::as_str), |a, b| {
+                names[a].cmp(&names[b])
+            });
+    }
+
+    pub fn paste(&mut self, text: &str) {
+        self.picker.paste(text);
+        self.update();
+    }
+
+    // handle a key while the overlay is open, false to let the editor have it
+    pub fn handle_key(&mut self, key: &Key, modifiers: ModifiersState) -> bool {
+        match key {
+            Key::Named(NamedKey::Escape) => self.close(),
+            Key::Named(NamedKey::Enter) => {
+                let (query, times) = self.split_query();
+                let typed = query.trim().to_string();
+                let selected = self
+                    .picker
+                    .selected()
+                    .map(|index| self.names[index].clone());
+                // saving goes by the typed name, playing by the selected macro
+                let name = match self.action {
+                    MacroAction::Save if !typed.is_empty() => Some(typed),
+                    _ => selected,
+                };
+                self.chosen = name.map(|name| Choice {
+                    action: self.action,
+                    name,
+                    times,
+                });
+                self.close();
+            }
+            key => match self.picker.handle_key(key) {
+                Response::Ignored => return !modifiers.control_key(),
+                Response::Moved => {}
+                Response::Edited => self.update(),
+            },
+        }
+        true
+    }
+
+    pub fn sections(&self, view: &View) -> Vec<OwnedSection> {
+        let status = match (self.action, self.split_query().1) {
+            (MacroAction::Save, _) => "name to save as".to_string(),
+            (_, 1) => "add a count to repeat".to_string(),
+            (_, times) => format!("×{times}"),
+        };
+        self.picker
+            .sections(view, &status, |index| self.names[index].as_str(), |_| None)
+    }
+
+    pub fn rects(&self, view: &View) -> Vec<RectVertex> {
+        self.picker.rects(view)
+    }
+}
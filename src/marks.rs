@@ -0,0 +1,363 @@
+use std::collections::BTreeMap;
+
+use glyph_brush::OwnedSection;
+use serde::{Deserialize, Serialize};
+use winit::keyboard::{Key, ModifiersState, NamedKey};
+
+use crate::{
+    editor::Editor,
+    history::Change,
+    jumps::Location,
+    picker::{Picker, Response},
+    selection::Selection,
+    text_document::TextDocument,
+    utils::RectVertex,
+    view::View,
+};
+
+// where an offset ends up after the change; one in deleted text goes to where it was
+fn follow(offset: usize, change: &Change) -> usize {
+    let end = change.offset + change.deleted.len();
+    match offset {
+        offset if offset < change.offset => offset,
+        offset if offset < end => change.offset,
+        offset => offset - change.deleted.len() + change.inserted.len(),
+    }
+}
+
+fn line_and_column(document: &TextDocument, offset: usize) -> [usize; 2] {
+    let line = document.line_of_offset(offset);
+    let start = document.line_start(line);
+    [line, document.text()[start..offset].chars().count()]
+}
+
+fn offset_of(document: &TextDocument, [line, column]: [usize; 2]) -> usize {
+    let line = line.min(document.line_count() - 1);
+    let text = document.line(line);
+    let offset = text
+        .char_indices()
+        .nth(column)
+        .map_or(text.len(), |(i, _)| i);
+    document.line_start(line) + offset
+}
+
+// the marks of a file as lines and (line, column) pairs, for keeping them between runs
+#[derive(Clone, Debug, Default, PartialEq, Eq, Deserialize, Serialize)]
+#[serde(default)]
+pub struct SavedMarks {
+    pub bookmarks: Vec<usize>,
+    pub named: BTreeMap<String, [usize; 2]>,
+}
+
+impl SavedMarks {
+    pub fn is_empty(&self) -> bool {
+        self.bookmarks.is_empty() && self.named.is_empty()
+    }
+}
+
+// bookmarked lines and named marks of a buffer, held as offsets that move with the text
+#[derive(Clone, Debug, Default)]
+pub struct Marks {
+    // line starts when set, sorted
+    bookmarks: Vec<usize>,
+    named: BTreeMap<String, usize>,
+    // set once the marks saved for the file were applied
+    pub restored: bool,
+}
+
+impl Marks {
+    pub fn update(&mut self, change: &Change) {
+        for offset in self.bookmarks.iter_mut().chain(self.named.values_mut()) {
+            *offset = follow(*offset, change);
+        }
+    }
+
+    // lines with a bookmark, in order; bookmarks whose lines were joined count once
+    pub fn bookmarked_lines(&self, document: &TextDocument) -> Vec<usize> {
+        let mut lines: Vec<usize> = self
+            .bookmarks
+            .iter()
+            .map(|offset| document.line_of_offset(*offset))
+            .collect();
+        lines.dedup();
+        lines
+    }
+
+    // add a bookmark on the line or remove the one there, true if it was added
+    pub fn toggle_bookmark(&mut self, document: &TextDocument, line: usize) -> bool {
+        let count = self.bookmarks.len();
+        self.bookmarks
+            .retain(|offset| document.line_of_offset(*offset) != line);
+        if self.bookmarks.len() != count {
+            return false;
+        }
+        let start = document.line_start(line);
+        let index = self.bookmarks.partition_point(|offset| *offset < start);
+        self.bookmarks.insert(index, start);
+        true
+    }
+
+    pub fn clear_bookmarks(&mut self) {
+        self.bookmarks.clear();
+    }
+
+    pub fn named(&self, name: &str) -> Option<usize> {
+        self.named.get(name).copied()
+    }
+
+    pub fn named_marks(&self) -> impl Iterator<Item = (&str, usize)> {
+        self.named
+            .iter()
+            .map(|(name, offset)| (name.as_str(), *offset))
+    }
+
+    pub fn set_named(&mut self, name: &str, offset: usize) {
+        self.named.insert(name.to_string(), offset);
+    }
+
+    pub fn remove_named(&mut self, name: &str) -> bool {
+        self.named.remove(name).is_some()
+    }
+
+    pub fn saved(&self, document: &TextDocument) -> SavedMarks {
+        SavedMarks {
+            bookmarks: self.bookmarked_lines(document),
+            named: self
+                .named
+                .iter()
+                .map(|(name, offset)| (name.clone(), line_and_column(document, *offset)))
+                .collect(),
+        }
+    }
+
+    pub fn restore(&mut self, document: &TextDocument, saved: &SavedMarks) {
+        self.bookmarks = saved
+            .bookmarks
+            .iter()
+            .filter(|line| **line < document.line_count())
+            .map(|line| document.line_start(*line))
+            .collect();
+        self.bookmarks.sort_unstable();
+        self.bookmarks.dedup();
+        self.named = saved
+            .named
+            .iter()
+            .map(|(name, position)| (name.clone(), offset_of(document, *position)))
+            .collect();
+    }
+}
+
+// move the caret to the next or previous bookmarked line, wrapping around
+pub fn next_bookmark(editor: &mut Editor, forward: bool) {
+    let lines = editor.marks.bookmarked_lines(&editor.document);
+    let caret_line = editor.caret_line();
+    let found = match forward {
+        true => lines
+            .iter()
+            .find(|line| **line > caret_line)
+            .or(lines.first()),
+        false => lines
+            .iter()
+            .rev()
+            .find(|line| **line < caret_line)
+            .or(lines.last()),
+    };
+    match found {
+        Some(line) => {
+            editor.select(Selection::caret(editor.document.line_start(*line)));
+            editor.scroll_to_center();
+        }
+        None => editor.set_message("No bookmarks"),
+    }
+}
+
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub enum MarkAction {
+    // the bookmarks of every buffer and of the files marked in earlier runs
+    #[default]
+    Bookmarks,
+    GotoNamed,
+    // name a mark at the caret, moving it if the name is taken
+    SetNamed,
+}
+
+pub struct MarkEntry {
+    pub label: String,
+    pub detail: Option<String>,
+    pub location: Location,
+}
+
+pub enum MarkChoice {
+    Set(String),
+    Visit(Location),
+}
+
+// overlay listing bookmarks or named marks to go to, or naming a new mark
+#[derive(Default)]
+pub struct MarkPicker {
+    open: bool,
+    action: MarkAction,
+    picker: Picker,
+    entries: Vec<MarkEntry>,
+    chosen: Option<MarkChoice>,
+}
+
+impl MarkPicker {
+    pub fn is_open(&self) -> bool {
+        self.open
+    }
+
+    pub fn open(&mut self, action: MarkAction, entries: Vec<MarkEntry>) {
+        self.open = true;
+        self.action = action;
+        self.entries = entries;
+        self.picker.reset();
+        self.update();
+    }
+
+    pub fn close(&mut self) {
+        self.open = false;
+    }
+
+    pub fn take_chosen(&mut self) -> Option<MarkChoice> {
+        self.chosen.take()
+    }
+
+    fn update(&mut self) {
+        let entries = &self.entries;
+        // the list keeps its order for equally good matches
+        self.picker
+            .update(entries.iter().map(|entry| entry.label.as_str()), |a, b| {
+                a.cmp(&b)
+            });
+    }
+
+    pub fn paste(&mut self, text: &str) {
+        self.picker.paste(text);
+        self.update();
+    }
+
+    // handle a key while the overlay is open, false to let the editor have it
+    pub fn handle_key(&mut self, key: &Key, modifiers: ModifiersState) -> bool {
+        match key {
+            Key::Named(NamedKey::Escape) => self.close(),
+            Key::Named(NamedKey::Enter) => {
+                let typed = self.picker.query.text().trim().to_string();
+                let selected = self.picker.selected().map(|index| &self.entries[index]);
+                self.chosen = match self.action {
+                    MarkAction::SetNamed if !typed.is_empty() => Some(MarkChoice::Set(typed)),
+                    MarkAction::SetNamed => {
+                        selected.map(|entry| MarkChoice::Set(entry.label.clone()))
+                    }
+                    _ => selected.map(|entry| MarkChoice::Visit(entry.location.clone())),
+                };
+                self.close();
+            }
+            key => match self.picker.handle_key(key) {
+                Response::Ignored => return !modifiers.control_key(),
+                Response::Moved => {}
+                Response::Edited => self.update(),
+            },
+        }
+        true
+    }
+
+    pub fn sections(&self, view: &View) -> Vec<OwnedSection> {
+        let status = match self.action {
+            MarkAction::Bookmarks => "bookmarks",
+            MarkAction::GotoNamed => "marks",
+            MarkAction::SetNamed => "name of the mark",
+        };
+        self.picker.sections(
+            view,
+            status,
+            |index| self.entries[index].label.as_str(),
+            |index| self.entries[index].detail.clone(),
+        )
+    }
+
+    pub fn rects(&self, view: &View) -> Vec<RectVertex> {
+        self.picker.rects(view)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    // replace the range of the document and let the marks follow
+    fn edit(document: &mut TextDocument, marks: &mut Marks, offset: usize, len: usize, text: &str) {
+        let change = Change {
+            offset,
+            deleted: document.text()[offset..offset + len].into(),
+            inserted: text.into(),
+        };
+        document.replace(offset..offset + len, text);
+        marks.update(&change);
+    }
+
+    #[test]
+    fn marks_move_with_the_text() {
+        let mut document = TextDocument::from_text("one\ntwo\nthree");
+        let mut marks = Marks::default();
+        marks.set_named("a", 5);
+        marks.set_named("b", 10);
+        marks.toggle_bookmark(&document, 1);
+
+        // an insert before them moves them, one after leaves them
+        edit(&mut document, &mut marks, 0, 0, "zero\n");
+        edit(&mut document, &mut marks, 18, 0, "!");
+        assert_eq!(marks.named("a"), Some(10));
+        assert_eq!(marks.named("b"), Some(15));
+        assert_eq!(marks.bookmarked_lines(&document), vec![2]);
+
+        // one in deleted text goes to where the deletion was
+        edit(&mut document, &mut marks, 9, 3, "");
+        assert_eq!(document.text(), "zero\none\n\nthree!");
+        assert_eq!(marks.named("a"), Some(9));
+        assert_eq!(marks.named("b"), Some(12));
+    }
+
+    #[test]
+    fn bookmarks_on_joined_lines_count_once() {
+        let mut document = TextDocument::from_text("a\nb\nc");
+        let mut marks = Marks::default();
+        assert!(marks.toggle_bookmark(&document, 0));
+        assert!(marks.toggle_bookmark(&document, 1));
+        edit(&mut document, &mut marks, 1, 1, " ");
+        assert_eq!(document.text(), "a b\nc");
+        assert_eq!(marks.bookmarked_lines(&document), vec![0]);
+        assert_eq!(marks.saved(&document).bookmarks, vec![0]);
+
+        // toggling the line takes away both
+        assert!(!marks.toggle_bookmark(&document, 0));
+        assert!(marks.bookmarked_lines(&document).is_empty());
+    }
+
+    #[test]
+    fn saved_marks_restore_as_lines_and_columns() {
+        let document = TextDocument::from_text("one\ntwö\nthree");
+        let mut marks = Marks::default();
+        marks.toggle_bookmark(&document, 2);
+        marks.set_named("a", 8);
+        let saved = marks.saved(&document);
+        assert_eq!(saved.bookmarks, vec![2]);
+        assert_eq!(saved.named["a"], [1, 3]);
+
+        let mut restored = Marks::default();
+        restored.restore(&document, &saved);
+        assert_eq!(restored.saved(&document), saved);
+        assert_eq!(restored.named("a"), Some(8));
+
+        // lines past the end of a shorter file are dropped or clamped
+        let shorter = TextDocument::from_text("x\nyy");
+        let saved = SavedMarks {
+            bookmarks: vec![1, 5],
+            named: BTreeMap::from([("a".to_string(), [7, 1]), ("b".to_string(), [0, 9])]),
+        };
+        restored.restore(&shorter, &saved);
+        assert_eq!(restored.bookmarked_lines(&shorter), vec![1]);
+        assert_eq!(restored.named("a"), Some(3));
+        assert_eq!(restored.named("b"), Some(1));
+    }
+}
@@ -0,0 +1,178 @@
+use std::{
+    ops::Range,
+    time::{Duration, Instant},
+};
+
+use winit::dpi::PhysicalPosition;
+
+use crate::{
+    editor::{Block, Editor},
+    selection::Selection,
+    text_view::TextView,
+};
+
+// clicks closer together than this count as double and triple clicks
+const MULTI_CLICK_TIME: Duration = Duration::from_millis(400);
+const MULTI_CLICK_DISTANCE: f64 = 4.0;
+// delay between scroll steps while dragging outside the text area
+const AUTO_SCROLL_INTERVAL: Duration = Duration::from_millis(40);
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Granularity {
+    Char,
+    Word,
+    Line,
+}
+
+struct Drag {
+    granularity: Granularity,
+    // unit under the initial click, always kept selected
+    anchor: Range<usize>,
+    // the click added a selection instead of replacing them all
+    add: bool,
+    // line and visual column of an added caret, dragging from it selects a block
+    block_anchor: Option<(usize, usize)>,
+}
+
+pub struct Mouse {
+    pub position: PhysicalPosition<f64>,
+    drag: Option<Drag>,
+    last_click: Option<(Instant, PhysicalPosition<f64>)>,
+    click_count: usize,
+    last_scroll: Instant,
+}
+
+impl Default for Mouse {
+    fn default() -> Self {
+        Self {
+            position: PhysicalPosition::new(0.0, 0.0),
+            drag: None,
+            last_click: None,
+            click_count: 0,
+            last_scroll: Instant::now(),
+        }
+    }
+}
+
+impl Mouse {
+    // left button pressed: place the caret, select a word or a line depending on the click
+    // count, extend the primary selection or add another selection
+    pub fn press(
+        &mut self,
+        editor: &mut Editor,
+        text_view: &TextView,
+        in_gutter: bool,
+        extend: bool,
+        add: bool,
+    ) {
+        let now = Instant::now();
+        let repeated = self.last_click.is_some_and(|(time, position)| {
+            now - time < MULTI_CLICK_TIME
+                && (position.x - self.position.x).abs() < MULTI_CLICK_DISTANCE
+                && (position.y - self.position.y).abs() < MULTI_CLICK_DISTANCE
+        });
+        self.click_count = match repeated {
+            true => self.click_count % 3 + 1,
+            false => 1,
+        };
+        self.last_click = Some((now, self.position));
+
+        let Some(offset) = self.offset(text_view) else {
+            return;
+        };
+        let granularity = match (in_gutter, self.click_count) {
+            (true, _) | (false, 3) => Granularity::Line,
+            (false, 2) => Granularity::Word,
+            _ => Granularity::Char,
+        };
+
+        let drag = match extend {
+            true => {
+                let anchor = editor.primary().anchor;
+                Drag {
+                    granularity: Granularity::Char,
+                    anchor: anchor..anchor,
+                    add: false,
+                    block_anchor: None,
+                }
+            }
+            false => Drag {
+                granularity,
+                anchor: unit(editor, granularity, offset),
+                add,
+                block_anchor: add.then(|| self.visual_position(editor, text_view, offset)),
+            },
+        };
+        let head = unit(editor, drag.granularity, offset);
+        let selection = Selection::between(drag.anchor.clone(), head);
+        match drag.add {
+            true => editor.add_selection(selection),
+            false => editor.select(selection),
+        }
+        self.drag = Some(drag);
+    }
+
+    pub fn release(&mut self) {
+        self.drag = None;
+    }
+
+    // extend the drag selection to the pointer, scrolling when it is above or below the
+    // text area; called once per frame with the fresh layout
+    pub fn update_drag(&mut self, editor: &mut Editor, text_view: &TextView) {
+        let Some(drag) = &self.drag else {
+            return;
+        };
+        let Some(offset) = self.offset(text_view) else {
+            return;
+        };
+        match drag.block_anchor {
+            Some(anchor) => {
+                let head = self.visual_position(editor, text_view, offset);
+                if head != anchor || editor.block().is_some() {
+                    editor.select_block(Block { anchor, head });
+                }
+            }
+            // the dragged selection is the primary one after press()
+            None => {
+                let head = unit(editor, drag.granularity, offset);
+                editor.replace_primary(Selection::between(drag.anchor.clone(), head));
+            }
+        }
+
+        let y = self.position.y as f32;
+        let direction = match y {
+            y if y < 0.0 => -1,
+            y if y > text_view.bottom => 1,
+            _ => return,
+        };
+        if self.last_scroll.elapsed() >= AUTO_SCROLL_INTERVAL {
+            editor.scroll_by(direction);
+            self.last_scroll = Instant::now();
+        }
+    }
+
+    // visual position under the pointer, counting columns past the end of the line
+    fn visual_position(
+        &self,
+        editor: &Editor,
+        text_view: &TextView,
+        offset: usize,
+    ) -> (usize, usize) {
+        let (line, column) = editor.visual_position(offset);
+        let past_end = text_view.columns_past_end(self.position.x as f32, self.position.y as f32);
+        (line, column + past_end)
+    }
+
+    fn offset(&self, text_view: &TextView) -> Option<usize> {
+        let x = (self.position.x as f32).max(text_view.left);
+        text_view.offset_at(x, self.position.y as f32)
+    }
+}
+
+fn unit(editor: &Editor, granularity: Granularity, offset: usize) -> Range<usize> {
+    match granularity {
+        Granularity::Char => offset..offset,
+        Granularity::Word => editor.word_range(offset),
+        Granularity::Line => editor.line_range(offset),
+    }
+}
@@ -0,0 +1,90 @@
+use glyph_brush::OwnedSection;
+use winit::keyboard::{Key, ModifiersState, NamedKey};
+
+use crate::{
+    commands::COMMANDS,
+    keymap::Keymap,
+    picker::{Picker, Response},
+    utils::RectVertex,
+    view::View,
+};
+
+// Ctrl+Shift+P overlay listing the commands that fuzzy match the query, recently run
+// ones first
+#[derive(Default)]
+pub struct Palette {
+    open: bool,
+    picker: Picker,
+    // command run with Enter, picked up by the caller
+    chosen: Option<&'static str>,
+}
+
+impl Palette {
+    pub fn is_open(&self) -> bool {
+        self.open
+    }
+
+    pub fn open(&mut self, recent: &[String]) {
+        self.open = true;
+        self.picker.reset();
+        self.update(recent);
+    }
+
+    pub fn close(&mut self) {
+        self.open = false;
+    }
+
+    pub fn take_chosen(&mut self) -> Option<&'static str> {
+        self.chosen.take()
+    }
+
+    fn update(&mut self, recent: &[String]) {
+        let rank = |index: usize| {
+            recent
+                .iter()
+                .position(|id| id == COMMANDS[index].id)
+                .unwrap_or(usize::MAX)
+        };
+        self.picker
+            .update(COMMANDS.iter().map(|command| command.title), |a, b| {
+                rank(a)
+                    .cmp(&rank(b))
+                    .then(COMMANDS[a].title.cmp(COMMANDS[b].title))
+            });
+    }
+
+    pub fn paste(&mut self, text: &str, recent: &[String]) {
+        self.picker.paste(text);
+        self.update(recent);
+    }
+
+    // handle a key while the palette is open, false to let the editor have it
+    pub fn handle_key(&mut self, key: &Key, modifiers: ModifiersState, recent: &[String]) -> bool {
+        match key {
+            Key::Named(NamedKey::Escape) => self.close(),
+            Key::Named(NamedKey::Enter) => {
+                self.chosen = self.picker.selected().map(|index| COMMANDS[index].id);
+                self.close();
+            }
+            key => match self.picker.handle_key(key) {
+                Response::Ignored => return !modifiers.control_key(),
+                Response::Moved => {}
+                Response::Edited => self.update(recent),
+            },
+        }
+        true
+    }
+
+    pub fn sections(&self, view: &View, keymap: &Keymap) -> Vec<OwnedSection> {
+        self.picker.sections(
+            view,
+            "",
+            |index| COMMANDS[index].title,
+            |index| keymap.binding(COMMANDS[index].id).map(ToString::to_string),
+        )
+    }
+
+    pub fn rects(&self, view: &View) -> Vec<RectVertex> {
+        self.picker.rects(view)
+    }
+}
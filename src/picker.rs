@@ -0,0 +1,241 @@
+use std::cmp::Ordering;
+
+use glyph_brush::{Extra, HorizontalAlign, Layout, OwnedSection, OwnedText};
+use winit::keyboard::{Key, NamedKey};
+
+use crate::{
+    fuzzy::{fuzzy_match, Match},
+    gl_renderer::render_gl::OVERLAY_Z,
+    input::LineInput,
+    utils::RectVertex,
+    view::View,
+};
+
+const BACKGROUND_COLOR: [f32; 4] = [0.1, 0.1, 0.12, 1.0];
+const SELECTED_COLOR: [f32; 4] = [0.15, 0.3, 0.5, 1.0];
+const TEXT_COLOR: [f32; 4] = [0.8, 0.8, 0.8, 1.0];
+const MATCHED_COLOR: [f32; 4] = [0.4, 0.7, 1.0, 1.0];
+const LABEL_COLOR: [f32; 4] = [0.5, 0.5, 0.55, 1.0];
+const CARET_COLOR: [f32; 4] = [0.9, 0.9, 0.9, 1.0];
+const PROMPT: &str = "> ";
+// results shown at a time, the list scrolls to keep the selected one in view
+const ROWS: usize = 12;
+// width of the overlay in columns, narrower when the window is
+const COLUMNS: usize = 80;
+
+pub enum Response {
+    Ignored,
+    Moved,
+    // the query changed, the results need updating
+    Edited,
+}
+
+// query field and fuzzy filtered list shared by the overlays at the top of the window
+#[derive(Default)]
+pub struct Picker {
+    pub query: LineInput,
+    // index of the candidate and how it matched, best first
+    results: Vec<(usize, Match)>,
+    selected: usize,
+    // first result shown
+    scroll: usize,
+}
+
+impl Picker {
+    pub fn reset(&mut self) {
+        self.query.set_text("");
+        self.results.clear();
+        self.selected = 0;
+        self.scroll = 0;
+    }
+
+    // candidate index of the selected result
+    pub fn selected(&self) -> Option<usize> {
+        self.results.get(self.selected).map(|(index, _)| *index)
+    }
+
+    pub fn results(&self) -> &[(usize, Match)] {
+        &self.results
+    }
+
+    // match the labels against the query, `tie` orders candidates scoring the same
+    pub fn update<'a>(
+        &mut self,
+        labels: impl Iterator<Item = &'a str>,
+        tie: impl Fn(usize, usize) -> Ordering,
+    ) {
+        let query = self.query.text().to_string();
+        self.filter(&query, labels, tie);
+    }
+
+    // the same with only part of the query, for fields that also take options
+    pub fn filter<'a>(
+        &mut self,
+        query: &str,
+        labels: impl Iterator<Item = &'a str>,
+        tie: impl Fn(usize, usize) -> Ordering,
+    ) {
+        let mut results: Vec<_> = labels
+            .enumerate()
+            .filter_map(|(index, label)| Some((index, fuzzy_match(query, label)?)))
+            .collect();
+        results.sort_by(|(a, a_match), (b, b_match)| {
+            b_match.score.cmp(&a_match.score).then_with(|| tie(*a, *b))
+        });
+        self.results = results;
+        self.select(self.selected);
+    }
+
+    fn select(&mut self, selected: usize) {
+        self.selected = selected.min(self.results.len().saturating_sub(1));
+        self.scroll = self
+            .scroll
+            .min(self.selected)
+            .max((self.selected + 1).saturating_sub(ROWS));
+    }
+
+    fn visible(&self) -> &[(usize, Match)] {
+        &self.results[self.scroll..(self.scroll + ROWS).min(self.results.len())]
+    }
+
+    pub fn paste(&mut self, text: &str) {
+        self.query.insert(text);
+        self.selected = 0;
+    }
+
+    pub fn handle_key(&mut self, key: &Key) -> Response {
+        match key {
+            Key::Named(NamedKey::ArrowUp) => self.select(self.selected.saturating_sub(1)),
+            Key::Named(NamedKey::ArrowDown) => self.select(self.selected + 1),
+            Key::Named(NamedKey::PageUp) => self.select(self.selected.saturating_sub(ROWS)),
+            Key::Named(NamedKey::PageDown) => self.select(self.selected + ROWS),
+            key => {
+                let before = self.query.text().to_string();
+                if !self.query.handle_key(key) {
+                    return Response::Ignored;
+                }
+                if self.query.text() == before {
+                    return Response::Moved;
+                }
+                self.selected = 0;
+                return Response::Edited;
+            }
+        }
+        Response::Moved
+    }
+
+    fn left(&self, view: &View) -> f32 {
+        (view.width - self.width(view)) / 2.0
+    }
+
+    fn width(&self, view: &View) -> f32 {
+        (COLUMNS as f32 * view.char_width).min(view.width)
+    }
+
+    fn height(&self, view: &View) -> f32 {
+        (1 + self.visible().len()) as f32 * view.line_height
+    }
+
+    // the query row with the status at its right, then a row per result with the matched
+    // chars highlighted and the detail at its right
+    pub fn sections<'a>(
+        &self,
+        view: &View,
+        status: &str,
+        label: impl Fn(usize) -> &'a str,
+        detail: impl Fn(usize) -> Option<String>,
+    ) -> Vec<OwnedSection> {
+        let left = self.left(view) + view.char_width;
+        let width = self.width(view) - 2.0 * view.char_width;
+        let right = |text: String, top: f32| {
+            OwnedSection::<Extra>::default()
+                .with_text(vec![OwnedText::new(text)
+                    .with_scale(view.scale)
+                    .with_color(LABEL_COLOR)])
+                .with_screen_position((left + width, top))
+                .with_bounds((width, view.line_height))
+                .with_layout(Layout::default_single_line().h_align(HorizontalAlign::Right))
+        };
+
+        let mut sections = vec![
+            OwnedSection::<Extra>::default()
+                .with_text(vec![
+                    OwnedText::new(PROMPT)
+                        .with_scale(view.scale)
+                        .with_color(LABEL_COLOR),
+                    OwnedText::new(self.query.text())
+                        .with_scale(view.scale)
+                        .with_color(TEXT_COLOR),
+                ])
+                .with_screen_position((left, 0.0))
+                .with_bounds((width, view.line_height))
+                .with_layout(Layout::default_single_line()),
+            right(status.into(), 0.0),
+        ];
+
+        for (row, (index, matched)) in self.visible().iter().enumerate() {
+            let top = (row + 1) as f32 * view.line_height;
+            sections.push(
+                OwnedSection::<Extra>::default()
+                    .with_text(highlighted(label(*index), matched, view))
+                    .with_screen_position((left, top))
+                    .with_bounds((width, view.line_height))
+                    .with_layout(Layout::default_single_line()),
+            );
+            if let Some(detail) = detail(*index) {
+                sections.push(right(detail, top));
+            }
+        }
+        // drawn above the text of the editor
+        for text in sections.iter_mut().flat_map(|section| &mut section.text) {
+            text.extra.z = OVERLAY_Z;
+        }
+        sections
+    }
+
+    pub fn rects(&self, view: &View) -> Vec<RectVertex> {
+        let (left, width) = (self.left(view), self.width(view));
+        let [r, g, b, a] = BACKGROUND_COLOR;
+        let mut rects = vec![[left, 0.0, left + width, self.height(view), r, g, b, a]];
+
+        if !self.results.is_empty() {
+            let top = (self.selected - self.scroll + 1) as f32 * view.line_height;
+            let [r, g, b, a] = SELECTED_COLOR;
+            rects.push([left, top, left + width, top + view.line_height, r, g, b, a]);
+        }
+
+        let x = left + (1 + PROMPT.len() + self.query.cursor_column()) as f32 * view.char_width;
+        let [r, g, b, a] = CARET_COLOR;
+        rects.push([x, 0.0, x + 2.0, view.line_height, r, g, b, a]);
+        rects
+    }
+}
+
+// the label in runs of matched and unmatched chars
+fn highlighted(label: &str, matched: &Match, view: &View) -> Vec<OwnedText> {
+    let mut texts: Vec<OwnedText> = Vec::new();
+    let mut run = String::new();
+    let mut run_matched = false;
+    for (i, c) in label.chars().enumerate() {
+        let is_matched = matched.positions.contains(&i);
+        if is_matched != run_matched && !run.is_empty() {
+            texts.push(run_text(std::mem::take(&mut run), run_matched, view));
+        }
+        run_matched = is_matched;
+        run.push(c);
+    }
+    if !run.is_empty() {
+        texts.push(run_text(run, run_matched, view));
+    }
+    texts
+}
+
+fn run_text(text: String, matched: bool, view: &View) -> OwnedText {
+    let color = match matched {
+        true => MATCHED_COLOR,
+        false => TEXT_COLOR,
+    };
+    OwnedText::new(text)
+        .with_scale(view.scale)
+        .with_color(color)
+}
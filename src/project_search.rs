@@ -0,0 +1,387 @@
+use std::{
+    fmt::Write,
+    fs,
+    ops::Range,
+    path::{Path, PathBuf},
+    sync::{
+        atomic::{AtomicBool, Ordering},
+        mpsc::{self, Receiver, Sender, TryRecvError},
+        Arc,
+    },
+    thread,
+};
+
+use ignore::WalkBuilder;
+use regex::Regex;
+
+use crate::{
+    buffers::Buffers, editor::Editor, encoding::Encoding, find::SearchQuery, selection::Selection,
+    text_document::TextDocument,
+};
+
+pub const RESULTS_TITLE: &str = "Search Results";
+// stop once this many lines matched, the buffer gets unwieldy past that
+const MAX_MATCHES: usize = 10_000;
+// longer lines are cut in the preview
+const MAX_PREVIEW_CHARS: usize = 200;
+// files with a NUL byte in their first bytes are taken as binary
+const BINARY_PROBE: usize = 8 * 1024;
+
+// where a line of the results buffer leads to
+#[derive(Clone, Debug)]
+pub struct Location {
+    pub path: PathBuf,
+    pub line: usize,
+    // byte range of the match in the line
+    pub range: Range<usize>,
+}
+
+struct LineMatch {
+    line: usize,
+    range: Range<usize>,
+    text: String,
+}
+
+enum Message {
+    File(PathBuf, Vec<LineMatch>),
+    // a file that could not be read
+    Skipped,
+    Done { truncated: bool },
+}
+
+// searches the files under a directory on a background thread and streams the matches
+// into the results buffer
+#[derive(Default)]
+pub struct ProjectSearch {
+    root: PathBuf,
+    // set to stop the running search
+    cancel: Arc<AtomicBool>,
+    receiver: Option<Receiver<Message>>,
+    // one per line of the results buffer, None for lines leading nowhere
+    locations: Vec<Option<Location>>,
+    files: usize,
+    matches: usize,
+    skipped: usize,
+}
+
+impl ProjectSearch {
+    pub fn is_running(&self) -> bool {
+        self.receiver.is_some()
+    }
+
+    // start searching, cancelling the previous search, and show the results buffer
+    pub fn start(
+        &mut self,
+        query: &SearchQuery,
+        root: PathBuf,
+        editor: &mut Editor,
+        buffers: &mut Buffers,
+    ) {
+        let regex = match query.compile() {
+            Ok(regex) => regex,
+            Err(e) => {
+                editor.set_message(e.to_string());
+                return;
+            }
+        };
+        self.cancel.store(true, Ordering::Relaxed);
+        self.cancel = Arc::default();
+
+        let header = format!("Results for \"{}\" in {}\n", query.pattern, root.display());
+        let mut results = Editor::new(
+            TextDocument::from_text(&header).with_title(RESULTS_TITLE),
+            editor.indentation,
+        );
+        results.read_only = true;
+        results.set_message("Searching…");
+        buffers.show_titled(editor, results);
+
+        let (sender, receiver) = mpsc::channel();
+        let cancel = self.cancel.clone();
+        let walk_root = root.clone();
+        thread::spawn(move || search(&walk_root, &regex, &cancel, &sender));
+
+        self.root = root;
+        self.receiver = Some(receiver);
+        self.locations = vec![None];
+        self.files = 0;
+        self.matches = 0;
+        self.skipped = 0;
+    }
+
+    pub fn cancel(&mut self, editor: &mut Editor) -> bool {
+        if self.receiver.take().is_none() {
+            return false;
+        }
+        self.cancel.store(true, Ordering::Relaxed);
+        editor.set_message(format!("Search cancelled, {}", self.summary()));
+        true
+    }
+
+    fn summary(&self) -> String {
+        let matches = match self.matches {
+            1 => "match",
+            _ => "matches",
+        };
+        let files = match self.files {
+            1 => "file",
+            _ => "files",
+        };
+        let summary = format!("{} {matches} in {} {files}", self.matches, self.files);
+        match self.skipped {
+            0 => summary,
+            1 => format!("{summary}, 1 file could not be read"),
+            skipped => format!("{summary}, {skipped} files could not be read"),
+        }
+    }
+
+    // append what the search found since the last call to the results buffer
+    pub fn poll(&mut self, editor: &mut Editor, buffers: &mut Buffers) {
+        let Some(receiver) = &self.receiver else {
+            return;
+        };
+        let Some(results) = buffers.titled_mut(editor, RESULTS_TITLE) else {
+            return;
+        };
+
+        let mut text = String::new();
+        let mut done = None;
+        loop {
+            match receiver.try_recv() {
+                Ok(Message::File(path, matches)) => {
+                    let shown = path.strip_prefix(&self.root).unwrap_or(&path);
+                    let _ = write!(text, "\n{}\n", shown.display());
+                    self.locations.push(None);
+                    self.locations.push(Some(Location {
+                        path: path.clone(),
+                        line: 0,
+                        range: 0..0,
+                    }));
+                    for LineMatch {
+                        line,
+                        range,
+                        text: preview,
+                    } in matches
+                    {
+                        let _ = writeln!(text, "  {:>5}: {preview}", line + 1);
+                        self.locations.push(Some(Location {
+                            path: path.clone(),
+                            line,
+                            range,
+                        }));
+                        self.matches += 1;
+                    }
+                    self.files += 1;
+                }
+                Ok(Message::Skipped) => self.skipped += 1,
+                Ok(Message::Done { truncated }) => {
+                    done = Some(truncated);
+                    break;
+                }
+                Err(TryRecvError::Empty) => break,
+                Err(TryRecvError::Disconnected) => {
+                    done = Some(false);
+                    break;
+                }
+            }
+        }
+
+        if !text.is_empty() {
+            results.document.append(&text);
+        }
+        if let Some(truncated) = done {
+            let summary = self.summary();
+            results.set_message(match truncated {
+                true => format!("{summary}, stopped after {MAX_MATCHES} matches"),
+                false => summary,
+            });
+            self.receiver = None;
+        }
+    }
+
+    // where the caret line of the results buffer leads, None in other buffers
+    pub fn location(&self, editor: &Editor) -> Option<&Location> {
+        if editor.document.title() != Some(RESULTS_TITLE) {
+            return None;
+        }
+        self.locations.get(editor.caret_line())?.as_ref()
+    }
+
+    // open the file of the result under the caret and select the match, false if the caret
+    // is not on a result
+    pub fn activate(&self, editor: &mut Editor, buffers: &mut Buffers) -> bool {
+        let Some(location) = self.location(editor).cloned() else {
+            return false;
+        };
+        if let Err(e) = buffers.open(editor, &location.path) {
+            editor.set_message(format!("{}: {e}", location.path.display()));
+            return true;
+        }
+
+        // the file may have changed since it was searched
+        let document = &editor.document;
+        let line = location.line.min(document.line_count() - 1);
+        let start = document.line_start(line);
+        let end = document.line_end(line);
+        let offset = |column: usize| {
+            let offset = (start + column).min(end);
+            match document.text().is_char_boundary(offset) {
+                true => offset,
+                false => start,
+            }
+        };
+        let selection = Selection::new(offset(location.range.start), offset(location.range.end));
+        editor.select(selection);
+        true
+    }
+}
+
+impl Drop for ProjectSearch {
+    fn drop(&mut self) {
+        self.cancel.store(true, Ordering::Relaxed);
+    }
+}
+
+// runs on the search thread until done, cancelled or nobody listens anymore
+fn search(root: &Path, regex: &Regex, cancel: &AtomicBool, sender: &Sender<Message>) {
+    let mut budget = MAX_MATCHES;
+    // .gitignore files apply even outside of a git repository
+    for entry in WalkBuilder::new(root).require_git(false).build() {
+        if cancel.load(Ordering::Relaxed) {
+            return;
+        }
+        let Ok(entry) = entry else {
+            continue;
+        };
+        if !entry.file_type().is_some_and(|kind| kind.is_file()) {
+            continue;
+        }
+
+        let Some(mut matches) = search_file(entry.path(), regex) else {
+            if sender.send(Message::Skipped).is_err() {
+                return;
+            }
+            continue;
+        };
+        if matches.is_empty() {
+            continue;
+        }
+        let truncated = matches.len() >= budget;
+        matches.truncate(budget);
+        budget -= matches.len();
+        if sender
+            .send(Message::File(entry.into_path(), matches))
+            .is_err()
+        {
+            return;
+        }
+        if truncated {
+            let _ = sender.send(Message::Done { truncated });
+            return;
+        }
+    }
+    let _ = sender.send(Message::Done { truncated: false });
+}
+
+// first match on every line, none in binary files and no list for unreadable ones; text
+// that isn't UTF-8 is decoded as opening the file would, so the ranges fit its buffer
+fn search_file(path: &Path, regex: &Regex) -> Option<Vec<LineMatch>> {
+    let bytes = fs::read(path).ok()?;
+    if bytes[..bytes.len().min(BINARY_PROBE)].contains(&0) {
+        return Some(Vec::new());
+    }
+    let text = match String::from_utf8(bytes) {
+        Ok(text) => text,
+        Err(e) => {
+            let bytes = e.into_bytes();
+            Encoding::detect(&bytes).decode(&bytes)
+        }
+    };
+
+    let matches = text
+        .split('\n')
+        .enumerate()
+        .filter_map(|(line, content)| {
+            let content = content.strip_suffix('\r').unwrap_or(content);
+            let found = regex.find_iter(content).find(|found| !found.is_empty())?;
+            Some(LineMatch {
+                line,
+                range: found.range(),
+                text: preview(content),
+            })
+        })
+        .collect();
+    Some(matches)
+}
+
+fn preview(line: &str) -> String {
+    let line = line.trim_end();
+    match line.char_indices().nth(MAX_PREVIEW_CHARS) {
+        Some((cut, _)) => format!("{}…", &line[..cut]),
+        None => line.into(),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::time::Duration;
+
+    use crate::editor::Indentation;
+
+    #[test]
+    fn result_lines_lead_to_their_matches() {
+        let root = std::env::temp_dir().join(format!("text_editor-search-{}", std::process::id()));
+        fs::create_dir_all(root.join("sub")).unwrap();
+        let files: [(&str, &[u8]); 4] = [
+            ("a.txt", b"one\nfoo two\r\nthree foo\n"),
+            ("sub/b.txt", b"foo\n"),
+            ("latin.txt", b"caf\xe9 d\xe9j\xe0 foo\n"),
+            ("none.txt", b"nothing here\n"),
+        ];
+        for (name, bytes) in files {
+            fs::write(root.join(name), bytes).unwrap();
+        }
+
+        let query = SearchQuery {
+            pattern: "foo".into(),
+            ..SearchQuery::default()
+        };
+        let mut editor = Editor::new(TextDocument::from_text(""), Indentation::default());
+        let mut buffers = Buffers::new(Indentation::default());
+        let mut search = ProjectSearch::default();
+        search.start(&query, root.clone(), &mut editor, &mut buffers);
+        while search.is_running() {
+            thread::sleep(Duration::from_millis(5));
+            search.poll(&mut editor, &mut buffers);
+        }
+        assert_eq!(search.summary(), "4 matches in 3 files");
+
+        let lines: Vec<String> = editor.document.text().lines().map(String::from).collect();
+        assert_eq!(lines.len(), search.locations.len());
+        for (line, text) in lines.iter().enumerate() {
+            editor.select(Selection::caret(editor.document.line_start(line)));
+            let location = search.location(&editor);
+            match text.trim_start().split_once(": ") {
+                // a match: its line in the file has the match at the range
+                Some((number, _)) if text.starts_with("  ") => {
+                    let location = location.unwrap();
+                    assert_eq!(location.line + 1, number.parse::<usize>().unwrap());
+                    let bytes = fs::read(&location.path).unwrap();
+                    let file = Encoding::detect(&bytes).decode(&bytes);
+                    let file_line = file.split('\n').nth(location.line).unwrap();
+                    assert_eq!(&file_line[location.range.clone()], "foo");
+                }
+                // the header and the blank lines before files lead nowhere
+                _ if line == 0 || text.is_empty() => assert!(location.is_none(), "{text}"),
+                // a file name leads to its start
+                _ => {
+                    let location = location.unwrap();
+                    assert_eq!(location.path, root.join(text));
+                    assert_eq!(location.line, 0);
+                }
+            }
+        }
+        fs::remove_dir_all(&root).unwrap();
+    }
+}
@@ -0,0 +1,202 @@
+use std::ops::Range;
+
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub struct Selection {
+    pub anchor: usize,
+    pub head: usize,
+    // column the head tries to return to on vertical movement
+    pub column: Option<usize>,
+}
+
+impl Selection {
+    pub fn new(anchor: usize, head: usize) -> Self {
+        Self {
+            anchor,
+            head,
+            column: None,
+        }
+    }
+
+    pub fn caret(offset: usize) -> Self {
+        Self::new(offset, offset)
+    }
+
+    // select from the anchor unit to the head unit (chars, words or lines),
+    // keeping the whole anchor unit selected whichever way the head moves
+    pub fn between(anchor: Range<usize>, head: Range<usize>) -> Self {
+        match head.start < anchor.start {
+            true => Self::new(anchor.end, head.start),
+            false => Self::new(anchor.start, head.end),
+        }
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.anchor == self.head
+    }
+
+    pub fn start(&self) -> usize {
+        self.anchor.min(self.head)
+    }
+
+    pub fn end(&self) -> usize {
+        self.anchor.max(self.head)
+    }
+
+    pub fn range(&self) -> Range<usize> {
+        self.start()..self.end()
+    }
+
+    fn is_backward(&self) -> bool {
+        self.head < self.anchor
+    }
+}
+
+// non-overlapping selections sorted by position, one of them is the primary selection
+// that the viewport follows
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct Selections {
+    selections: Vec<Selection>,
+    primary: usize,
+}
+
+impl Default for Selections {
+    fn default() -> Self {
+        Self::single(Selection::default())
+    }
+}
+
+impl Selections {
+    pub fn single(selection: Selection) -> Self {
+        Self {
+            selections: vec![selection],
+            primary: 0,
+        }
+    }
+
+    pub fn new(selections: Vec<Selection>, primary: usize) -> Self {
+        assert!(primary < selections.len(), "primary selection out of range");
+        let mut selections = Self {
+            selections,
+            primary,
+        };
+        selections.normalize();
+        selections
+    }
+
+    pub fn primary(&self) -> Selection {
+        self.selections[self.primary]
+    }
+
+    pub fn primary_index(&self) -> usize {
+        self.primary
+    }
+
+    pub fn len(&self) -> usize {
+        self.selections.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.selections.is_empty()
+    }
+
+    pub fn iter(&self) -> impl DoubleEndedIterator<Item = &Selection> + '_ {
+        self.selections.iter()
+    }
+
+    // add a selection and make it the primary one
+    pub fn push(&mut self, selection: Selection) {
+        self.selections.push(selection);
+        self.primary = self.selections.len() - 1;
+        self.normalize();
+    }
+
+    pub fn replace_primary(&mut self, selection: Selection) {
+        self.selections[self.primary] = selection;
+        self.normalize();
+    }
+
+    pub fn map(&self, f: impl FnMut(&Selection) -> Selection) -> Self {
+        Self::new(self.selections.iter().map(f).collect(), self.primary)
+    }
+
+    // sort by start and merge selections that overlap, or carets that touch a selection
+    fn normalize(&mut self) {
+        let mut indexed: Vec<_> = self.selections.drain(..).enumerate().collect();
+        indexed.sort_by_key(|(_, selection)| (selection.start(), selection.end()));
+
+        let mut merged: Vec<(bool, Selection)> = Vec::with_capacity(indexed.len());
+        for (index, selection) in indexed {
+            let is_primary = index == self.primary;
+            if let Some((last_primary, last)) = merged.last_mut() {
+                let overlaps = selection.start() < last.end()
+                    || (selection.start() == last.end()
+                        && (selection.is_empty() || last.is_empty()));
+                if overlaps {
+                    let (start, end) = (last.start(), last.end().max(selection.end()));
+                    let backward = match is_primary {
+                        true => selection.is_backward(),
+                        false => last.is_backward(),
+                    };
+                    *last = match backward {
+                        true => Selection::new(end, start),
+                        false => Selection::new(start, end),
+                    };
+                    *last_primary |= is_primary;
+                    continue;
+                }
+            }
+            merged.push((is_primary, selection));
+        }
+
+        self.primary = merged
+            .iter()
+            .position(|(is_primary, _)| *is_primary)
+            .unwrap_or(0);
+        self.selections = merged.into_iter().map(|(_, selection)| selection).collect();
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn normalize_sorts_and_merges_overlaps() {
+        let selections = Selections::new(
+            vec![
+                Selection::new(10, 14),
+                Selection::new(0, 3),
+                Selection::new(12, 20),
+            ],
+            0,
+        );
+        let ranges: Vec<_> = selections.iter().map(Selection::range).collect();
+        assert_eq!(ranges, vec![0..3, 10..20]);
+        assert_eq!(selections.primary_index(), 1);
+    }
+
+    #[test]
+    fn normalize_merges_carets_touching_a_selection() {
+        let selections = Selections::new(
+            vec![
+                Selection::new(2, 5),
+                Selection::caret(5),
+                Selection::caret(8),
+            ],
+            2,
+        );
+        assert_eq!(selections.len(), 2);
+        assert_eq!(selections.primary(), Selection::caret(8));
+
+        // adjacent selections that both have text stay apart
+        let selections = Selections::new(vec![Selection::new(0, 2), Selection::new(2, 4)], 0);
+        assert_eq!(selections.len(), 2);
+    }
+
+    #[test]
+    fn merged_selection_keeps_the_primary_direction() {
+        let selections = Selections::new(vec![Selection::new(0, 4), Selection::new(6, 2)], 1);
+        assert_eq!(selections.len(), 1);
+        assert_eq!(selections.primary(), Selection::new(6, 0));
+    }
+}
@@ -0,0 +1,60 @@
+use std::{collections::BTreeMap, fs, io, path::PathBuf};
+
+use serde::{Deserialize, Serialize};
+
+use crate::{macros::Step, marks::SavedMarks, utils::Res};
+
+// how many recently run commands the palette remembers
+const MAX_RECENT_COMMANDS: usize = 20;
+
+// state kept between runs in <data dir>/text_editor/session.toml, unlike the config it is
+// written by the editor itself
+#[derive(Debug, Default, Deserialize, Serialize)]
+#[serde(default)]
+pub struct Session {
+    // command ids, most recent first
+    pub recent_commands: Vec<String>,
+    // keyboard macros by name
+    pub macros: BTreeMap<String, Vec<Step>>,
+    // folded (header, last) lines by file path
+    pub folds: BTreeMap<String, Vec<[usize; 2]>>,
+    // bookmarks and named marks by file path
+    pub marks: BTreeMap<String, SavedMarks>,
+}
+
+impl Session {
+    pub fn path() -> Option<PathBuf> {
+        dirs::data_dir().map(|dir| dir.join("text_editor").join("session.toml"))
+    }
+
+    // a missing session file is not an error, a malformed one is
+    pub fn load() -> Res<Self> {
+        let Some(path) = Self::path() else {
+            return Ok(Self::default());
+        };
+        match fs::read_to_string(&path) {
+            Ok(text) => {
+                toml::from_str(&text).map_err(|e| format!("{}: {e}", path.display()).into())
+            }
+            Err(e) if e.kind() == io::ErrorKind::NotFound => Ok(Self::default()),
+            Err(e) => Err(e.into()),
+        }
+    }
+
+    pub fn save(&self) -> Res<()> {
+        let Some(path) = Self::path() else {
+            return Ok(());
+        };
+        if let Some(dir) = path.parent() {
+            fs::create_dir_all(dir)?;
+        }
+        fs::write(&path, toml::to_string(self)?)?;
+        Ok(())
+    }
+
+    pub fn command_used(&mut self, id: &str) {
+        self.recent_commands.retain(|recent| recent != id);
+        self.recent_commands.insert(0, id.into());
+        self.recent_commands.truncate(MAX_RECENT_COMMANDS);
+    }
+}
@@ -0,0 +1,730 @@
+use std::{
+    cell::{Cell, OnceCell},
+    collections::{hash_map::RandomState, BTreeMap},
+    fs,
+    hash::BuildHasher,
+    io,
+    ops::Range,
+    path::PathBuf,
+    time::{SystemTime, UNIX_EPOCH},
+};
+
+use regex::Regex;
+use serde::Deserialize;
+
+use crate::{
+    editor::Editor,
+    history::{Change, EditKind},
+    indent,
+    language::Language,
+    selection::{Selection, Selections},
+    utils::Res,
+};
+
+#[derive(Deserialize)]
+#[serde(untagged)]
+enum OneOrMany {
+    One(String),
+    Many(Vec<String>),
+}
+
+impl OneOrMany {
+    fn into_vec(self) -> Vec<String> {
+        match self {
+            Self::One(one) => vec![one],
+            Self::Many(many) => many,
+        }
+    }
+}
+
+// an entry of a snippet file, as VS Code writes them
+#[derive(Deserialize)]
+struct SnippetEntry {
+    prefix: OneOrMany,
+    // lines of the body when given as a list
+    body: OneOrMany,
+    #[serde(default)]
+    description: Option<String>,
+}
+
+#[derive(Clone, Debug)]
+pub struct Snippet {
+    pub name: String,
+    pub prefixes: Vec<String>,
+    pub body: String,
+    pub description: Option<String>,
+}
+
+// snippets read from <config dir>/text_editor/snippets: <language>.json for a language,
+// named by its lower case name with ++ as pp and spaces as _, and global.json for all
+#[derive(Debug, Default)]
+pub struct Snippets {
+    by_language: BTreeMap<String, Vec<Snippet>>,
+    // files that could not be read, the others still load
+    problems: Vec<String>,
+}
+
+fn file_stem(language: &Language) -> String {
+    language
+        .name
+        .to_lowercase()
+        .replace("++", "pp")
+        .replace(' ', "_")
+}
+
+const GLOBAL: &str = "global";
+
+impl Snippets {
+    pub fn dir() -> Option<PathBuf> {
+        dirs::config_dir().map(|dir| dir.join("text_editor").join("snippets"))
+    }
+
+    // a missing directory means no snippets
+    pub fn load() -> Self {
+        let mut snippets = Self::default();
+        let Some(dir) = Self::dir() else {
+            return snippets;
+        };
+        let entries = match fs::read_dir(&dir) {
+            Ok(entries) => entries,
+            Err(e) if e.kind() == io::ErrorKind::NotFound => return snippets,
+            Err(e) => {
+                snippets.problems.push(format!("{}: {e}", dir.display()));
+                return snippets;
+            }
+        };
+        for path in entries.filter_map(|entry| entry.ok().map(|entry| entry.path())) {
+            let Some(stem) = path
+                .file_stem()
+                .filter(|_| path.extension().is_some_and(|ext| ext == "json"))
+                .and_then(|stem| stem.to_str())
+            else {
+                continue;
+            };
+            match Self::read(&path) {
+                Ok(file) => {
+                    snippets.by_language.insert(stem.to_string(), file);
+                }
+                Err(e) => snippets.problems.push(format!("{}: {e}", path.display())),
+            }
+        }
+        snippets
+    }
+
+    fn read(path: &PathBuf) -> Res<Vec<Snippet>> {
+        let text = fs::read_to_string(path)?;
+        let entries: BTreeMap<String, SnippetEntry> = serde_json::from_str(&text)?;
+        Ok(entries
+            .into_iter()
+            .map(|(name, entry)| Snippet {
+                name,
+                prefixes: entry.prefix.into_vec(),
+                body: entry.body.into_vec().join("\n"),
+                description: entry.description,
+            })
+            .collect())
+    }
+
+    pub fn problems(&self) -> &[String] {
+        &self.problems
+    }
+
+    pub fn for_language<'a>(&'a self, language: &Language) -> impl Iterator<Item = &'a Snippet> {
+        [file_stem(language), GLOBAL.to_string()]
+            .into_iter()
+            .filter_map(|stem| self.by_language.get(&stem))
+            .flatten()
+    }
+
+    // the snippet whose prefix the text before the caret ends with, as a whole word, and
+    // the length of that prefix; the longest prefix wins
+    pub fn at_caret(&self, language: &Language, before: &str) -> Option<(&Snippet, usize)> {
+        self.for_language(language)
+            .flat_map(|snippet| snippet.prefixes.iter().map(move |prefix| (snippet, prefix)))
+            .filter(|(_, prefix)| !prefix.is_empty() && before.ends_with(prefix.as_str()))
+            .filter(|(_, prefix)| {
+                let starts_word = prefix.starts_with(is_word_char);
+                let rest = &before[..before.len() - prefix.len()];
+                !starts_word || !rest.ends_with(is_word_char)
+            })
+            .max_by_key(|(_, prefix)| prefix.len())
+            .map(|(snippet, prefix)| (snippet, prefix.len()))
+    }
+}
+
+fn is_word_char(c: char) -> bool {
+    c.is_alphanumeric() || c == '_'
+}
+
+// the body with its variables resolved and the fields it has, by number, in offsets of
+// the text
+#[derive(Debug, Default, PartialEq, Eq)]
+pub struct Expansion {
+    pub text: String,
+    pub fields: Vec<(usize, Range<usize>)>,
+}
+
+struct Parser<'a> {
+    chars: Vec<char>,
+    position: usize,
+    expansion: Expansion,
+    // text put in front of every line after the first, and what a tab becomes
+    line_indent: &'a str,
+    tab: &'a str,
+    // the default of each field, for the occurrences that give none
+    defaults: &'a BTreeMap<usize, String>,
+    variable: &'a dyn Fn(&str) -> Option<String>,
+}
+
+impl Parser<'_> {
+    fn peek(&self) -> Option<char> {
+        self.chars.get(self.position).copied()
+    }
+
+    fn eat(&mut self, c: char) -> bool {
+        let found = self.peek() == Some(c);
+        if found {
+            self.position += 1;
+        }
+        found
+    }
+
+    fn push(&mut self, text: &str) {
+        for c in text.chars() {
+            match c {
+                '\n' => {
+                    self.expansion.text.push('\n');
+                    self.expansion.text.push_str(self.line_indent);
+                }
+                '\t' => self.expansion.text.push_str(self.tab),
+                c => self.expansion.text.push(c),
+            }
+        }
+    }
+
+    fn number(&mut self) -> Option<usize> {
+        let start = self.position;
+        while self.peek().is_some_and(|c| c.is_ascii_digit()) {
+            self.position += 1;
+        }
+        let digits: String = self.chars[start..self.position].iter().collect();
+        digits.parse().ok()
+    }
+
+    fn name(&mut self) -> Option<String> {
+        let start = self.position;
+        if !self
+            .peek()
+            .is_some_and(|c| c == '_' || c.is_ascii_alphabetic())
+        {
+            return None;
+        }
+        while self
+            .peek()
+            .is_some_and(|c| c == '_' || c.is_ascii_alphanumeric())
+        {
+            self.position += 1;
+        }
+        Some(self.chars[start..self.position].iter().collect())
+    }
+
+    // text up to the unescaped character, which is consumed; escapes of the characters in
+    // `escapable` are taken literally
+    fn until(&mut self, end: char, escapable: &[char]) -> Option<String> {
+        let mut text = String::new();
+        loop {
+            let c = self.peek()?;
+            self.position += 1;
+            match c {
+                '\\' if self.peek().is_some_and(|next| escapable.contains(&next)) => {
+                    text.push(self.peek()?);
+                    self.position += 1;
+                }
+                c if c == end => return Some(text),
+                c => text.push(c),
+            }
+        }
+    }
+
+    // text and fields up to the closing brace of a placeholder, or to the end of the body
+    fn body(&mut self, nested: bool) -> bool {
+        while let Some(c) = self.peek() {
+            match c {
+                '}' if nested => {
+                    self.position += 1;
+                    return true;
+                }
+                '\\' => {
+                    self.position += 1;
+                    match self.peek() {
+                        Some(next @ ('$' | '}' | '\\')) => {
+                            self.position += 1;
+                            self.push(&next.to_string());
+                        }
+                        _ => self.push("\\"),
+                    }
+                }
+                '$' => {
+                    let start = self.position;
+                    self.position += 1;
+                    if !self.dollar() {
+                        // not a field or variable after all, the dollar stays as written
+                        self.position = start + 1;
+                        self.push("$");
+                    }
+                }
+                c => {
+                    self.position += 1;
+                    self.push(&c.to_string());
+                }
+            }
+        }
+        !nested
+    }
+
+    fn field(&mut self, number: usize, start: usize) {
+        if start == self.expansion.text.len() {
+            if let Some(default) = self.defaults.get(&number) {
+                self.expansion.text.push_str(default);
+            }
+        }
+        let end = self.expansion.text.len();
+        self.expansion.fields.push((number, start..end));
+    }
+
+    // what follows a dollar sign, false if it is none of the forms
+    fn dollar(&mut self) -> bool {
+        let start = self.expansion.text.len();
+        if let Some(number) = self.number() {
+            self.field(number, start);
+            return true;
+        }
+        if let Some(name) = self.name() {
+            let value = (self.variable)(&name).unwrap_or(name);
+            self.push(&value);
+            return true;
+        }
+        if !self.eat('{') {
+            return false;
+        }
+        if let Some(number) = self.number() {
+            if self.eat('}') {
+                self.field(number, start);
+                return true;
+            }
+            if self.eat(':') {
+                let fields = self.expansion.fields.len();
+                if !self.body(true) {
+                    return false;
+                }
+                // fields inside a placeholder come before it in the list, it goes first
+                self.expansion.fields.insert(fields, (number, start..start));
+                self.expansion.fields[fields].1.end = self.expansion.text.len();
+                if self.expansion.fields[fields].1.is_empty() {
+                    self.expansion.fields.remove(fields);
+                    self.field(number, start);
+                }
+                return true;
+            }
+            if self.eat('|') {
+                // a choice puts in its first option
+                let Some(options) = self.until('|', &['$', '}', '\\', ',', '|']) else {
+                    return false;
+                };
+                if !self.eat('}') {
+                    return false;
+                }
+                let first = options.split(',').next().unwrap_or_default().to_string();
+                self.push(&first);
+                self.field(number, start);
+                return true;
+            }
+            return false;
+        }
+        let Some(name) = self.name() else {
+            return false;
+        };
+        let value = (self.variable)(&name);
+        if self.eat('}') {
+            let value = value.unwrap_or(name);
+            self.push(&value);
+            return true;
+        }
+        if self.eat(':') {
+            return match value {
+                Some(value) => {
+                    // the default is skipped over, fields in it included
+                    let fields = self.expansion.fields.len();
+                    let text = self.expansion.text.len();
+                    if !self.body(true) {
+                        return false;
+                    }
+                    self.expansion.fields.truncate(fields);
+                    self.expansion.text.truncate(text);
+                    self.push(&value);
+                    true
+                }
+                None => self.body(true),
+            };
+        }
+        if self.eat('/') {
+            let escapable = ['/', '\\', '$', '}'];
+            let Some(pattern) = self.until('/', &escapable) else {
+                return false;
+            };
+            let Some(format) = self.until('/', &escapable) else {
+                return false;
+            };
+            let Some(options) = self.until('}', &[]) else {
+                return false;
+            };
+            let value = value.unwrap_or_default();
+            let value = match Regex::new(&pattern) {
+                Ok(regex) if options.contains('g') => {
+                    regex.replace_all(&value, format.as_str()).into_owned()
+                }
+                Ok(regex) => regex.replace(&value, format.as_str()).into_owned(),
+                Err(_) => value,
+            };
+            self.push(&value);
+            return true;
+        }
+        false
+    }
+}
+
+// expand the body of a snippet in the TextMate syntax: $1 and ${1:default} fields, choices
+// ${1|a,b|} taking the first option, $NAME and ${NAME:default} variables and their
+// ${NAME/regex/format/options} transforms; the lines after the first are indented like
+// the line it goes into and tabs become a level of indentation
+pub fn expand(
+    body: &str,
+    line_indent: &str,
+    tab: &str,
+    variable: &dyn Fn(&str) -> Option<String>,
+) -> Expansion {
+    let parse = |defaults: &BTreeMap<usize, String>| {
+        let mut parser = Parser {
+            chars: body.chars().collect(),
+            position: 0,
+            expansion: Expansion::default(),
+            line_indent,
+            tab,
+            defaults,
+            variable,
+        };
+        parser.body(false);
+        parser.expansion
+    };
+    let first = parse(&BTreeMap::new());
+    // a field written without a default mirrors the default given in another place
+    let mut defaults = BTreeMap::new();
+    for (number, range) in &first.fields {
+        if !range.is_empty() {
+            defaults
+                .entry(*number)
+                .or_insert_with(|| first.text[range.clone()].to_string());
+        }
+    }
+    match defaults.is_empty() {
+        true => first,
+        false => parse(&defaults),
+    }
+}
+
+const MONTHS: [&str; 12] = [
+    "January",
+    "February",
+    "March",
+    "April",
+    "May",
+    "June",
+    "July",
+    "August",
+    "September",
+    "October",
+    "November",
+    "December",
+];
+
+const DAYS: [&str; 7] = [
+    "Thursday",
+    "Friday",
+    "Saturday",
+    "Sunday",
+    "Monday",
+    "Tuesday",
+    "Wednesday",
+];
+
+// year, month and day of the days since 1970-01-01
+fn civil_date(days: i64) -> (i64, usize, usize) {
+    let days = days + 719_468;
+    let era = days.div_euclid(146_097);
+    let day_of_era = days.rem_euclid(146_097);
+    let year_of_era =
+        (day_of_era - day_of_era / 1460 + day_of_era / 36_524 - day_of_era / 146_096) / 365;
+    let day_of_year = day_of_era - (365 * year_of_era + year_of_era / 4 - year_of_era / 100);
+    let month_index = (5 * day_of_year + 2) / 153;
+    let day = day_of_year - (153 * month_index + 2) / 5 + 1;
+    let month = if month_index < 10 {
+        month_index + 3
+    } else {
+        month_index - 9
+    };
+    let year = year_of_era + era * 400 + i64::from(month <= 2);
+    (year, month as usize, day as usize)
+}
+
+// the value of a snippet variable at the caret; times are in UTC
+pub fn variable(name: &str, editor: &Editor, clipboard: &dyn Fn() -> String) -> Option<String> {
+    let document = &editor.document;
+    let path = document.file_name();
+    let line = editor.caret_line();
+    let seconds = SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .map_or(0, |time| time.as_secs() as i64);
+    let days = seconds.div_euclid(86_400);
+    let (year, month, day) = civil_date(days);
+    let time = seconds.rem_euclid(86_400);
+    let random = || RandomState::new().hash_one(seconds);
+    let comment = editor.language.block_comment;
+    Some(match name {
+        "TM_SELECTED_TEXT" => editor.selected_text(&editor.primary()).to_string(),
+        "TM_CURRENT_LINE" => document.line(line).to_string(),
+        "TM_CURRENT_WORD" => {
+            let word = editor.word_range(editor.caret());
+            document.text()[word].to_string()
+        }
+        "TM_LINE_INDEX" => line.to_string(),
+        "TM_LINE_NUMBER" => (line + 1).to_string(),
+        "TM_FILENAME" => path?.file_name()?.to_string_lossy().into_owned(),
+        "TM_FILENAME_BASE" => path?.file_stem()?.to_string_lossy().into_owned(),
+        "TM_DIRECTORY" => path?.parent()?.to_string_lossy().into_owned(),
+        "TM_FILEPATH" => path?.to_string_lossy().into_owned(),
+        "CLIPBOARD" => clipboard(),
+        "CURRENT_YEAR" => year.to_string(),
+        "CURRENT_YEAR_SHORT" => format!("{:02}", year % 100),
+        "CURRENT_MONTH" => format!("{month:02}"),
+        "CURRENT_MONTH_NAME" => MONTHS[month - 1].to_string(),
+        "CURRENT_MONTH_NAME_SHORT" => MONTHS[month - 1][..3].to_string(),
+        "CURRENT_DATE" => format!("{day:02}"),
+        "CURRENT_DAY_NAME" => DAYS[days.rem_euclid(7) as usize].to_string(),
+        "CURRENT_DAY_NAME_SHORT" => DAYS[days.rem_euclid(7) as usize][..3].to_string(),
+        "CURRENT_HOUR" => format!("{:02}", time / 3600),
+        "CURRENT_MINUTE" => format!("{:02}", time % 3600 / 60),
+        "CURRENT_SECOND" => format!("{:02}", time % 60),
+        "CURRENT_SECONDS_UNIX" => seconds.to_string(),
+        "RANDOM" => format!("{:06}", random() % 1_000_000),
+        "RANDOM_HEX" => format!("{:06x}", random() & 0xff_ffff),
+        "LINE_COMMENT" => editor.language.line_comment?.to_string(),
+        "BLOCK_COMMENT_START" => comment?.0.to_string(),
+        "BLOCK_COMMENT_END" => comment?.1.to_string(),
+        _ => return None,
+    })
+}
+
+// the fields of an expanded snippet while Tab moves through them
+#[derive(Clone, Debug)]
+pub struct SnippetSession {
+    // ranges of each field in the order Tab visits them, the final caret last; the ranges
+    // of a field mirror each other
+    fields: Vec<Vec<Range<usize>>>,
+    current: usize,
+}
+
+impl SnippetSession {
+    pub fn current(&self) -> &[Range<usize>] {
+        &self.fields[self.current]
+    }
+
+    fn is_last(&self) -> bool {
+        self.current + 1 == self.fields.len()
+    }
+
+    // whether every selection is in a range of the current field
+    pub fn holds(&self, selections: &Selections) -> bool {
+        selections.iter().all(|selection| {
+            self.current()
+                .iter()
+                .any(|range| range.start <= selection.start() && selection.end() <= range.end)
+        })
+    }
+
+    // follow a change to the text, false when it cut into a field; the current field grows
+    // with text typed at its edges
+    pub fn update(&mut self, change: &Change) -> bool {
+        let end = change.offset + change.deleted.len();
+        let inserted = change.inserted.len();
+        let delta = inserted as isize - change.deleted.len() as isize;
+        for (index, field) in self.fields.iter_mut().enumerate() {
+            let current = index == self.current;
+            for range in field.iter_mut() {
+                let inside = match current {
+                    true => range.start <= change.offset && end <= range.end,
+                    false => range.start < change.offset && end < range.end,
+                };
+                if inside {
+                    range.end = range.end.saturating_add_signed(delta);
+                } else if end <= range.start {
+                    range.start = range.start.saturating_add_signed(delta);
+                    range.end = range.end.saturating_add_signed(delta);
+                } else if change.offset < range.end {
+                    // a field replaced as a whole, such as one nested in a placeholder,
+                    // is left empty after the new text
+                    if range.start < change.offset || end < range.end {
+                        return false;
+                    }
+                    *range = change.offset + inserted..change.offset + inserted;
+                }
+            }
+        }
+        true
+    }
+
+    fn selections(&self) -> Selections {
+        let selections = self
+            .current()
+            .iter()
+            .map(|range| Selection::new(range.start, range.end))
+            .collect();
+        Selections::new(selections, 0)
+    }
+}
+
+// put the snippet in place of the prefix before the caret and select its first field
+pub fn insert(editor: &mut Editor, expansion: Expansion, replace: Range<usize>) {
+    let mut numbers: Vec<usize> = expansion.fields.iter().map(|(number, _)| *number).collect();
+    numbers.sort_unstable();
+    numbers.dedup();
+    // $0 is where the caret ends up, after the other fields
+    if numbers.first() == Some(&0) {
+        numbers.rotate_left(1);
+    }
+    let start = replace.start;
+    let length = expansion.text.len();
+    let mut fields: Vec<Vec<Range<usize>>> = numbers
+        .iter()
+        .map(|number| {
+            expansion
+                .fields
+                .iter()
+                .filter(|(field, _)| field == number)
+                .map(|(_, range)| start + range.start..start + range.end)
+                .collect()
+        })
+        .collect();
+    if !numbers.contains(&0) {
+        let end = start + length..start + length;
+        fields.push(vec![end]);
+    }
+    let session = SnippetSession { fields, current: 0 };
+    let first = session.selections();
+    editor.edit(EditKind::Insert, vec![(replace, expansion.text)], |_| {
+        first.iter().copied().collect()
+    });
+    if !session.is_last() {
+        editor.snippet = Some(session);
+    }
+}
+
+// select the next field, the final caret ends the snippet; false without one
+pub fn next_field(editor: &mut Editor) -> bool {
+    let Some(mut session) = editor.snippet.take() else {
+        return false;
+    };
+    session.current += 1;
+    let selections = session.selections();
+    if !session.is_last() {
+        editor.snippet = Some(session);
+    }
+    editor.set_selections(selections);
+    true
+}
+
+pub fn previous_field(editor: &mut Editor) -> bool {
+    let Some(mut session) = editor.snippet.take() else {
+        return false;
+    };
+    session.current = session.current.saturating_sub(1);
+    let selections = session.selections();
+    editor.snippet = Some(session);
+    editor.set_selections(selections);
+    true
+}
+
+// the snippet whose prefix is before the only caret, expanded in its place; false if
+// there is none. `clipboard` reads the system clipboard, only when the snippet uses it,
+// and the editor's own clipboard stands in when it gives nothing
+pub fn expand_at_caret(
+    editor: &mut Editor,
+    snippets: &Snippets,
+    clipboard: impl FnOnce() -> Option<String>,
+) -> bool {
+    if editor.selections().len() != 1 || !editor.primary().is_empty() {
+        return false;
+    }
+    let caret = editor.caret();
+    let line_start = editor.document.line_start(editor.caret_line());
+    let before = &editor.document.text()[line_start..caret];
+    let Some((snippet, prefix)) = snippets.at_caret(editor.language, before) else {
+        return false;
+    };
+    let line_indent = indent::leading(before).to_string();
+    let tab = editor.indentation.unit();
+    let read = Cell::new(Some(clipboard));
+    let text = OnceCell::new();
+    let clipboard = || {
+        text.get_or_init(|| {
+            read.take()
+                .and_then(|read| read())
+                .unwrap_or_else(|| editor.clipboard_text())
+        })
+        .clone()
+    };
+    let expansion = expand(&snippet.body, &line_indent, &tab, &|name| {
+        variable(name, editor, &clipboard)
+    });
+    insert(editor, expansion, caret - prefix..caret);
+    true
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{editor::Indentation, text_document::TextDocument};
+
+    fn one_snippet(prefix: &str, body: &str) -> Snippets {
+        let snippet = Snippet {
+            name: prefix.into(),
+            prefixes: vec![prefix.into()],
+            body: body.into(),
+            description: None,
+        };
+        Snippets {
+            by_language: BTreeMap::from([(GLOBAL.to_string(), vec![snippet])]),
+            problems: Vec::new(),
+        }
+    }
+
+    #[test]
+    fn reads_the_clipboard_only_when_the_snippet_uses_it() {
+        let mut editor = Editor::new(TextDocument::from_text("cb"), Indentation::default());
+        editor.select(Selection::caret(2));
+        let read = Cell::new(0);
+        let clipboard = || {
+            read.set(read.get() + 1);
+            Some("pasted".to_string())
+        };
+        let snippets = one_snippet("cb", "[$CLIPBOARD|$CLIPBOARD]");
+        assert!(expand_at_caret(&mut editor, &snippets, clipboard));
+        assert_eq!(editor.document.text(), "[pasted|pasted]");
+        assert_eq!(read.get(), 1);
+
+        let mut editor = Editor::new(TextDocument::from_text("fn"), Indentation::default());
+        editor.select(Selection::caret(2));
+        let snippets = one_snippet("fn", "fn $1() {}");
+        assert!(expand_at_caret(&mut editor, &snippets, || unreachable!()));
+        assert_eq!(editor.document.text(), "fn () {}");
+    }
+}
@@ -0,0 +1,150 @@
+use glyph_brush::{Extra, HorizontalAlign, Layout, OwnedSection, OwnedText};
+use serde::Deserialize;
+
+use crate::{editor::Editor, utils::RectVertex, view::View};
+
+const BACKGROUND_COLOR: [f32; 4] = [0.12, 0.12, 0.14, 1.0];
+const TEXT_COLOR: [f32; 4] = [0.75, 0.75, 0.75, 1.0];
+const MESSAGE_COLOR: [f32; 4] = [0.9, 0.75, 0.3, 1.0];
+const SEPARATOR: &str = "   ";
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum Segment {
+    // state of modal editing such as -- INSERT --
+    Mode,
+    FileName,
+    Dirty,
+    Position,
+    Selection,
+    Language,
+    Encoding,
+    LineEnding,
+    Indentation,
+    Message,
+}
+
+// single line at the bottom of the window, segments are listed in the config file
+#[derive(Clone, Debug, Deserialize)]
+#[serde(default)]
+pub struct StatusBar {
+    pub left: Vec<Segment>,
+    pub right: Vec<Segment>,
+}
+
+impl Default for StatusBar {
+    fn default() -> Self {
+        use Segment::*;
+        Self {
+            left: vec![Mode, FileName, Dirty, Message],
+            right: vec![
+                Position,
+                Selection,
+                Language,
+                Encoding,
+                LineEnding,
+                Indentation,
+            ],
+        }
+    }
+}
+
+impl StatusBar {
+    pub fn height(&self, view: &View) -> f32 {
+        view.line_height
+    }
+
+    pub fn top(&self, view: &View) -> f32 {
+        view.height - self.height(view)
+    }
+
+    pub fn background(&self, view: &View) -> RectVertex {
+        let [r, g, b, a] = BACKGROUND_COLOR;
+        [0.0, self.top(view), view.width, view.height, r, g, b, a]
+    }
+
+    // empty segments are skipped so they leave no gap
+    pub fn segment_text(segment: Segment, editor: &Editor, mode: Option<&str>) -> Option<String> {
+        let document = &editor.document;
+        match segment {
+            Segment::Mode => mode.map(Into::into),
+            Segment::FileName => Some(
+                document
+                    .file_name()
+                    .and_then(|path| path.file_name())
+                    .map(|name| name.to_string_lossy().into())
+                    .or_else(|| document.title().map(Into::into))
+                    .unwrap_or_else(|| "untitled".into()),
+            ),
+            Segment::Dirty => (document.is_dirty() && !editor.read_only).then(|| "●".into()),
+            Segment::Position => {
+                let (line, column) = editor.caret_position();
+                Some(format!("Ln {line}, Col {column}"))
+            }
+            Segment::Selection => {
+                let selections = editor.selections();
+                let selected: usize = selections
+                    .iter()
+                    .map(|selection| editor.selected_text(selection).chars().count())
+                    .sum();
+                match (selections.len(), selected) {
+                    (1, 0) => None,
+                    (1, _) => Some(format!("({selected} selected)")),
+                    (count, _) => Some(format!("{count} selections ({selected} selected)")),
+                }
+            }
+            Segment::Language => Some(editor.language.name.into()),
+            Segment::Encoding => Some(document.encoding().to_string()),
+            Segment::LineEnding => Some(match document.has_mixed_line_endings() {
+                true => format!("{} (mixed)", document.line_ending()),
+                false => document.line_ending().to_string(),
+            }),
+            Segment::Indentation => Some(editor.indentation.to_string()),
+            Segment::Message => editor.message().map(Into::into),
+        }
+    }
+
+    pub fn sections(&self, view: &View, editor: &Editor, mode: Option<&str>) -> [OwnedSection; 2] {
+        let top = self.top(view);
+        let left = self
+            .texts(&self.left, view, editor, mode)
+            .with_screen_position((view.char_width, top));
+        let right = self
+            .texts(&self.right, view, editor, mode)
+            .with_screen_position((view.width - view.char_width, top))
+            .with_layout(Layout::default_single_line().h_align(HorizontalAlign::Right));
+        [left, right]
+    }
+
+    fn texts(
+        &self,
+        segments: &[Segment],
+        view: &View,
+        editor: &Editor,
+        mode: Option<&str>,
+    ) -> OwnedSection {
+        let mut text = Vec::new();
+        for &segment in segments {
+            let Some(content) = Self::segment_text(segment, editor, mode) else {
+                continue;
+            };
+            if !text.is_empty() {
+                text.push(OwnedText::new(SEPARATOR).with_scale(view.scale));
+            }
+            let color = match segment {
+                Segment::Message => MESSAGE_COLOR,
+                _ => TEXT_COLOR,
+            };
+            text.push(
+                OwnedText::new(content)
+                    .with_scale(view.scale)
+                    .with_color(color),
+            );
+        }
+
+        OwnedSection::<Extra>::default()
+            .with_text(text)
+            .with_bounds((view.width, self.height(view)))
+            .with_layout(Layout::default_single_line())
+    }
+}
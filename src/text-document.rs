@@ -1,23 +1,345 @@
+use std::{
+    fmt, fs,
+    ops::Range,
+    path::{Path, PathBuf},
+    sync::atomic::{AtomicU64, Ordering},
+};
+
+use crate::{encoding::Encoding, utils::Res};
+
+// revisions are unique across documents, so caches also notice a buffer switch
+static NEXT_REVISION: AtomicU64 = AtomicU64::new(1);
+
+// how lines end in the file; the text itself always breaks lines with \n
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub enum LineEnding {
+    #[default]
+    Lf,
+    Crlf,
+    Cr,
+}
+
+impl LineEnding {
+    pub fn as_str(self) -> &'static str {
+        match self {
+            Self::Lf => "\n",
+            Self::Crlf => "\r\n",
+            Self::Cr => "\r",
+        }
+    }
+
+    // the most common line ending of the text, LF when it has none, and whether others
+    // occur too
+    pub fn detect(text: &str) -> (Self, bool) {
+        let mut counts = [0usize; 3];
+        let mut bytes = text.bytes().peekable();
+        while let Some(byte) = bytes.next() {
+            match byte {
+                b'\n' => counts[0] += 1,
+                b'\r' if bytes.peek() == Some(&b'\n') => {
+                    bytes.next();
+                    counts[1] += 1;
+                }
+                b'\r' => counts[2] += 1,
+                _ => {}
+            }
+        }
+        let [lf, crlf, cr] = counts;
+        let ending = if crlf > lf && crlf >= cr {
+            Self::Crlf
+        } else if cr > lf && cr > crlf {
+            Self::Cr
+        } else {
+            Self::Lf
+        };
+        let mixed = counts.iter().filter(|count| **count > 0).count() > 1;
+        (ending, mixed)
+    }
+}
+
+impl fmt::Display for LineEnding {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.write_str(match self {
+            Self::Lf => "LF",
+            Self::Crlf => "CRLF",
+            Self::Cr => "CR",
+        })
+    }
+}
+
+// the text with every \r\n and lone \r made a \n
+pub fn normalize_line_endings(text: &str) -> String {
+    text.replace("\r\n", "\n").replace('\r', "\n")
+}
 
 pub struct TextDocument {
+    file_name: Option<PathBuf>,
+    // shown instead of the file name for buffers not backed by a file
+    title: Option<String>,
+    text: String,
     length: usize,
     line_count: usize,
-    line_buffer: Vec<usize>
+    // byte offset of the start of every line
+    line_buffer: Vec<usize>,
+    // modified since it was loaded
+    dirty: bool,
+    // bumped on every change, for caches of derived data
+    revision: u64,
+    line_ending: LineEnding,
+    // the file ended its lines in more than one way; saving makes them all line_ending
+    mixed_line_endings: bool,
+    // the file ended with a line break, saving writes one back only then
+    final_newline: bool,
+    encoding: Encoding,
 }
 
 impl TextDocument {
-    fn new(file_name: &str) -> Self {
-        if let Ok(lines)=read_lines(file_name){
+    // in the encoding the bytes look to be in
+    pub fn new(file_name: &str) -> Res<Self> {
+        Self::open(file_name, None)
+    }
 
+    pub fn with_encoding(file_name: &str, encoding: Encoding) -> Res<Self> {
+        Self::open(file_name, Some(encoding))
+    }
+
+    // the final line break is dropped, saving adds it back if there was one
+    fn open(file_name: &str, encoding: Option<Encoding>) -> Res<Self> {
+        let bytes = fs::read(file_name)?;
+        let encoding = encoding.unwrap_or_else(|| Encoding::detect(&bytes));
+        let text = encoding.decode(&bytes);
+        let (line_ending, mixed_line_endings) = LineEnding::detect(&text);
+        let stripped = text
+            .strip_suffix("\r\n")
+            .or_else(|| text.strip_suffix(['\n', '\r']));
+
+        let mut document = Self::from_text(stripped.unwrap_or(&text));
+        document.file_name = Some(file_name.into());
+        document.line_ending = line_ending;
+        document.mixed_line_endings = mixed_line_endings;
+        // an empty file gets one once it has text
+        document.final_newline = stripped.is_some() || text.is_empty();
+        document.encoding = encoding;
+        Ok(document)
+    }
+
+    pub fn from_text(text: &str) -> Self {
+        let mut document = Self {
+            file_name: None,
+            title: None,
+            text: normalize_line_endings(text),
+            length: 0,
+            line_count: 0,
+            line_buffer: Vec::new(),
+            dirty: false,
+            revision: 0,
+            line_ending: LineEnding::default(),
+            mixed_line_endings: false,
+            final_newline: true,
+            encoding: Encoding::default(),
+        };
+        document.index_lines();
+        document
+    }
+
+    pub fn file_name(&self) -> Option<&Path> {
+        self.file_name.as_deref()
+    }
+
+    pub fn with_title(mut self, title: &str) -> Self {
+        self.title = Some(title.into());
+        self
+    }
+
+    pub fn title(&self) -> Option<&str> {
+        self.title.as_deref()
+    }
+
+    pub fn is_dirty(&self) -> bool {
+        self.dirty
+    }
+
+    // write the text back to the file, ending it with a line break if it had one
+    pub fn save(&mut self) -> Res<()> {
+        let Some(file_name) = &self.file_name else {
+            return Err("the buffer has no file name".into());
+        };
+        let ending = self.line_ending.as_str();
+        let mut contents = match self.line_ending {
+            LineEnding::Lf => self.text.clone(),
+            _ => self.text.replace('\n', ending),
+        };
+        if self.final_newline && !contents.is_empty() {
+            contents.push_str(ending);
+        }
+        fs::write(file_name, self.encoding.encode(&contents)?)?;
+        self.dirty = false;
+        self.mixed_line_endings = false;
+        Ok(())
+    }
+
+    pub fn revision(&self) -> u64 {
+        self.revision
+    }
+
+    pub fn line_ending(&self) -> LineEnding {
+        self.line_ending
+    }
+
+    pub fn has_mixed_line_endings(&self) -> bool {
+        self.mixed_line_endings
+    }
+
+    // end every line this way from the next save on
+    pub fn set_line_ending(&mut self, line_ending: LineEnding) {
+        if line_ending != self.line_ending || self.mixed_line_endings {
+            self.dirty = true;
+        }
+        self.line_ending = line_ending;
+        self.mixed_line_endings = false;
+    }
+
+    pub fn encoding(&self) -> Encoding {
+        self.encoding
+    }
+
+    // write the file in the encoding from the next save on
+    pub fn set_encoding(&mut self, encoding: Encoding) {
+        if encoding != self.encoding {
+            self.dirty = true;
+        }
+        self.encoding = encoding;
+    }
+
+    pub fn text(&self) -> &str {
+        &self.text
+    }
+
+    pub fn len(&self) -> usize {
+        self.length
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.length == 0
+    }
+
+    pub fn line_count(&self) -> usize {
+        self.line_count
+    }
+
+    pub fn line_start(&self, line: usize) -> usize {
+        self.line_buffer[line.min(self.line_count - 1)]
+    }
+
+    // end of the line, excluding the line break
+    pub fn line_end(&self, line: usize) -> usize {
+        if line + 1 < self.line_count {
+            self.line_buffer[line + 1] - 1
+        } else {
+            self.length
+        }
+    }
+
+    pub fn line(&self, line: usize) -> &str {
+        &self.text[self.line_start(line)..self.line_end(line)]
     }
-}
 
-fn read_lines<P>(filename: P) -> io::Result<io::Lines<io::BufReader<File>>>
-where
-    P: AsRef<Path>,
-{
-    let file = File::open(filename)?;
-    Ok(io::BufReader::new(file).lines())
+    pub fn line_of_offset(&self, offset: usize) -> usize {
+        match self.line_buffer.binary_search(&offset) {
+            Ok(line) => line,
+            Err(next) => next - 1,
+        }
+    }
+
+    pub fn append(&mut self, text: &str) {
+        self.insert(self.length, text);
+    }
+
+    // line breaks in the inserted text become \n, the only one the text uses
+    pub fn insert(&mut self, offset: usize, text: &str) {
+        self.text.insert_str(offset, &normalize_line_endings(text));
+        self.dirty = true;
+        self.index_lines();
+    }
+
+    pub fn delete(&mut self, range: Range<usize>) {
+        self.text.replace_range(range, "");
+        self.dirty = true;
+        self.index_lines();
+    }
+
+    pub fn replace(&mut self, range: Range<usize>, text: &str) {
+        self.text
+            .replace_range(range, &normalize_line_endings(text));
+        self.dirty = true;
+        self.index_lines();
+    }
+
+    fn index_lines(&mut self) {
+        self.revision = NEXT_REVISION.fetch_add(1, Ordering::Relaxed);
+        self.line_buffer.clear();
+        self.line_buffer.push(0);
+        self.line_buffer.extend(
+            self.text
+                .bytes()
+                .enumerate()
+                .filter(|(_, b)| *b == b'\n')
+                .map(|(i, _)| i + 1),
+        );
+        self.length = self.text.len();
+        self.line_count = self.line_buffer.len();
+    }
 }
 
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    // the bytes a file of these bytes is saved as after opening it
+    fn saved(name: &str, bytes: &[u8]) -> Vec<u8> {
+        let path = std::env::temp_dir().join(format!("text_editor-{}-{name}", std::process::id()));
+        fs::write(&path, bytes).unwrap();
+        let mut document = TextDocument::new(path.to_str().unwrap()).unwrap();
+        document.save().unwrap();
+        let saved = fs::read(&path).unwrap();
+        fs::remove_file(&path).unwrap();
+        saved
+    }
+
+    #[test]
+    fn detects_the_most_common_line_ending() {
+        assert_eq!(LineEnding::detect("a\nb\nc"), (LineEnding::Lf, false));
+        assert_eq!(LineEnding::detect("a\r\nb\r\nc"), (LineEnding::Crlf, false));
+        assert_eq!(LineEnding::detect("a\rb\rc"), (LineEnding::Cr, false));
+        assert_eq!(
+            LineEnding::detect("a\r\nb\r\nc\nd"),
+            (LineEnding::Crlf, true)
+        );
+        assert_eq!(LineEnding::detect("no breaks"), (LineEnding::Lf, false));
+    }
 
+    #[test]
+    fn saving_keeps_line_endings_and_the_final_newline() {
+        for (name, bytes) in [
+            ("lf", &b"one\ntwo\n"[..]),
+            ("crlf", b"one\r\ntwo\r\n"),
+            ("cr", b"one\rtwo\r"),
+            ("unterminated", b"one\r\ntwo"),
+            ("empty", b""),
+        ] {
+            assert_eq!(saved(name, bytes), bytes, "{name}");
+        }
+        // saving makes mixed line endings the most common one
+        assert_eq!(saved("mixed", b"a\r\nb\r\nc\n"), b"a\r\nb\r\nc\r\n");
+    }
+
+    #[test]
+    fn inserted_line_breaks_become_lf() {
+        let mut document = TextDocument::from_text("ab");
+        document.insert(1, "\r\nx\r");
+        assert_eq!(document.text(), "a\nx\nb");
+        document.replace(0..1, "y\r\n");
+        assert_eq!(document.text(), "y\n\nx\nb");
+        assert_eq!(document.line_count(), 4);
+    }
+}
@@ -0,0 +1,357 @@
+use std::{hash::BuildHasher, ops::Range};
+
+use glyph_brush::{
+    ab_glyph::{Font, ScaleFont},
+    Extra, GlyphBrush, GlyphCruncher, OwnedSection, OwnedText,
+};
+
+use serde::Deserialize;
+
+use crate::{
+    columns,
+    editor::Editor,
+    encoding,
+    folding::FOLD_MARKER,
+    utils::{RectVertex, Vertex},
+    view::View,
+    wrap::Row,
+};
+
+const TEXT_COLOR: [f32; 4] = [0.9, 0.9, 0.9, 1.0];
+const WHITESPACE_COLOR: [f32; 4] = [0.35, 0.35, 0.4, 1.0];
+const UNDECODABLE_COLOR: [f32; 4] = [0.9, 0.4, 0.35, 1.0];
+const SELECTION_COLOR: [f32; 4] = [0.15, 0.3, 0.5, 1.0];
+const CARET_COLOR: [f32; 4] = [0.9, 0.9, 0.9, 1.0];
+const CARET_WIDTH: f32 = 2.0;
+
+// horizontal extent of a laid out glyph and the document offset it came from
+#[derive(Clone, Copy, Debug)]
+pub struct GlyphSpan {
+    pub offset: usize,
+    pub left: f32,
+    pub right: f32,
+}
+
+// which whitespace is drawn as faint markers
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum Whitespace {
+    #[default]
+    None,
+    // spaces, tabs and line breaks
+    All,
+    // only the whitespace ending a line
+    Trailing,
+}
+
+// a run of one row's text drawn in one color, with the document offset of each byte
+struct Piece {
+    row: usize,
+    offsets: Vec<usize>,
+}
+
+// the visible rows of the document as glyph_brush laid them out in the last frame
+pub struct TextView {
+    pub left: f32,
+    pub bottom: f32,
+    pub rows: Vec<Row>,
+    whitespace: Whitespace,
+    // section texts of the last frame, in order
+    pieces: Vec<Piece>,
+    // glyphs of every row, in the same order as rows
+    glyphs: Vec<Vec<GlyphSpan>>,
+    line_height: f32,
+    char_width: f32,
+}
+
+impl TextView {
+    pub fn new(
+        editor: &Editor,
+        view: &View,
+        whitespace: Whitespace,
+        left: f32,
+        bottom: f32,
+    ) -> Self {
+        Self {
+            left,
+            bottom,
+            rows: editor.visible_rows(),
+            whitespace,
+            pieces: Vec::new(),
+            glyphs: Vec::new(),
+            line_height: view.line_height,
+            char_width: view.char_width,
+        }
+    }
+
+    // the texts of every row, tabs expanded to the next tab stop, whitespace markers and
+    // bytes the encoding couldn't decode in their own colors; offsets map the shown bytes
+    // back into the document
+    pub fn section(&mut self, editor: &Editor, view: &View) -> OwnedSection {
+        let document = editor.document.text();
+        let tab_width = editor.indentation.width.max(1);
+        let mut texts = Vec::new();
+        self.pieces.clear();
+        for (index, row) in self.rows.iter().enumerate() {
+            let shown = &document[row.range.clone()];
+            let ends_line = self
+                .rows
+                .get(index + 1)
+                .is_none_or(|next| !next.continuation);
+            let trailing = match ends_line {
+                true => row.range.start + shown.trim_end().len(),
+                false => row.range.end,
+            };
+
+            // (text, color, offsets) runs of the row
+            let mut runs: Vec<(String, [f32; 4], Vec<usize>)> = Vec::new();
+            let mut column = 0;
+            for (i, c) in shown.char_indices() {
+                let offset = row.range.start + i;
+                let marked = match self.whitespace {
+                    Whitespace::None => false,
+                    Whitespace::All => true,
+                    Whitespace::Trailing => offset >= trailing,
+                };
+                let width = columns::char_columns(c, column, tab_width);
+                column += width;
+                let text = match c {
+                    '\t' if marked => format!("→{}", " ".repeat(width - 1)),
+                    '\t' => " ".repeat(width),
+                    ' ' if marked => "·".to_string(),
+                    c if encoding::undecodable_byte(c).is_some() => {
+                        char::REPLACEMENT_CHARACTER.to_string()
+                    }
+                    c => c.to_string(),
+                };
+                let color = if encoding::undecodable_byte(c).is_some() {
+                    UNDECODABLE_COLOR
+                } else if marked && (c == '\t' || c == ' ') {
+                    WHITESPACE_COLOR
+                } else {
+                    TEXT_COLOR
+                };
+                if runs.last().is_none_or(|run| run.1 != color) {
+                    runs.push((String::new(), color, Vec::new()));
+                }
+                let run = runs.last_mut().unwrap();
+                run.0.push_str(&text);
+                run.2.extend(std::iter::repeat_n(offset, text.len()));
+            }
+            // like the line break the placeholder sits past the row end, out of hit-testing
+            if row.folded {
+                runs.push((
+                    FOLD_MARKER.to_string(),
+                    WHITESPACE_COLOR,
+                    vec![row.range.end; FOLD_MARKER.len()],
+                ));
+            }
+            if self.whitespace == Whitespace::All
+                && ends_line
+                && !row.folded
+                && row.range.end < document.len()
+            {
+                runs.push((
+                    "¬".to_string(),
+                    WHITESPACE_COLOR,
+                    vec![row.range.end; '¬'.len_utf8()],
+                ));
+            }
+            if runs.is_empty() {
+                runs.push((String::new(), TEXT_COLOR, Vec::new()));
+            }
+            runs.last_mut().unwrap().0.push('\n');
+
+            for (text, color, offsets) in runs {
+                texts.push(
+                    OwnedText::new(text)
+                        .with_scale(view.scale)
+                        .with_color(color),
+                );
+                self.pieces.push(Piece {
+                    row: index,
+                    offsets,
+                });
+            }
+        }
+
+        OwnedSection::<Extra>::default()
+            .with_text(texts)
+            .with_screen_position((self.left, 0.0))
+            .with_bounds((f32::INFINITY, self.bottom))
+    }
+
+    // record glyph positions of the section, must be the one last returned by section()
+    pub fn layout<F, H>(
+        &mut self,
+        glyph_brush: &mut GlyphBrush<Vertex, Extra, F, H>,
+        section: &OwnedSection,
+    ) where
+        F: Font,
+        H: BuildHasher,
+    {
+        let glyphs: Vec<_> = glyph_brush.glyphs(section).cloned().collect();
+        let fonts = glyph_brush.fonts();
+
+        self.glyphs = vec![Vec::new(); self.rows.len()];
+        for glyph in glyphs {
+            let Some(piece) = self.pieces.get(glyph.section_index) else {
+                continue;
+            };
+            let row = &self.rows[piece.row];
+            let Some(&offset) = piece.offsets.get(glyph.byte_index) else {
+                continue;
+            };
+            if offset >= row.range.end {
+                continue;
+            }
+            let advance = fonts[glyph.font_id.0]
+                .as_scaled(glyph.glyph.scale)
+                .h_advance(glyph.glyph.id);
+            let (left, right) = (glyph.glyph.position.x, glyph.glyph.position.x + advance);
+            let spans = &mut self.glyphs[piece.row];
+            // the spaces an expanded tab is drawn with make up one glyph
+            match spans.last_mut() {
+                Some(span) if span.offset == offset => span.right = right,
+                _ => spans.push(GlyphSpan {
+                    offset,
+                    left,
+                    right,
+                }),
+            }
+        }
+    }
+
+    // index of the row under the y coordinate, clamped to the visible rows
+    pub fn row_at(&self, y: f32) -> usize {
+        ((y.max(0.0) / self.line_height) as usize).min(self.rows.len().saturating_sub(1))
+    }
+
+    // document offset closest to the pixel position
+    pub fn offset_at(&self, x: f32, y: f32) -> Option<usize> {
+        let index = self.row_at(y);
+        let row = self.rows.get(index)?;
+        let glyphs = self.glyphs.get(index).map_or(&[][..], Vec::as_slice);
+
+        let offset = glyphs
+            .iter()
+            .find(|glyph| x < (glyph.left + glyph.right) / 2.0)
+            .map_or(row.range.end, |glyph| glyph.offset);
+        Some(offset)
+    }
+
+    // whole columns between the end of the row under the pointer and the pointer,
+    // for selecting blocks past the end of short lines
+    pub fn columns_past_end(&self, x: f32, y: f32) -> usize {
+        let index = self.row_at(y);
+        if index >= self.glyphs.len() {
+            return 0;
+        }
+        let end = self.glyphs[index]
+            .last()
+            .map_or(self.left, |glyph| glyph.right);
+        ((x - end) / self.char_width).round().max(0.0) as usize
+    }
+
+    // row that shows the offset, a wrap boundary belongs to the row it starts
+    fn row_of(&self, offset: usize) -> Option<usize> {
+        self.rows
+            .iter()
+            .rposition(|row| row.range.start <= offset && offset <= row.range.end)
+    }
+
+    fn x_of(&self, index: usize, offset: usize) -> f32 {
+        let glyphs = &self.glyphs[index];
+        match glyphs.iter().find(|glyph| glyph.offset >= offset) {
+            Some(glyph) => glyph.left,
+            None => glyphs.last().map_or(self.left, |glyph| glyph.right),
+        }
+    }
+
+    fn selection_rects(
+        &self,
+        selection: Range<usize>,
+        view: &View,
+        color: [f32; 4],
+        rects: &mut Vec<RectVertex>,
+    ) {
+        for (index, row) in self.rows.iter().enumerate() {
+            let start = selection.start.max(row.range.start);
+            let end = selection.end.min(row.range.end);
+            // a selected line break shows as one extra column
+            let ends_line = self
+                .rows
+                .get(index + 1)
+                .is_none_or(|next| !next.continuation);
+            let selects_break =
+                ends_line && selection.end > row.range.end && selection.start <= row.range.end;
+            if start > end || (start == end && !selects_break) {
+                continue;
+            }
+
+            let top = index as f32 * self.line_height;
+            let mut right = self.x_of(index, end);
+            if selects_break {
+                right += view.char_width;
+            }
+            let [r, g, b, a] = color;
+            rects.push([
+                self.x_of(index, start),
+                top,
+                right,
+                top + self.line_height,
+                r,
+                g,
+                b,
+                a,
+            ]);
+        }
+    }
+
+    // selection highlights and the carets
+    pub fn rects(&self, editor: &Editor, view: &View) -> Vec<RectVertex> {
+        let mut rects = Vec::new();
+        if self.glyphs.len() != self.rows.len() {
+            return rects;
+        }
+
+        for selection in editor.selections().iter() {
+            self.selection_rects(selection.range(), view, SELECTION_COLOR, &mut rects);
+        }
+
+        for selection in editor.selections().iter() {
+            if let Some(index) = self.row_of(selection.head) {
+                let x = self.x_of(index, selection.head);
+                let top = index as f32 * self.line_height;
+                let [r, g, b, a] = CARET_COLOR;
+                rects.push([x, top, x + CARET_WIDTH, top + self.line_height, r, g, b, a]);
+            }
+        }
+        rects
+    }
+
+    // background for ranges such as search matches, only those in the visible rows
+    pub fn highlight_rects(
+        &self,
+        ranges: &[Range<usize>],
+        view: &View,
+        color: [f32; 4],
+    ) -> Vec<RectVertex> {
+        let mut rects = Vec::new();
+        let (Some(first), Some(last)) = (self.rows.first(), self.rows.last()) else {
+            return rects;
+        };
+        if self.glyphs.len() != self.rows.len() {
+            return rects;
+        }
+
+        let start = ranges.partition_point(|range| range.end < first.range.start);
+        for range in ranges[start..]
+            .iter()
+            .take_while(|range| range.start <= last.range.end)
+        {
+            self.selection_rects(range.clone(), view, color, &mut rects);
+        }
+        rects
+    }
+}
@@ -6,6 +6,9 @@ pub type Res<T> = Result<T, Box<dyn std::error::Error>>;
 
 pub type Vertex = [GLfloat; 13];
 
+// left, top, right, bottom, r, g, b, a
+pub type RectVertex = [GLfloat; 8];
+
 pub fn gl_err_to_str(err: u32) -> &'static str {
     match err {
         gl::INVALID_ENUM => "INVALID_ENUM",
@@ -0,0 +1,37 @@
+use glyph_brush::ab_glyph::{Font, FontRef, PxScale, ScaleFont};
+
+// pixel geometry of the editor window, derived from the font and window size
+#[derive(Clone, Copy, Debug)]
+pub struct View {
+    pub scale: PxScale,
+    pub line_height: f32,
+    pub char_width: f32,
+    pub width: f32,
+    pub height: f32,
+}
+
+impl View {
+    pub fn new(font: &FontRef, scale: f32, (width, height): (f32, f32)) -> Self {
+        let scale = PxScale::from(scale);
+        let scaled = font.as_scaled(scale);
+
+        Self {
+            scale,
+            // glyph_brush advances lines by ascent - descent + line_gap
+            line_height: scaled.height() + scaled.line_gap(),
+            char_width: scaled.h_advance(font.glyph_id(' ')),
+            width,
+            height,
+        }
+    }
+
+    pub fn resize(&mut self, width: f32, height: f32) {
+        self.width = width;
+        self.height = height;
+    }
+
+    // number of whole lines that fit into the given height
+    pub fn lines_in(&self, height: f32) -> usize {
+        ((height / self.line_height).floor() as usize).max(1)
+    }
+}
@@ -0,0 +1,1701 @@
+use std::{collections::HashMap, mem, ops::Range};
+
+use winit::keyboard::{Key, ModifiersState, NamedKey, NativeKeyCode, PhysicalKey};
+
+use crate::{
+    editor::Editor, history::EditKind, indent, input::LineInput, keymap, selection::Selection,
+    text_document::TextDocument,
+};
+
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub enum Mode {
+    #[default]
+    Normal,
+    Insert,
+    Visual,
+    VisualLine,
+    // typing a : command
+    Ex,
+}
+
+// a key as it was pressed, kept to replay changes with .
+#[derive(Clone, Debug)]
+pub struct Stroke {
+    pub key: Key,
+    pub physical: PhysicalKey,
+    pub modifiers: ModifiersState,
+}
+
+impl Stroke {
+    pub fn new(key: Key, physical: PhysicalKey, modifiers: ModifiersState) -> Self {
+        Self {
+            key,
+            physical,
+            modifiers,
+        }
+    }
+
+    fn logical(key: Key, modifiers: ModifiersState) -> Self {
+        Self::new(
+            key,
+            PhysicalKey::Unidentified(NativeKeyCode::Unidentified),
+            modifiers,
+        )
+    }
+}
+
+pub enum Response {
+    // not a Vim key, the key bindings and typing get it
+    Pass,
+    Handled,
+    // ex commands map onto the commands of the editor
+    Run(Vec<&'static str>),
+    // keys of the last change, fed back in for .
+    Repeat(Vec<Stroke>),
+}
+
+#[derive(Clone, Debug, Default)]
+struct Register {
+    text: String,
+    // yanked whole lines, put on lines of their own
+    linewise: bool,
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+enum Operator {
+    Delete,
+    Change,
+    Yank,
+    Indent,
+    Outdent,
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+enum Motion {
+    Left,
+    Right,
+    Up,
+    Down,
+    WordStart { big: bool },
+    WordEnd { big: bool },
+    WordBack { big: bool },
+    LineStart,
+    FirstNonBlank,
+    LineEnd,
+    FirstLine,
+    LastLine,
+    ParagraphForward,
+    ParagraphBack,
+    Find(Find),
+    RepeatFind { reverse: bool },
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+struct Find {
+    c: char,
+    forward: bool,
+    // stop next to the char, as t and T do
+    till: bool,
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+enum Object {
+    Word { big: bool },
+    Quote(char),
+    Pair(char, char),
+    Paragraph,
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+enum Target {
+    Motion(Motion),
+    Object { object: Object, around: bool },
+    // the operator doubled, as in dd, on count lines
+    Lines,
+    // what visual mode selected
+    Selection,
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+enum InsertAt {
+    Caret,
+    AfterCaret,
+    FirstNonBlank,
+    LineEnd,
+    LineBelow,
+    LineAbove,
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+enum Action {
+    Move(Motion),
+    Operate(Operator, Target),
+    SelectObject { object: Object, around: bool },
+    Insert(InsertAt),
+    Visual { linewise: bool },
+    SwapEnds,
+    Put { before: bool },
+    Join,
+    Replace(char),
+    Undo,
+    Repeat,
+    Ex,
+}
+
+impl Action {
+    // changes are what . repeats
+    fn is_change(&self) -> bool {
+        match self {
+            Action::Operate(operator, _) => *operator != Operator::Yank,
+            Action::Insert(_) | Action::Put { .. } | Action::Join | Action::Replace(_) => true,
+            _ => false,
+        }
+    }
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+struct Command {
+    register: Option<char>,
+    count: Option<usize>,
+    action: Action,
+}
+
+enum Parse<T> {
+    // more keys are needed
+    Incomplete,
+    Invalid,
+    Done(T),
+}
+
+// text covered by an operator
+enum Span {
+    Chars(Range<usize>),
+    // first and last line
+    Lines(usize, usize),
+}
+
+// modal editing on top of the editor: normal mode keys are parsed into counts, operators
+// and motions, insert mode leaves the keys to the key bindings
+#[derive(Default)]
+pub struct Vim {
+    enabled: bool,
+    mode: Mode,
+    // keys of the normal mode command typed so far
+    pending: String,
+    registers: HashMap<char, Register>,
+    // ends of the visual selection, the head is the char the caret is on
+    anchor: usize,
+    head: usize,
+    // column j and k keep to across short lines
+    column: Option<usize>,
+    last_find: Option<Find>,
+    ex: LineInput,
+    // keys of the change being typed, the last complete one is repeated with .
+    recording: Vec<Stroke>,
+    recording_insert: bool,
+    last_change: Vec<Stroke>,
+}
+
+impl Vim {
+    pub fn is_enabled(&self) -> bool {
+        self.enabled
+    }
+
+    pub fn set_enabled(&mut self, enabled: bool) {
+        self.enabled = enabled;
+        self.mode = Mode::Normal;
+        self.pending.clear();
+    }
+
+    pub fn mode(&self) -> Mode {
+        self.mode
+    }
+
+    // shown in the status bar
+    pub fn status(&self) -> Option<String> {
+        if !self.enabled {
+            return None;
+        }
+        Some(match self.mode {
+            Mode::Normal if self.pending.is_empty() => "NORMAL".into(),
+            Mode::Normal => format!("NORMAL {}", self.pending),
+            Mode::Insert => "-- INSERT --".into(),
+            Mode::Visual => "-- VISUAL --".into(),
+            Mode::VisualLine => "-- VISUAL LINE --".into(),
+            Mode::Ex => format!(":{}", self.ex.text()),
+        })
+    }
+
+    pub fn handle_key(&mut self, editor: &mut Editor, stroke: &Stroke) -> Response {
+        // Shift pressed for the $ of d$ leaves the d pending
+        if !self.enabled || keymap::is_modifier(&stroke.key) {
+            return Response::Pass;
+        }
+        match self.mode {
+            Mode::Insert => self.insert_key(editor, stroke),
+            Mode::Ex => self.ex_key(editor, stroke),
+            Mode::Normal | Mode::Visual | Mode::VisualLine => self.command_key(editor, stroke),
+        }
+    }
+
+    fn insert_key(&mut self, editor: &mut Editor, stroke: &Stroke) -> Response {
+        if self.recording_insert {
+            self.recording.push(stroke.clone());
+        }
+        if stroke.key != Key::Named(NamedKey::Escape) {
+            return Response::Pass;
+        }
+        if mem::take(&mut self.recording_insert) {
+            self.last_change = mem::take(&mut self.recording);
+        }
+        self.mode = Mode::Normal;
+        // the caret steps back onto the last char typed
+        let caret = editor.caret();
+        let line_start = editor.document.line_start(editor.caret_line());
+        let caret = match caret > line_start {
+            true => prev(editor.document.text(), caret),
+            false => caret,
+        };
+        set_caret(editor, caret);
+        Response::Handled
+    }
+
+    fn ex_key(&mut self, editor: &mut Editor, stroke: &Stroke) -> Response {
+        match &stroke.key {
+            Key::Named(NamedKey::Escape) => self.mode = Mode::Normal,
+            Key::Named(NamedKey::Enter) => {
+                self.mode = Mode::Normal;
+                let command = self.ex.text().trim().to_string();
+                return self.ex_command(editor, &command);
+            }
+            Key::Named(NamedKey::Backspace) if self.ex.text().is_empty() => {
+                self.mode = Mode::Normal
+            }
+            key => {
+                self.ex.handle_key(key);
+            }
+        }
+        Response::Handled
+    }
+
+    fn ex_command(&mut self, editor: &mut Editor, command: &str) -> Response {
+        let ids = match command {
+            "" => return Response::Handled,
+            "w" => vec!["file.save"],
+            "q" => vec!["buffer.close"],
+            "q!" => vec!["buffer.force_close"],
+            "wq" | "x" => vec!["file.save", "buffer.close"],
+            "qa" | "qall" => vec!["app.quit"],
+            "qa!" | "qall!" => vec!["app.force_quit"],
+            _ => match command.parse::<usize>() {
+                Ok(line) => {
+                    let line = line.clamp(1, editor.document.line_count()) - 1;
+                    set_caret(editor, first_non_blank(&editor.document, line));
+                    return Response::Handled;
+                }
+                Err(_) => {
+                    editor.set_message(format!("Not an editor command: {command}"));
+                    return Response::Handled;
+                }
+            },
+        };
+        Response::Run(ids)
+    }
+
+    fn command_key(&mut self, editor: &mut Editor, stroke: &Stroke) -> Response {
+        let modifiers = stroke.modifiers;
+        if modifiers.control_key() && stroke.key == Key::Character("r".into()) {
+            self.pending.clear();
+            editor.redo();
+            self.normalize(editor);
+            return Response::Handled;
+        }
+        // other shortcuts still reach the key bindings
+        if modifiers.control_key() || modifiers.super_key() || modifiers.alt_key() {
+            return Response::Pass;
+        }
+        if stroke.key == Key::Named(NamedKey::Escape) {
+            if !self.pending.is_empty() {
+                self.pending.clear();
+            } else if self.mode != Mode::Normal {
+                self.leave_visual(editor);
+            } else {
+                editor.collapse_selections();
+            }
+            return Response::Handled;
+        }
+        let Some(c) = key_char(&stroke.key) else {
+            self.pending.clear();
+            return Response::Pass;
+        };
+
+        if self.pending.is_empty() {
+            self.recording.clear();
+        }
+        self.recording.push(stroke.clone());
+        self.pending.push(c);
+        let keys: Vec<char> = self.pending.chars().collect();
+        let visual = self.mode != Mode::Normal;
+        match parse(&keys, visual) {
+            Parse::Incomplete => Response::Handled,
+            Parse::Invalid => {
+                self.pending.clear();
+                Response::Handled
+            }
+            Parse::Done(command) => {
+                self.pending.clear();
+                self.execute(editor, command, visual)
+            }
+        }
+    }
+
+    fn execute(&mut self, editor: &mut Editor, command: Command, visual: bool) -> Response {
+        let count = command.count.unwrap_or(1);
+        if !matches!(command.action, Action::Move(Motion::Up | Motion::Down)) {
+            self.column = None;
+        }
+        match command.action {
+            Action::Move(motion) => {
+                let from = self.caret(editor);
+                if let Some(to) = self.motion_target(editor, from, motion, command.count) {
+                    self.move_to(editor, to);
+                }
+            }
+            Action::Operate(operator, target) => {
+                let span = match target {
+                    Target::Selection => Some(self.selection_span(editor)),
+                    target => self.span(editor, operator, target, count, command.count),
+                };
+                if visual {
+                    self.mode = Mode::Normal;
+                }
+                if let Some(span) = span {
+                    self.operate(editor, operator, span, command.register);
+                }
+            }
+            Action::SelectObject { object, around } => {
+                let caret = self.caret(editor);
+                match object_span(&editor.document, caret, object, around) {
+                    Some(Span::Chars(range)) if !range.is_empty() => {
+                        if self.head == self.anchor {
+                            self.anchor = range.start;
+                        }
+                        self.head = prev(editor.document.text(), range.end);
+                    }
+                    Some(Span::Lines(first, last)) => {
+                        self.mode = Mode::VisualLine;
+                        self.anchor = editor.document.line_start(first);
+                        self.head = editor.document.line_start(last);
+                    }
+                    _ => {}
+                }
+                self.show_visual(editor);
+            }
+            Action::Insert(at) => self.insert(editor, at),
+            Action::Visual { linewise } => {
+                let mode = match linewise {
+                    true => Mode::VisualLine,
+                    false => Mode::Visual,
+                };
+                match self.mode {
+                    current if current == mode => self.leave_visual(editor),
+                    Mode::Normal => {
+                        self.mode = mode;
+                        self.anchor = editor.caret();
+                        self.head = self.anchor;
+                        self.show_visual(editor);
+                    }
+                    _ => {
+                        self.mode = mode;
+                        self.show_visual(editor);
+                    }
+                }
+            }
+            Action::SwapEnds => {
+                mem::swap(&mut self.anchor, &mut self.head);
+                self.show_visual(editor);
+            }
+            Action::Put { before } => self.put(editor, command.register, before, count),
+            Action::Join => self.join(editor, count),
+            Action::Replace(c) => self.replace(editor, c, count),
+            Action::Undo => {
+                for _ in 0..count {
+                    editor.undo();
+                }
+                self.normalize(editor);
+            }
+            Action::Repeat => return self.repeat(command.count),
+            Action::Ex => {
+                if visual {
+                    self.leave_visual(editor);
+                }
+                self.ex.set_text("");
+                self.mode = Mode::Ex;
+            }
+        }
+
+        if command.action.is_change() && !visual {
+            match self.mode {
+                Mode::Insert => self.recording_insert = true,
+                _ => self.last_change = mem::take(&mut self.recording),
+            }
+        }
+        Response::Handled
+    }
+
+    // the last change again, the count replacing the one it was typed with
+    fn repeat(&mut self, count: Option<usize>) -> Response {
+        if self.last_change.is_empty() {
+            return Response::Handled;
+        }
+        let mut strokes = self.last_change.clone();
+        if let Some(count) = count {
+            let typed = strokes
+                .iter()
+                .take_while(|stroke| key_char(&stroke.key).is_some_and(|c| c.is_ascii_digit()))
+                .count();
+            strokes.splice(
+                ..typed,
+                count.to_string().chars().map(|c| {
+                    Stroke::logical(
+                        Key::Character(c.to_string().into()),
+                        ModifiersState::empty(),
+                    )
+                }),
+            );
+        }
+        Response::Repeat(strokes)
+    }
+
+    // the char the commands act on, the visual head while selecting
+    fn caret(&self, editor: &Editor) -> usize {
+        match self.mode {
+            Mode::Visual | Mode::VisualLine => self.head,
+            _ => editor.caret(),
+        }
+    }
+
+    fn move_to(&mut self, editor: &mut Editor, to: usize) {
+        match self.mode {
+            Mode::Visual | Mode::VisualLine => {
+                self.head = to.min(editor.document.len());
+                self.show_visual(editor);
+            }
+            _ => {
+                set_caret(editor, to);
+                if let Some(column) = self.column {
+                    let mut caret = editor.primary();
+                    caret.column = Some(column);
+                    editor.select(caret);
+                }
+            }
+        }
+    }
+
+    fn show_visual(&self, editor: &mut Editor) {
+        let document = &editor.document;
+        let text = document.text();
+        let selection = match self.mode {
+            Mode::VisualLine => {
+                let anchor_line = document.line_of_offset(self.anchor);
+                let head_line = document.line_of_offset(self.head);
+                let (first, last) = (anchor_line.min(head_line), anchor_line.max(head_line));
+                let range = lines_range(document, first, last);
+                match head_line < anchor_line {
+                    true => Selection::new(range.end, range.start),
+                    false => Selection::new(range.start, range.end),
+                }
+            }
+            _ if self.head < self.anchor => Selection::new(next(text, self.anchor), self.head),
+            _ => Selection::new(self.anchor, next(text, self.head)),
+        };
+        editor.select(selection);
+    }
+
+    fn leave_visual(&mut self, editor: &mut Editor) {
+        self.mode = Mode::Normal;
+        set_caret(editor, self.head);
+    }
+
+    fn selection_span(&self, editor: &Editor) -> Span {
+        let document = &editor.document;
+        match self.mode {
+            Mode::VisualLine => {
+                let anchor_line = document.line_of_offset(self.anchor);
+                let head_line = document.line_of_offset(self.head);
+                Span::Lines(anchor_line.min(head_line), anchor_line.max(head_line))
+            }
+            _ => Span::Chars(editor.primary().range()),
+        }
+    }
+
+    // keep the caret on a char as normal mode does
+    fn normalize(&self, editor: &mut Editor) {
+        if self.mode == Mode::Normal {
+            set_caret(editor, editor.caret());
+        }
+    }
+
+    fn motion_target(
+        &mut self,
+        editor: &Editor,
+        from: usize,
+        motion: Motion,
+        count: Option<usize>,
+    ) -> Option<usize> {
+        let document = &editor.document;
+        let text = document.text();
+        let times = count.unwrap_or(1);
+        let line = document.line_of_offset(from);
+        let to = match motion {
+            Motion::Left => {
+                let start = document.line_start(line);
+                (0..times).fold(from, |at, _| match at > start {
+                    true => prev(text, at),
+                    false => at,
+                })
+            }
+            Motion::Right => {
+                let end = document.line_end(line);
+                (0..times).fold(from, |at, _| match at < end {
+                    true => next(text, at),
+                    false => at,
+                })
+            }
+            Motion::Up | Motion::Down => {
+                let target = match motion {
+                    Motion::Up => line.checked_sub(times.min(line))?,
+                    _ => (line + times).min(document.line_count() - 1),
+                };
+                let column = *self.column.get_or_insert_with(|| editor.column_of(from));
+                editor.offset_at_column(target, column)
+            }
+            Motion::WordStart { big } => (0..times).fold(from, |at, _| word_start(text, at, big)),
+            Motion::WordEnd { big } => (0..times).fold(from, |at, _| word_end(text, at, big)),
+            Motion::WordBack { big } => (0..times).fold(from, |at, _| word_back(text, at, big)),
+            Motion::LineStart => document.line_start(line),
+            Motion::FirstNonBlank => first_non_blank(document, line),
+            Motion::LineEnd => {
+                let last = (line + times - 1).min(document.line_count() - 1);
+                document.line_end(last)
+            }
+            Motion::FirstLine | Motion::LastLine => {
+                let target = match (motion, count) {
+                    (_, Some(count)) => count.clamp(1, document.line_count()) - 1,
+                    (Motion::FirstLine, None) => 0,
+                    _ => document.line_count() - 1,
+                };
+                first_non_blank(document, target)
+            }
+            Motion::ParagraphForward => {
+                (0..times).fold(from, |at, _| paragraph(document, at, true))
+            }
+            Motion::ParagraphBack => (0..times).fold(from, |at, _| paragraph(document, at, false)),
+            // f and t fail when the char is not found, t next to it stays put
+            Motion::Find(find) => {
+                self.last_find = Some(find);
+                return find_char(document, from, find, times);
+            }
+            Motion::RepeatFind { reverse } => {
+                let mut find = self.last_find?;
+                find.forward ^= reverse;
+                // t and T would find the char they already stopped before
+                let from = match (find.till, find.forward) {
+                    (true, true) => next(text, from),
+                    (true, false) => prev(text, from),
+                    _ => from,
+                };
+                return find_char(document, from, find, times);
+            }
+        };
+        // a motion that cannot move fails
+        (to != from).then_some(to)
+    }
+
+    // text a motion or text object covers for an operator
+    fn span(
+        &mut self,
+        editor: &Editor,
+        operator: Operator,
+        target: Target,
+        times: usize,
+        count: Option<usize>,
+    ) -> Option<Span> {
+        let document = &editor.document;
+        let text = document.text();
+        let from = editor.caret();
+        let line = document.line_of_offset(from);
+        match target {
+            Target::Lines => {
+                let last = line + times - 1;
+                (last < document.line_count()).then_some(Span::Lines(line, last))
+            }
+            Target::Object { object, around } => object_span(document, from, object, around),
+            Target::Selection => None,
+            // cw on a word changes to its end, like ce
+            Target::Motion(Motion::WordStart { big })
+                if operator == Operator::Change
+                    && char_at(text, from).is_some_and(|c| !c.is_whitespace()) =>
+            {
+                let run = char_at(text, from).map(|c| class(c, big));
+                let mut end = from;
+                let at_end = char_at(text, next(text, from)).map(|c| class(c, big)) != run;
+                if !at_end {
+                    end = word_end(text, end, big);
+                }
+                for _ in 1..times {
+                    end = word_end(text, end, big);
+                }
+                Some(Span::Chars(from..next(text, end)))
+            }
+            Target::Motion(motion) => {
+                let to = self.motion_target(editor, from, motion, count)?;
+                let (start, end) = (from.min(to), from.max(to));
+                match motion {
+                    Motion::Up | Motion::Down | Motion::FirstLine | Motion::LastLine => Some(
+                        Span::Lines(document.line_of_offset(start), document.line_of_offset(end)),
+                    ),
+                    Motion::WordEnd { .. } => Some(Span::Chars(start..next(text, end))),
+                    Motion::Find(Find { forward: true, .. }) => {
+                        Some(Span::Chars(start..next(text, end)))
+                    }
+                    Motion::RepeatFind { .. } if to > from => {
+                        Some(Span::Chars(start..next(text, end)))
+                    }
+                    // the last word of a line is deleted up to the line end, not the next line
+                    Motion::WordStart { .. } if document.line_of_offset(to) > line => {
+                        let previous_end = document.line_end(document.line_of_offset(to) - 1);
+                        let end = match text[previous_end..to].trim().is_empty() {
+                            true => previous_end.max(from),
+                            false => to,
+                        };
+                        Some(Span::Chars(from..end))
+                    }
+                    _ => Some(Span::Chars(start..end)),
+                }
+            }
+        }
+    }
+
+    fn operate(
+        &mut self,
+        editor: &mut Editor,
+        operator: Operator,
+        span: Span,
+        register: Option<char>,
+    ) {
+        let document = &editor.document;
+        let (range, linewise) = match span {
+            Span::Chars(range) => (range, false),
+            Span::Lines(first, last) => (lines_range(document, first, last), true),
+        };
+        let first_line = document.line_of_offset(range.start);
+        let mut copied = document.text()[range.clone()].to_string();
+        if linewise && !copied.ends_with('\n') {
+            copied.push('\n');
+        }
+
+        match operator {
+            Operator::Yank => {
+                self.store(register, copied, linewise, true);
+                if !linewise || editor.caret_line() != first_line {
+                    set_caret(editor, range.start);
+                }
+            }
+            Operator::Delete => {
+                self.store(register, copied, linewise, false);
+                let mut range = range;
+                // the last line takes the line break before it along
+                if linewise && range.end == document.len() && range.start > 0 {
+                    range.start -= 1;
+                }
+                editor.edit(
+                    EditKind::Other,
+                    vec![(range.clone(), String::new())],
+                    |_| vec![Selection::caret(range.start)],
+                );
+                match linewise {
+                    true => {
+                        let line = first_line.min(editor.document.line_count() - 1);
+                        set_caret(editor, first_non_blank(&editor.document, line));
+                    }
+                    false => set_caret(editor, range.start),
+                }
+            }
+            Operator::Change => {
+                self.store(register, copied, linewise, false);
+                // changed lines keep their indentation and line break
+                let (range, text) = match linewise {
+                    true => {
+                        let last_line = document.line_of_offset(range.end.saturating_sub(1));
+                        let end = document.line_end(last_line.max(first_line));
+                        (range.start..end, indentation(document, first_line))
+                    }
+                    false => (range, String::new()),
+                };
+                editor.edit(EditKind::Other, vec![(range, text)], |inserted| {
+                    vec![Selection::caret(inserted[0].end)]
+                });
+                self.mode = Mode::Insert;
+            }
+            Operator::Indent | Operator::Outdent => {
+                let last_line =
+                    document.line_of_offset(range.end.saturating_sub(1).max(range.start));
+                editor.shift_lines(first_line..last_line + 1, operator == Operator::Outdent);
+                set_caret(editor, first_non_blank(&editor.document, first_line));
+            }
+        }
+    }
+
+    // deletes and yanks go to the unnamed register and the one given, yanks also to 0
+    fn store(&mut self, register: Option<char>, text: String, linewise: bool, yank: bool) {
+        let entry = Register { text, linewise };
+        match register {
+            Some('_') => return,
+            Some(name) if name.is_ascii_uppercase() => {
+                let appended = self.registers.entry(name.to_ascii_lowercase()).or_default();
+                appended.text.push_str(&entry.text);
+                appended.linewise |= entry.linewise;
+            }
+            Some(name) if name != '"' => {
+                self.registers.insert(name, entry.clone());
+            }
+            _ if yank => {
+                self.registers.insert('0', entry.clone());
+            }
+            _ => {}
+        }
+        self.registers.insert('"', entry);
+    }
+
+    fn put(&mut self, editor: &mut Editor, register: Option<char>, before: bool, count: usize) {
+        let name = register.unwrap_or('"').to_ascii_lowercase();
+        let Some(register) = self.registers.get(&name).cloned() else {
+            editor.set_message(format!("Register {name} is empty"));
+            return;
+        };
+        let text = register.text.repeat(count);
+        let document = &editor.document;
+        let caret = editor.caret();
+        let line = document.line_of_offset(caret);
+
+        if register.linewise {
+            let (at, text) = match (before, line + 1 < document.line_count()) {
+                (true, _) => (document.line_start(line), text),
+                (false, true) => (document.line_start(line + 1), text),
+                // below the last line the line break goes first
+                (false, false) => (document.len(), format!("\n{}", text.trim_end_matches('\n'))),
+            };
+            let first = document.line_of_offset(at) + usize::from(!before && at == document.len());
+            editor.edit(EditKind::Other, vec![(at..at, text)], |_| {
+                vec![Selection::caret(at)]
+            });
+            set_caret(editor, first_non_blank(&editor.document, first));
+        } else {
+            let at = match before || caret == document.line_end(line) {
+                true => caret,
+                false => next(document.text(), caret),
+            };
+            editor.edit(EditKind::Other, vec![(at..at, text)], |inserted| {
+                vec![Selection::caret(inserted[0].end)]
+            });
+            // the caret ends on the last char put
+            let end = editor.caret();
+            set_caret(editor, prev(editor.document.text(), end).max(at));
+        }
+    }
+
+    fn insert(&mut self, editor: &mut Editor, at: InsertAt) {
+        let document = &editor.document;
+        let caret = editor.caret();
+        let line = document.line_of_offset(caret);
+        let offset = match at {
+            InsertAt::Caret => caret,
+            InsertAt::AfterCaret => next(document.text(), caret).min(document.line_end(line)),
+            InsertAt::FirstNonBlank => first_non_blank(document, line),
+            InsertAt::LineEnd => document.line_end(line),
+            InsertAt::LineBelow | InsertAt::LineAbove => {
+                // the new line starts with the indentation of the current one
+                let indent = indentation(document, line);
+                let (at, text, caret) = match at {
+                    InsertAt::LineBelow => {
+                        let at = document.line_end(line);
+                        (at, format!("\n{indent}"), at + 1 + indent.len())
+                    }
+                    _ => {
+                        let at = document.line_start(line);
+                        (at, format!("{indent}\n"), at + indent.len())
+                    }
+                };
+                editor.edit(EditKind::Other, vec![(at..at, text)], |_| {
+                    vec![Selection::caret(caret)]
+                });
+                self.mode = Mode::Insert;
+                return;
+            }
+        };
+        editor.select(Selection::caret(offset));
+        self.mode = Mode::Insert;
+    }
+
+    // join the line with the ones below, count lines in all
+    fn join(&mut self, editor: &mut Editor, count: usize) {
+        let line = editor.caret_line();
+        let joins = count.max(2) - 1;
+        if line + joins >= editor.document.line_count() {
+            return;
+        }
+        let document = &editor.document;
+        let edits: Vec<_> = (line..line + joins)
+            .map(|line| {
+                let end = document.line_end(line);
+                let next_start = first_non_blank(document, line + 1);
+                let space = match document.line(line).ends_with(' ')
+                    || document.line(line + 1).trim().is_empty()
+                {
+                    true => "",
+                    false => " ",
+                };
+                (end..next_start, space.to_string())
+            })
+            .collect();
+        editor.edit(EditKind::Other, edits, |inserted| {
+            vec![Selection::caret(
+                inserted.last().map_or(0, |range| range.start),
+            )]
+        });
+        self.normalize(editor);
+    }
+
+    // replace count chars under the caret with c
+    fn replace(&mut self, editor: &mut Editor, c: char, count: usize) {
+        let document = &editor.document;
+        let text = document.text();
+        let caret = editor.caret();
+        let end = (0..count).try_fold(caret, |at, _| {
+            (at < document.line_end(document.line_of_offset(caret))).then(|| next(text, at))
+        });
+        let Some(end) = end else {
+            return;
+        };
+        editor.edit(
+            EditKind::Other,
+            vec![(caret..end, c.to_string().repeat(count))],
+            |inserted| vec![Selection::caret(inserted[0].end)],
+        );
+        let end = editor.caret();
+        set_caret(editor, prev(editor.document.text(), end));
+    }
+}
+
+// names of the keys in <>, as Vim writes them
+const KEY_NAMES: &[(&str, NamedKey)] = &[
+    ("Esc", NamedKey::Escape),
+    ("CR", NamedKey::Enter),
+    ("BS", NamedKey::Backspace),
+    ("Del", NamedKey::Delete),
+    ("Tab", NamedKey::Tab),
+    ("Space", NamedKey::Space),
+    ("Left", NamedKey::ArrowLeft),
+    ("Right", NamedKey::ArrowRight),
+    ("Up", NamedKey::ArrowUp),
+    ("Down", NamedKey::ArrowDown),
+];
+
+// keys written the way Vim documents them, like d2w or ciw<Esc>, for feeding keys without a
+// window; <C-r> holds Ctrl, <M-y> Alt and <lt> is a literal <
+pub fn strokes(notation: &str) -> Vec<Stroke> {
+    let mut strokes = Vec::new();
+    let mut rest = notation;
+    while let Some(c) = rest.chars().next() {
+        let special = rest
+            .strip_prefix('<')
+            .and_then(|after| after.split_once('>'))
+            .filter(|(name, _)| !name.is_empty() && !name.contains('<'));
+        let (key, modifiers) = match special {
+            Some((name, after)) => {
+                rest = after;
+                let mut modifiers = ModifiersState::empty();
+                let mut name = name;
+                while let Some((held, rest)) =
+                    name.split_once('-').filter(|(_, rest)| !rest.is_empty())
+                {
+                    modifiers |= match held {
+                        "C" => ModifiersState::CONTROL,
+                        "M" | "A" => ModifiersState::ALT,
+                        "S" => ModifiersState::SHIFT,
+                        _ => break,
+                    };
+                    name = rest;
+                }
+                let lower = name.to_lowercase();
+                let key = match KEY_NAMES
+                    .iter()
+                    .find(|(key_name, _)| key_name.eq_ignore_ascii_case(name))
+                {
+                    Some((_, named)) => Key::Named(*named),
+                    None if lower == "enter" => Key::Named(NamedKey::Enter),
+                    None if lower == "lt" => Key::Character("<".into()),
+                    None => Key::Character(name.into()),
+                };
+                (key, modifiers)
+            }
+            None => {
+                rest = &rest[c.len_utf8()..];
+                let key = match c {
+                    ' ' => Key::Named(NamedKey::Space),
+                    '\n' => Key::Named(NamedKey::Enter),
+                    c => Key::Character(c.to_string().into()),
+                };
+                (key, ModifiersState::empty())
+            }
+        };
+        strokes.push(Stroke::logical(key, modifiers));
+    }
+    strokes
+}
+
+// the stroke in the notation of strokes(), None for a key it has no name for
+pub fn notation(stroke: &Stroke) -> Option<String> {
+    let name = match &stroke.key {
+        Key::Character(c) if c.as_str() == "<" => "lt".to_string(),
+        Key::Character(c) => c.to_string(),
+        Key::Named(named) => KEY_NAMES
+            .iter()
+            .find(|(_, key)| key == named)?
+            .0
+            .to_string(),
+        _ => return None,
+    };
+    let mut held = String::new();
+    for (modifier, prefix) in [
+        (ModifiersState::CONTROL, "C-"),
+        (ModifiersState::ALT, "M-"),
+        (ModifiersState::SHIFT, "S-"),
+    ] {
+        if stroke.modifiers.contains(modifier) {
+            held.push_str(prefix);
+        }
+    }
+    Some(match held.is_empty() && name.chars().count() == 1 {
+        true => name,
+        false => format!("<{held}{name}>"),
+    })
+}
+
+// the char a key stands for in normal mode, arrows and such as their hjkl
+fn key_char(key: &Key) -> Option<char> {
+    match key {
+        Key::Character(s) => {
+            let mut chars = s.chars();
+            let c = chars.next()?;
+            chars.next().is_none().then_some(c)
+        }
+        Key::Named(NamedKey::Space) => Some(' '),
+        Key::Named(NamedKey::ArrowLeft) | Key::Named(NamedKey::Backspace) => Some('h'),
+        Key::Named(NamedKey::ArrowRight) => Some('l'),
+        Key::Named(NamedKey::ArrowUp) => Some('k'),
+        Key::Named(NamedKey::ArrowDown) | Key::Named(NamedKey::Enter) => Some('j'),
+        Key::Named(NamedKey::Home) => Some('0'),
+        Key::Named(NamedKey::End) => Some('$'),
+        Key::Named(NamedKey::Delete) => Some('x'),
+        _ => None,
+    }
+}
+
+// ["x][count]command, where an operator takes [count]motion, a text object or itself
+fn parse(keys: &[char], visual: bool) -> Parse<Command> {
+    let mut keys = keys;
+    let mut register = None;
+    if let Some(('"', rest)) = keys.split_first() {
+        let Some((name, rest)) = rest.split_first() else {
+            return Parse::Incomplete;
+        };
+        if !(name.is_ascii_alphanumeric() || *name == '"' || *name == '_') {
+            return Parse::Invalid;
+        }
+        register = Some(*name);
+        keys = rest;
+    }
+    let (count, keys) = parse_count(keys);
+    let Some((&c, rest)) = keys.split_first() else {
+        return Parse::Incomplete;
+    };
+
+    let operator = match c {
+        'd' => Some(Operator::Delete),
+        'c' => Some(Operator::Change),
+        'y' => Some(Operator::Yank),
+        '>' => Some(Operator::Indent),
+        '<' => Some(Operator::Outdent),
+        _ => None,
+    };
+    let action = match (operator, visual) {
+        (Some(operator), true) => done(rest, Action::Operate(operator, Target::Selection)),
+        (Some(operator), false) => match parse_target(rest, c) {
+            Parse::Done((target, inner)) => {
+                let count = match (count, inner) {
+                    (Some(a), Some(b)) => Some(a * b),
+                    (a, b) => a.or(b),
+                };
+                return Parse::Done(Command {
+                    register,
+                    count,
+                    action: Action::Operate(operator, target),
+                });
+            }
+            Parse::Incomplete => return Parse::Incomplete,
+            Parse::Invalid => return Parse::Invalid,
+        },
+        (None, _) => match (c, visual) {
+            ('x', true) => done(rest, Action::Operate(Operator::Delete, Target::Selection)),
+            ('s', true) => done(rest, Action::Operate(Operator::Change, Target::Selection)),
+            ('o', true) => done(rest, Action::SwapEnds),
+            ('i' | 'a', true) => match parse_object(rest) {
+                Parse::Done(object) => Parse::Done(Action::SelectObject {
+                    object,
+                    around: c == 'a',
+                }),
+                Parse::Incomplete => Parse::Incomplete,
+                Parse::Invalid => Parse::Invalid,
+            },
+            ('x', false) => done(rest, delete(Motion::Right)),
+            ('X', false) => done(rest, delete(Motion::Left)),
+            ('D', false) => done(rest, delete(Motion::LineEnd)),
+            ('C', false) => done(
+                rest,
+                Action::Operate(Operator::Change, Target::Motion(Motion::LineEnd)),
+            ),
+            ('s', false) => done(
+                rest,
+                Action::Operate(Operator::Change, Target::Motion(Motion::Right)),
+            ),
+            ('S', false) => done(rest, Action::Operate(Operator::Change, Target::Lines)),
+            ('Y', false) => done(rest, Action::Operate(Operator::Yank, Target::Lines)),
+            ('i', false) => done(rest, Action::Insert(InsertAt::Caret)),
+            ('a', false) => done(rest, Action::Insert(InsertAt::AfterCaret)),
+            ('I', false) => done(rest, Action::Insert(InsertAt::FirstNonBlank)),
+            ('A', false) => done(rest, Action::Insert(InsertAt::LineEnd)),
+            ('o', false) => done(rest, Action::Insert(InsertAt::LineBelow)),
+            ('O', false) => done(rest, Action::Insert(InsertAt::LineAbove)),
+            ('p', false) => done(rest, Action::Put { before: false }),
+            ('P', false) => done(rest, Action::Put { before: true }),
+            ('J', false) => done(rest, Action::Join),
+            ('u', false) => done(rest, Action::Undo),
+            ('.', false) => done(rest, Action::Repeat),
+            ('r', false) => match rest {
+                [] => Parse::Incomplete,
+                [c] => Parse::Done(Action::Replace(*c)),
+                _ => Parse::Invalid,
+            },
+            ('v', _) => done(rest, Action::Visual { linewise: false }),
+            ('V', _) => done(rest, Action::Visual { linewise: true }),
+            (':', _) => done(rest, Action::Ex),
+            _ => match parse_motion(keys) {
+                Parse::Done(motion) => Parse::Done(Action::Move(motion)),
+                Parse::Incomplete => Parse::Incomplete,
+                Parse::Invalid => Parse::Invalid,
+            },
+        },
+    };
+    match action {
+        Parse::Done(action) => Parse::Done(Command {
+            register,
+            count,
+            action,
+        }),
+        Parse::Incomplete => Parse::Incomplete,
+        Parse::Invalid => Parse::Invalid,
+    }
+}
+
+fn done(rest: &[char], action: Action) -> Parse<Action> {
+    match rest.is_empty() {
+        true => Parse::Done(action),
+        false => Parse::Invalid,
+    }
+}
+
+fn delete(motion: Motion) -> Action {
+    Action::Operate(Operator::Delete, Target::Motion(motion))
+}
+
+// a count does not start with 0, which is the line start motion
+fn parse_count(keys: &[char]) -> (Option<usize>, &[char]) {
+    let digits = match keys.first() {
+        Some('1'..='9') => keys.iter().take_while(|c| c.is_ascii_digit()).count(),
+        _ => 0,
+    };
+    let count = keys[..digits].iter().collect::<String>().parse().ok();
+    (count, &keys[digits..])
+}
+
+// what follows an operator and the count given after it
+fn parse_target(keys: &[char], operator: char) -> Parse<(Target, Option<usize>)> {
+    let (count, keys) = parse_count(keys);
+    let target = match keys {
+        [] => return Parse::Incomplete,
+        [c] if *c == operator => Parse::Done(Target::Lines),
+        [c @ ('i' | 'a'), rest @ ..] => match parse_object(rest) {
+            Parse::Done(object) => Parse::Done(Target::Object {
+                object,
+                around: *c == 'a',
+            }),
+            Parse::Incomplete => Parse::Incomplete,
+            Parse::Invalid => Parse::Invalid,
+        },
+        keys => match parse_motion(keys) {
+            Parse::Done(motion) => Parse::Done(Target::Motion(motion)),
+            Parse::Incomplete => Parse::Incomplete,
+            Parse::Invalid => Parse::Invalid,
+        },
+    };
+    match target {
+        Parse::Done(target) => Parse::Done((target, count)),
+        Parse::Incomplete => Parse::Incomplete,
+        Parse::Invalid => Parse::Invalid,
+    }
+}
+
+fn parse_motion(keys: &[char]) -> Parse<Motion> {
+    let motion = match keys {
+        [] | ['g'] => return Parse::Incomplete,
+        ['f' | 't' | 'F' | 'T'] => return Parse::Incomplete,
+        ['f' | 't' | 'F' | 'T', c] => Motion::Find(Find {
+            c: *c,
+            forward: matches!(keys[0], 'f' | 't'),
+            till: matches!(keys[0], 't' | 'T'),
+        }),
+        ['g', 'g'] => Motion::FirstLine,
+        ['h'] => Motion::Left,
+        ['l' | ' '] => Motion::Right,
+        ['k'] => Motion::Up,
+        ['j'] => Motion::Down,
+        ['w'] => Motion::WordStart { big: false },
+        ['W'] => Motion::WordStart { big: true },
+        ['e'] => Motion::WordEnd { big: false },
+        ['E'] => Motion::WordEnd { big: true },
+        ['b'] => Motion::WordBack { big: false },
+        ['B'] => Motion::WordBack { big: true },
+        ['0'] => Motion::LineStart,
+        ['^'] => Motion::FirstNonBlank,
+        ['$'] => Motion::LineEnd,
+        ['G'] => Motion::LastLine,
+        ['}'] => Motion::ParagraphForward,
+        ['{'] => Motion::ParagraphBack,
+        [';'] => Motion::RepeatFind { reverse: false },
+        [','] => Motion::RepeatFind { reverse: true },
+        _ => return Parse::Invalid,
+    };
+    Parse::Done(motion)
+}
+
+fn parse_object(keys: &[char]) -> Parse<Object> {
+    let object = match keys {
+        [] => return Parse::Incomplete,
+        ['w'] => Object::Word { big: false },
+        ['W'] => Object::Word { big: true },
+        ['p'] => Object::Paragraph,
+        [c @ ('"' | '\'' | '`')] => Object::Quote(*c),
+        ['(' | ')' | 'b'] => Object::Pair('(', ')'),
+        ['[' | ']'] => Object::Pair('[', ']'),
+        ['{' | '}' | 'B'] => Object::Pair('{', '}'),
+        ['<' | '>'] => Object::Pair('<', '>'),
+        _ => return Parse::Invalid,
+    };
+    Parse::Done(object)
+}
+
+// collapse to a caret kept on a char rather than after the last one of the line
+fn set_caret(editor: &mut Editor, offset: usize) {
+    let document = &editor.document;
+    let offset = offset.min(document.len());
+    let line = document.line_of_offset(offset);
+    let (start, end) = (document.line_start(line), document.line_end(line));
+    let offset = match offset >= end && end > start {
+        true => prev(document.text(), end),
+        false => offset,
+    };
+    editor.select(Selection::caret(offset));
+}
+
+fn char_at(text: &str, offset: usize) -> Option<char> {
+    text.get(offset..)?.chars().next()
+}
+
+fn next(text: &str, offset: usize) -> usize {
+    char_at(text, offset).map_or(offset, |c| offset + c.len_utf8())
+}
+
+fn prev(text: &str, offset: usize) -> usize {
+    text[..offset]
+        .char_indices()
+        .next_back()
+        .map_or(0, |(i, _)| i)
+}
+
+// 0 for whitespace, words and punctuation otherwise; big words are any non-blank run
+fn class(c: char, big: bool) -> u8 {
+    match c {
+        c if c.is_whitespace() => 0,
+        _ if big => 1,
+        c if c.is_alphanumeric() || c == '_' => 1,
+        _ => 2,
+    }
+}
+
+fn is_empty_line(text: &str, offset: usize) -> bool {
+    char_at(text, offset) == Some('\n') && (offset == 0 || text.as_bytes()[offset - 1] == b'\n')
+}
+
+// start of the next word, an empty line counts as one
+fn word_start(text: &str, from: usize, big: bool) -> usize {
+    let mut at = from;
+    if let Some(c) = char_at(text, at).filter(|c| !c.is_whitespace()) {
+        let run = class(c, big);
+        while char_at(text, at).is_some_and(|c| class(c, big) == run) {
+            at = next(text, at);
+        }
+    }
+    while let Some(c) = char_at(text, at).filter(|c| c.is_whitespace()) {
+        if at != from && is_empty_line(text, at) {
+            break;
+        }
+        at += c.len_utf8();
+    }
+    at
+}
+
+// last char of the word at or after the one following the offset
+fn word_end(text: &str, from: usize, big: bool) -> usize {
+    let mut at = next(text, from);
+    while let Some(c) = char_at(text, at).filter(|c| c.is_whitespace()) {
+        at += c.len_utf8();
+    }
+    let Some(run) = char_at(text, at).map(|c| class(c, big)) else {
+        return prev(text, text.len()).max(from);
+    };
+    while char_at(text, next(text, at)).is_some_and(|c| class(c, big) == run) {
+        at = next(text, at);
+    }
+    at
+}
+
+// start of the word before the offset, an empty line counts as one
+fn word_back(text: &str, from: usize, big: bool) -> usize {
+    let mut at = from;
+    while at > 0 {
+        let before = prev(text, at);
+        let c = char_at(text, before).unwrap_or(' ');
+        if !c.is_whitespace() {
+            break;
+        }
+        at = before;
+        if is_empty_line(text, at) {
+            return at;
+        }
+    }
+    let Some(run) = text[..at].chars().next_back().map(|c| class(c, big)) else {
+        return 0;
+    };
+    while text[..at]
+        .chars()
+        .next_back()
+        .is_some_and(|c| class(c, big) == run)
+    {
+        at = prev(text, at);
+    }
+    at
+}
+
+fn is_blank(document: &TextDocument, line: usize) -> bool {
+    document.line(line).trim().is_empty()
+}
+
+// the next blank line after a paragraph, or the document edge
+fn paragraph(document: &TextDocument, from: usize, forward: bool) -> usize {
+    let mut line = document.line_of_offset(from);
+    let last = document.line_count() - 1;
+    let step = |line: usize| match forward {
+        true => (line < last).then(|| line + 1),
+        false => line.checked_sub(1),
+    };
+    while let Some(next) = step(line).filter(|_| is_blank(document, line)) {
+        line = next;
+    }
+    while let Some(next) = step(line) {
+        line = next;
+        if is_blank(document, line) {
+            return document.line_start(line);
+        }
+    }
+    match forward {
+        true => document.len(),
+        false => 0,
+    }
+}
+
+fn find_char(document: &TextDocument, from: usize, find: Find, times: usize) -> Option<usize> {
+    let text = document.text();
+    let line = document.line_of_offset(from);
+    let (start, end) = (document.line_start(line), document.line_end(line));
+    let found = match find.forward {
+        true => text[next(text, from).min(end)..end]
+            .match_indices(find.c)
+            .nth(times - 1)
+            .map(|(i, _)| next(text, from) + i),
+        false => text[start..from]
+            .rmatch_indices(find.c)
+            .nth(times - 1)
+            .map(|(i, _)| start + i),
+    }?;
+    Some(match (find.till, find.forward) {
+        (true, true) => prev(text, found),
+        (true, false) => next(text, found),
+        _ => found,
+    })
+}
+
+fn first_non_blank(document: &TextDocument, line: usize) -> usize {
+    document.line_start(line) + indentation(document, line).len()
+}
+
+fn indentation(document: &TextDocument, line: usize) -> String {
+    indent::leading(document.line(line)).to_string()
+}
+
+// whole lines including the line break after the last one
+fn lines_range(document: &TextDocument, first: usize, last: usize) -> Range<usize> {
+    let end = match last + 1 < document.line_count() {
+        true => document.line_start(last + 1),
+        false => document.len(),
+    };
+    document.line_start(first)..end
+}
+
+fn object_span(
+    document: &TextDocument,
+    offset: usize,
+    object: Object,
+    around: bool,
+) -> Option<Span> {
+    let text = document.text();
+    let line = document.line_of_offset(offset);
+    let (start, end) = (document.line_start(line), document.line_end(line));
+    // whitespace right after the range, or before it when there is none after
+    let with_space = |range: Range<usize>| {
+        let after = text[range.end..end].len() - text[range.end..end].trim_start().len();
+        match after {
+            0 => {
+                let before =
+                    text[start..range.start].len() - text[start..range.start].trim_end().len();
+                range.start - before..range.end
+            }
+            after => range.start..range.end + after,
+        }
+    };
+
+    match object {
+        Object::Word { big } => {
+            let at = match offset >= end {
+                true if end > start => prev(text, end),
+                true => return None,
+                false => offset,
+            };
+            let run = class(char_at(text, at)?, big);
+            let same = |c: char| class(c, big) == run;
+            let word_start = start
+                + text[start..at]
+                    .char_indices()
+                    .rev()
+                    .take_while(|(_, c)| same(*c))
+                    .last()
+                    .map_or(at - start, |(i, _)| i);
+            let word_end = at + text[at..end].find(|c| !same(c)).unwrap_or(end - at);
+            let range = word_start..word_end;
+            Some(Span::Chars(match (around, run) {
+                (false, _) => range,
+                // on whitespace, the word after it
+                (true, 0) => {
+                    let following = word_start_in_line(text, word_end, end, big);
+                    word_start..following
+                }
+                (true, _) => with_space(range),
+            }))
+        }
+        Object::Quote(quote) => {
+            let quotes: Vec<usize> = text[start..end]
+                .match_indices(quote)
+                .map(|(i, _)| start + i)
+                .filter(|&i| i == start || text.as_bytes()[i - 1] != b'\\')
+                .collect();
+            let (open, close) = quotes
+                .chunks_exact(2)
+                .map(|pair| (pair[0], pair[1]))
+                .find(|&(open, close)| open <= offset && offset <= close)
+                .or_else(|| {
+                    quotes
+                        .chunks_exact(2)
+                        .map(|pair| (pair[0], pair[1]))
+                        .find(|&(open, _)| open > offset)
+                })?;
+            Some(Span::Chars(match around {
+                true => with_space(open..close + 1),
+                false => open + 1..close,
+            }))
+        }
+        Object::Pair(open, close) => {
+            let open_at = match char_at(text, offset) == Some(open) {
+                true => offset,
+                false => {
+                    let mut depth = 0;
+                    text[..offset].char_indices().rev().find_map(|(i, c)| {
+                        if c == close {
+                            depth += 1;
+                        } else if c == open {
+                            if depth == 0 {
+                                return Some(i);
+                            }
+                            depth -= 1;
+                        }
+                        None
+                    })?
+                }
+            };
+            let inner_start = open_at + open.len_utf8();
+            let mut depth = 0;
+            let close_at = text[inner_start..].char_indices().find_map(|(i, c)| {
+                if c == open {
+                    depth += 1;
+                } else if c == close {
+                    if depth == 0 {
+                        return Some(inner_start + i);
+                    }
+                    depth -= 1;
+                }
+                None
+            })?;
+            if around {
+                return Some(Span::Chars(open_at..close_at + close.len_utf8()));
+            }
+            // a block spanning lines keeps the lines of its brackets
+            let mut inner = inner_start..close_at;
+            if text[inner.clone()].contains('\n') {
+                if text[inner.clone()].starts_with('\n') {
+                    inner.start += 1;
+                }
+                if let Some(last_break) = text[inner.clone()].rfind('\n') {
+                    if text[inner.start + last_break + 1..inner.end]
+                        .trim()
+                        .is_empty()
+                    {
+                        inner.end = inner.start + last_break + 1;
+                    }
+                }
+            }
+            Some(Span::Chars(inner))
+        }
+        Object::Paragraph => {
+            let blank = is_blank(document, line);
+            let last = document.line_count() - 1;
+            let mut first = line;
+            while first > 0 && is_blank(document, first - 1) == blank {
+                first -= 1;
+            }
+            let mut final_line = line;
+            while final_line < last && is_blank(document, final_line + 1) == blank {
+                final_line += 1;
+            }
+            if around {
+                // the blank lines after a paragraph, or before it at the end
+                let mut after = final_line;
+                while after < last && is_blank(document, after + 1) != blank {
+                    after += 1;
+                }
+                if after > final_line {
+                    final_line = after;
+                } else {
+                    while first > 0 && is_blank(document, first - 1) != blank {
+                        first -= 1;
+                    }
+                }
+            }
+            Some(Span::Lines(first, final_line))
+        }
+    }
+}
+
+// past the whitespace and then the word after the offset, staying on the line
+fn word_start_in_line(text: &str, from: usize, end: usize, big: bool) -> usize {
+    let mut at = from;
+    while at < end && char_at(text, at).is_some_and(char::is_whitespace) {
+        at = next(text, at);
+    }
+    let Some(run) = char_at(text, at)
+        .filter(|_| at < end)
+        .map(|c| class(c, big))
+    else {
+        return at;
+    };
+    while at < end && char_at(text, at).is_some_and(|c| class(c, big) == run) {
+        at = next(text, at);
+    }
+    at
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::editor::Indentation;
+    use winit::keyboard::KeyCode;
+
+    // feed the keys to Vim, typing the ones it passes on as the editor would; returns the
+    // commands the ex commands asked for
+    fn feed(editor: &mut Editor, vim: &mut Vim, keys: &str) -> Vec<&'static str> {
+        feed_strokes(editor, vim, strokes(keys))
+    }
+
+    fn feed_strokes(
+        editor: &mut Editor,
+        vim: &mut Vim,
+        mut queue: Vec<Stroke>,
+    ) -> Vec<&'static str> {
+        let mut ran = Vec::new();
+        queue.reverse();
+        while let Some(stroke) = queue.pop() {
+            match vim.handle_key(editor, &stroke) {
+                Response::Pass => match &stroke.key {
+                    Key::Character(text) => editor.insert_text(text),
+                    Key::Named(NamedKey::Space) => editor.insert_text(" "),
+                    Key::Named(NamedKey::Enter) => editor.newline(),
+                    key if keymap::is_modifier(key) => {}
+                    key => panic!("unexpected key {key:?}"),
+                },
+                Response::Handled => {}
+                Response::Run(ids) => ran.extend(ids),
+                Response::Repeat(strokes) => queue.extend(strokes.into_iter().rev()),
+            }
+        }
+        ran
+    }
+
+    fn editor(text: &str, caret: usize) -> (Editor, Vim) {
+        let mut editor = Editor::new(TextDocument::from_text(text), Indentation::default());
+        editor.set_viewport(20, None);
+        editor.select(Selection::caret(caret));
+        let mut vim = Vim::default();
+        vim.set_enabled(true);
+        (editor, vim)
+    }
+
+    // the text after the keys and the caret offset
+    fn after(text: &str, caret: usize, keys: &str) -> (String, usize) {
+        let (mut editor, mut vim) = editor(text, caret);
+        feed(&mut editor, &mut vim, keys);
+        (editor.document.text().to_string(), editor.caret())
+    }
+
+    #[test]
+    fn modifier_presses_leave_operators_pending() {
+        let (mut editor, mut vim) = editor("one two\nthree", 4);
+        let shift = Stroke::new(
+            Key::Named(NamedKey::Shift),
+            PhysicalKey::Code(KeyCode::ShiftLeft),
+            ModifiersState::SHIFT,
+        );
+        let mut keys = strokes("d");
+        keys.push(shift.clone());
+        keys.extend(strokes("$"));
+        feed_strokes(&mut editor, &mut vim, keys);
+        assert_eq!(editor.document.text(), "one \nthree");
+
+        // nor do they break into what . repeats
+        let mut keys = strokes("j0");
+        keys.push(shift);
+        keys.extend(strokes("."));
+        feed_strokes(&mut editor, &mut vim, keys);
+        assert_eq!(editor.document.text(), "one \n");
+    }
+
+    #[test]
+    fn operators_take_motions() {
+        assert_eq!(
+            after("one two three four", 0, "d2w"),
+            ("three four".into(), 0)
+        );
+        assert_eq!(
+            after("one two three", 4, "cwsix<Esc>"),
+            ("one six three".into(), 6)
+        );
+        assert_eq!(after("abc def", 0, "d$"), ("".into(), 0));
+        assert_eq!(after("a\nb\nc\nd", 0, "2dd"), ("c\nd".into(), 0));
+    }
+
+    #[test]
+    fn operators_take_text_objects() {
+        assert_eq!(after("f(a, b) + 1", 3, "ci(x<Esc>"), ("f(x) + 1".into(), 2));
+        assert_eq!(
+            after("say \"hi there\" now", 7, "da\""),
+            ("say now".into(), 4)
+        );
+        assert_eq!(after("a\nb\n\nc\nd", 2, "dip"), ("\nc\nd".into(), 0));
+    }
+
+    #[test]
+    fn counts_repeat_motions_and_edits() {
+        assert_eq!(after("abcdef", 0, "3lx"), ("abcef".into(), 3));
+        assert_eq!(after("abcdef", 0, "2x"), ("cdef".into(), 0));
+        assert_eq!(after("a\nb\nc\nd", 0, "2jx"), ("a\nb\n\nd".into(), 4));
+    }
+
+    #[test]
+    fn registers_keep_what_was_yanked() {
+        let (text, _) = after("one\ntwo\nthree", 0, "\"ayyjdd\"ap");
+        assert_eq!(text, "one\nthree\none");
+        // the unnamed register got the deleted line
+        let (text, _) = after("one\ntwo\nthree", 0, "\"ayyjddp");
+        assert_eq!(text, "one\nthree\ntwo");
+    }
+
+    #[test]
+    fn dot_repeats_the_last_change() {
+        assert_eq!(after("a b c d", 0, "dw.."), ("d".into(), 0));
+        assert_eq!(after("x\ny\nz", 0, "Ai<Esc>j."), ("xi\nyi\nz".into(), 4));
+    }
+
+    #[test]
+    fn visual_line_indents_the_lines() {
+        let (text, _) = after("a\nb\nc", 0, "Vj>");
+        assert_eq!(text, "    a\n    b\nc");
+    }
+
+    #[test]
+    fn notation_reads_back_as_the_same_keys() {
+        for keys in ["d2w", "ci(x<Esc>", "<lt>:w<CR>", "<C-r><M-y><Space><S-Tab>"] {
+            let written: String = strokes(keys)
+                .iter()
+                .map(|stroke| notation(stroke).unwrap())
+                .collect();
+            assert_eq!(written, keys);
+        }
+    }
+
+    #[test]
+    fn ex_commands_map_to_editor_commands() {
+        let (mut editor, mut vim) = editor("a\nb\nc", 0);
+        assert_eq!(feed(&mut editor, &mut vim, ":w<CR>"), vec!["file.save"]);
+        assert_eq!(feed(&mut editor, &mut vim, ":q<CR>"), vec!["buffer.close"]);
+        assert_eq!(
+            feed(&mut editor, &mut vim, ":wq<CR>"),
+            vec!["file.save", "buffer.close"]
+        );
+        assert_eq!(feed(&mut editor, &mut vim, ":qa<CR>"), vec!["app.quit"]);
+        assert_eq!(
+            feed(&mut editor, &mut vim, ":qa!<CR>"),
+            vec!["app.force_quit"]
+        );
+        assert!(feed(&mut editor, &mut vim, ":3<CR>").is_empty());
+        assert_eq!(editor.caret_line(), 2);
+        assert_eq!(vim.mode(), Mode::Normal);
+    }
+}
@@ -0,0 +1,49 @@
+use std::ops::Range;
+
+use crate::columns::{char_columns, line_columns};
+
+// one visual row of the viewport, a whole line or a soft-wrapped piece of one
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct Row {
+    pub line: usize,
+    // document byte range, excluding the line break
+    pub range: Range<usize>,
+    // soft-wrapped continuation of the previous row
+    pub continuation: bool,
+    // the last row of a line with lines folded under it
+    pub folded: bool,
+}
+
+// split a line starting at document offset `start` into rows of at most `columns` visual
+// columns, breaking after whitespace where possible; tabs stop relative to the row start
+pub fn wrap_line(
+    text: &str,
+    start: usize,
+    columns: Option<usize>,
+    tab_width: usize,
+) -> Vec<Range<usize>> {
+    let Some(columns) = columns.filter(|columns| *columns > 0) else {
+        return std::iter::once(start..start + text.len()).collect();
+    };
+
+    let mut rows = Vec::new();
+    let mut row_start = 0;
+    let mut count = 0;
+    let mut last_break = None;
+    for (i, c) in text.char_indices() {
+        let width = char_columns(c, count, tab_width);
+        if count > 0 && count + width > columns {
+            let end = last_break.unwrap_or(i);
+            rows.push(start + row_start..start + end);
+            count = line_columns(&text[end..i], tab_width);
+            row_start = end;
+            last_break = None;
+        }
+        count += char_columns(c, count, tab_width);
+        if c.is_whitespace() {
+            last_break = Some(i + c.len_utf8());
+        }
+    }
+    rows.push(start + row_start..start + text.len());
+    rows
+}